@@ -0,0 +1,70 @@
+// Compares `run_frame()` with and without the block cache. Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use libchipolata::chip8::Interpreter;
+
+const SPACE_INVADERS: &[u8] = include_bytes!("../docs/space-invaders.ch8");
+const SPEED: u8 = 200;
+const FRAMES: usize = 100;
+
+// Space Invaders, past the title screen so that the game is running.
+fn space_invaders(block_cache: bool) -> Interpreter {
+    let mut interpreter = Interpreter::with_seed(SPACE_INVADERS.to_vec(), 0).unwrap();
+    interpreter.set_block_cache(block_cache);
+
+    for frame in 0..300 {
+        let mut keypad = [false; 16];
+        keypad[5] = frame % 20 < 10;
+        interpreter.update_keypad(keypad);
+        interpreter.run_frame(SPEED).unwrap();
+    }
+
+    interpreter
+}
+
+// A loop of arithmetic instructions, without any drawing.
+fn arithmetic(block_cache: bool) -> Interpreter {
+    let mut rom = Vec::new();
+    for x in 0..15u16 {
+        // 7X01: VX += 1
+        rom.extend_from_slice(&(0x7001 | x << 8).to_be_bytes());
+        // 8XY4: VX += VY, with Y = X + 1
+        rom.extend_from_slice(&(0x8004 | x << 8 | (x + 1) << 4).to_be_bytes());
+    }
+    // 1200: jump to the start
+    rom.extend_from_slice(&[0x12, 0x00]);
+
+    let mut interpreter = Interpreter::with_seed(rom, 0).unwrap();
+    interpreter.set_block_cache(block_cache);
+    interpreter
+}
+
+fn run_frames(c: &mut Criterion) {
+    bench_rom(c, "space_invaders", space_invaders);
+    bench_rom(c, "arithmetic", arithmetic);
+}
+
+fn bench_rom(c: &mut Criterion, rom: &str, interpreter: fn(bool) -> Interpreter) {
+    let mut group = c.benchmark_group(rom);
+
+    for (name, block_cache) in [("interpreter", false), ("block_cache", true)] {
+        let interpreter = interpreter(block_cache);
+
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || interpreter.clone(),
+                |interpreter| {
+                    for _ in 0..FRAMES {
+                        interpreter.run_frame(SPEED).unwrap();
+                    }
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, run_frames);
+criterion_main!(benches);
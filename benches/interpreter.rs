@@ -0,0 +1,133 @@
+// Measures how fast the interpreter runs: instructions per second with `step()` on a game and on
+// synthetic opcode mixes, the time to run a frame and the cost of snapshots. Run with `cargo
+// bench --bench interpreter`, Criterion compares the results with those of the previous run.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use libchipolata::chip8::{Interpreter, Snapshot};
+
+const SPACE_INVADERS: &[u8] = include_bytes!("../docs/space-invaders.ch8");
+const STEPS: usize = 10_000;
+const SPEED: u8 = 9;
+
+// Space Invaders, past the title screen so that the game is running.
+fn space_invaders() -> Interpreter {
+    let mut interpreter = Interpreter::with_seed(SPACE_INVADERS.to_vec(), 0).unwrap();
+
+    for frame in 0..300 {
+        let mut keypad = [false; 16];
+        keypad[5] = frame % 20 < 10;
+        interpreter.update_keypad(keypad);
+        interpreter.run_frame(SPEED).unwrap();
+    }
+
+    interpreter
+}
+
+// Creates an interpreter that runs `opcodes` in a loop.
+fn synthetic(opcodes: &[u16]) -> Interpreter {
+    let mut rom: Vec<u8> = opcodes.iter().flat_map(|op| op.to_be_bytes()).collect();
+    // 1200: jump to the start
+    rom.extend_from_slice(&[0x12, 0x00]);
+
+    Interpreter::with_seed(rom, 0).unwrap()
+}
+
+fn roms() -> Vec<(&'static str, Interpreter)> {
+    vec![
+        ("space_invaders", space_invaders()),
+        (
+            "arithmetic",
+            synthetic(&[
+                0x6005, // V0 = 5
+                0x7101, // V1 += 1
+                0x8214, // V2 += V1
+                0x8305, // V3 -= V0
+                0x8416, // V4 = V1 >> 1
+                0x8512, // V5 &= V1
+                0xC6FF, // V6 = random
+            ]),
+        ),
+        (
+            "memory",
+            synthetic(&[
+                0xA300, // I = 0x300
+                0xF355, // store V0..V3
+                0xF365, // load V0..V3
+                0xF033, // store the BCD of V0
+                0x7001, // V0 += 1
+                0xF01E, // I += V0
+            ]),
+        ),
+        (
+            "draw",
+            synthetic(&[
+                0xF029, // I = font sprite of V0
+                0xD125, // draw 5 rows at (V1, V2)
+                0x7001, // V0 += 1
+                0x7105, // V1 += 5
+                0x7203, // V2 += 3
+            ]),
+        ),
+        (
+            "calls",
+            synthetic(&[
+                0x2204, // call 0x204
+                0x1200, // jump to the start
+                0x7001, // V0 += 1
+                0x00EE, // return
+            ]),
+        ),
+    ]
+}
+
+fn step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step");
+    group.throughput(Throughput::Elements(STEPS as u64));
+
+    for (name, mut interpreter) in roms() {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for _ in 0..STEPS {
+                    interpreter.step().unwrap();
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn run_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("run_frame");
+    group.throughput(Throughput::Elements(1));
+
+    for speed in [SPEED, 100, 255] {
+        let mut interpreter = space_invaders();
+        group.bench_function(format!("speed_{}", speed), |b| {
+            b.iter(|| interpreter.run_frame(speed).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn snapshot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot");
+    let mut interpreter = space_invaders();
+    let snapshot = interpreter.save_state();
+    let bytes = snapshot.to_bytes();
+
+    group.bench_function("save_state", |b| b.iter(|| interpreter.save_state()));
+    group.bench_function("load_state", |b| {
+        b.iter(|| interpreter.load_state(black_box(&snapshot)))
+    });
+    group.bench_function("to_bytes", |b| b.iter(|| black_box(&snapshot).to_bytes()));
+    group.bench_function("from_bytes", |b| {
+        b.iter(|| Snapshot::from_bytes(black_box(&bytes)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, step, run_frame, snapshot);
+criterion_main!(benches);
@@ -0,0 +1,18 @@
+// Generates the C header of the FFI (see `src/ffi.rs`) when the `ffi` feature is enabled.
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).unwrap();
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{}/src/ffi.rs", crate_dir))
+            .generate()
+            .expect("unable to generate the C header")
+            .write_to_file(format!("{}/include/chipolata.h", crate_dir));
+    }
+}
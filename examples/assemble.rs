@@ -0,0 +1,25 @@
+// Assembles a listing (e.g. written by `chipolata --listing`) into a ROM:
+//
+//   $ cargo run --example assemble -- game.asm game.ch8
+
+use std::env;
+use std::fs;
+use std::process;
+
+use libchipolata::chip8::assembler::assemble;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("Usage: {} <listing> <rom>", args[0]);
+        process::exit(1);
+    }
+    let source = fs::read_to_string(&args[1]).unwrap();
+
+    let rom = assemble(&source).unwrap_or_else(|e| {
+        eprintln!("Cannot assemble {}: {}", args[1], e);
+        process::exit(1);
+    });
+    fs::write(&args[2], &rom).unwrap();
+    println!("Wrote {} bytes to {}", rom.len(), args[2]);
+}
@@ -0,0 +1,40 @@
+// Runs many instances of a ROM in parallel with random key presses and prints the throughput:
+//
+//   $ cargo run --release --example batch -- docs/space-invaders.ch8 1000 600
+//
+// The arguments are the ROM, the number of instances and the number of frames.
+
+use std::env;
+use std::fs;
+use std::process;
+
+use libchipolata::chip8::batch::Batch;
+
+const SPEED: u8 = 9;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: {} <rom> [instances] [frames]", args[0]);
+        process::exit(1);
+    }
+    let rom = fs::read(&args[1]).unwrap();
+    let instances: usize = args.get(2).map(|n| n.parse().unwrap()).unwrap_or(1000);
+    let frames: usize = args.get(3).map(|n| n.parse().unwrap()).unwrap_or(600);
+
+    let mut batch = Batch::with_rom(&rom, instances, 0).unwrap();
+    // Each instance holds a different key for a few frames.
+    let stats = batch.run(frames, SPEED, |index, frame| {
+        let hash = (index * 31 + frame / 10).wrapping_mul(2654435761);
+        1 << (hash % 16)
+    });
+
+    println!(
+        "{} instances, {} frames in {:.2?}: {:.0} frames/s, {:.0} instructions/s",
+        batch.len(),
+        stats.frames,
+        stats.elapsed,
+        stats.frames_per_second(),
+        stats.instructions_per_second()
+    );
+}
@@ -0,0 +1,122 @@
+// Converts the `programs.json` file of the community chip-8-database
+// (https://github.com/chip-8/chip-8-database) into the entries of the table of ROMs in
+// `src/chip8/database.rs`, sorted by SHA-1:
+//
+//   $ cargo run --example import_database -- chip-8-database/database/programs.json > roms.txt
+//
+// Each program has a title and its ROMs keyed by SHA-1, with (all optional) the platforms they
+// run on, from the preferred one, their tickrate, colors and what their keys do:
+//
+//   [{"title": "...", "roms": {"<sha1>": {"platforms": ["originalChip8"], "tickrate": 15,
+//     "colors": {"pixels": ["#000000", "#FFFFFF"]}, "keys": {"left": 4, "right": 6}}}}]
+//
+// The ROMs for the platforms that chipolata does not know (e.g. MEGA-CHIP) are skipped.
+
+use std::env;
+use std::fs;
+use std::process;
+
+use serde_json::Value;
+
+const DEFAULT_TICKRATE: u64 = 9;
+
+// XO-CHIP follows the original behavior, except for VF and the sprites that wrap around.
+const XO_CHIP_QUIRKS: &str = "Quirks {
+            shift_vy: true,
+            load_store_increment_i: true,
+            jump_vx: false,
+            vf_reset: false,
+            clip_sprites: false,
+        }";
+
+// Returns the platform and the quirks of a platform of the database.
+fn platform(id: &str) -> Option<(&'static str, &'static str)> {
+    match id {
+        "originalChip8" | "hybridVIP" => Some(("Platform::Chip8", "Quirks::chip8()")),
+        "modernChip8" => Some(("Platform::Chip8", "Quirks::DEFAULT")),
+        "chip48" | "superchip1" | "superchip" => Some(("Platform::SuperChip", "Quirks::schip()")),
+        "xochip" => Some(("Platform::XoChip", XO_CHIP_QUIRKS)),
+        _ => None,
+    }
+}
+
+fn color(value: &Value) -> Option<u32> {
+    u32::from_str_radix(value.as_str()?.trim_start_matches('#'), 16).ok()
+}
+
+fn entry(title: &str, sha1: &str, rom: &Value) -> Option<String> {
+    let (platform, quirks) = rom["platforms"]
+        .as_array()?
+        .iter()
+        .filter_map(|id| platform(id.as_str()?))
+        .next()?;
+    let tickrate = rom["tickrate"]
+        .as_u64()
+        .unwrap_or(DEFAULT_TICKRATE)
+        .min(255);
+    // The first color is the one of the pixels that are off.
+    let colors = match rom["colors"]["pixels"].as_array() {
+        Some(pixels) if pixels.len() >= 2 => match (color(&pixels[1]), color(&pixels[0])) {
+            (Some(foreground), Some(background)) => {
+                format!("Some((0x{:06X}, 0x{:06X}))", foreground, background)
+            }
+            _ => "None".to_string(),
+        },
+        _ => "None".to_string(),
+    };
+    let mut keys: Vec<(u64, &str)> = rom["keys"]
+        .as_object()
+        .map(|keys| {
+            keys.iter()
+                .filter_map(|(name, key)| Some((key.as_u64()?, name.as_str())))
+                .filter(|(key, _)| *key < 16)
+                .collect()
+        })
+        .unwrap_or_default();
+    keys.sort();
+    let keys: Vec<String> = keys
+        .iter()
+        .map(|(key, name)| format!("(0x{:X}, {:?})", key, name))
+        .collect();
+
+    Some(format!(
+        "    RomInfo {{\n        sha1: {:?},\n        title: {:?},\n        platform: {},\n        \
+        quirks: {},\n        tickrate: {},\n        colors: {},\n        keys: &[{}],\n    }},\n",
+        sha1,
+        title,
+        platform,
+        quirks,
+        tickrate,
+        colors,
+        keys.join(", ")
+    ))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: {} <programs.json>", args[0]);
+        process::exit(1);
+    }
+    let programs: Value = serde_json::from_str(&fs::read_to_string(&args[1]).unwrap())
+        .unwrap_or_else(|e| {
+            eprintln!("Cannot read {}: {}", args[1], e);
+            process::exit(1);
+        });
+
+    let mut entries = Vec::new();
+    for program in programs.as_array().into_iter().flatten() {
+        let title = program["title"].as_str().unwrap_or("Unknown");
+        for (sha1, rom) in program["roms"].as_object().into_iter().flatten() {
+            if let Some(entry) = entry(title, &sha1.to_ascii_lowercase(), rom) {
+                entries.push((sha1.to_ascii_lowercase(), entry));
+            }
+        }
+    }
+    entries.sort();
+
+    for (_, entry) in &entries {
+        print!("{}", entry);
+    }
+    eprintln!("Imported {} ROMs", entries.len());
+}
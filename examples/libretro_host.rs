@@ -0,0 +1,217 @@
+// A minimal libretro frontend that loads the chipolata core, runs a ROM for a number of frames
+// while pressing A (CHIP-8 key 5) from time to time, and checks that restoring a serialized state
+// replays the exact same frames. It is a quick way to test the core without RetroArch:
+//
+//   $ cargo build --features libretro
+//   $ cargo run --features libretro --example libretro_host -- \
+//       target/debug/liblibchipolata.so docs/space-invaders.ch8 600
+
+use libloading::{Library, Symbol};
+use std::env;
+use std::ffi::CStr;
+use std::fs;
+use std::os::raw::{c_char, c_uint, c_void};
+use std::process;
+use std::sync::Mutex;
+
+use libchipolata::chip8;
+use libchipolata::libretro::{
+    RetroAudioSampleBatch, RetroEnvironment, RetroGameInfo, RetroInputPoll, RetroInputState,
+    RetroSystemAvInfo, RetroSystemInfo, RetroVideoRefresh,
+};
+
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_DEVICE_ID_JOYPAD_A: c_uint = 8;
+
+// The state shared with the callbacks.
+struct Host {
+    frame: usize,
+    video: Vec<u32>,
+    audio_frames: usize,
+    pixel_format: Option<c_uint>,
+}
+
+static HOST: Mutex<Host> = Mutex::new(Host {
+    frame: 0,
+    video: Vec::new(),
+    audio_frames: 0,
+    pixel_format: None,
+});
+
+unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match cmd {
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT => {
+            HOST.lock().unwrap().pixel_format = Some(*(data as *const c_uint));
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn video_refresh(
+    data: *const c_void,
+    width: c_uint,
+    height: c_uint,
+    pitch: usize,
+) {
+    let mut host = HOST.lock().unwrap();
+    host.video.clear();
+    for y in 0..height as usize {
+        let row = (data as *const u8).add(y * pitch) as *const u32;
+        host.video
+            .extend_from_slice(std::slice::from_raw_parts(row, width as usize));
+    }
+}
+
+unsafe extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+    HOST.lock().unwrap().audio_frames += frames;
+    frames
+}
+
+unsafe extern "C" fn input_poll() {}
+
+unsafe extern "C" fn input_state(port: c_uint, _device: c_uint, _index: c_uint, id: c_uint) -> i16 {
+    let frame = HOST.lock().unwrap().frame;
+    (port == 0 && id == RETRO_DEVICE_ID_JOYPAD_A && frame % 20 < 5) as i16
+}
+
+fn run(library: &Library, frames: usize) -> Vec<Vec<u32>> {
+    let retro_run: Symbol<unsafe extern "C" fn()> = unsafe { library.get(b"retro_run") }.unwrap();
+
+    let mut video = Vec::new();
+    for _ in 0..frames {
+        unsafe { retro_run() };
+        let mut host = HOST.lock().unwrap();
+        host.frame += 1;
+        video.push(host.video.clone());
+    }
+    video
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("Usage: {} <core> <rom> [frames]", args[0]);
+        process::exit(1);
+    }
+    let rom = fs::read(&args[2]).unwrap();
+    let frames: usize = args.get(3).map(|n| n.parse().unwrap()).unwrap_or(600);
+
+    let library = unsafe { Library::new(&args[1]) }.unwrap();
+    unsafe {
+        let api_version: Symbol<unsafe extern "C" fn() -> c_uint> =
+            library.get(b"retro_api_version").unwrap();
+        println!("API version: {}", api_version());
+
+        let get_system_info: Symbol<unsafe extern "C" fn(*mut RetroSystemInfo)> =
+            library.get(b"retro_get_system_info").unwrap();
+        let mut info: RetroSystemInfo = std::mem::zeroed();
+        get_system_info(&mut info);
+        println!(
+            "Core: {} {} ({})",
+            CStr::from_ptr(info.library_name).to_string_lossy(),
+            CStr::from_ptr(info.library_version).to_string_lossy(),
+            CStr::from_ptr(info.valid_extensions).to_string_lossy()
+        );
+
+        let set_environment: Symbol<unsafe extern "C" fn(RetroEnvironment)> =
+            library.get(b"retro_set_environment").unwrap();
+        set_environment(environment);
+        let set_video_refresh: Symbol<unsafe extern "C" fn(RetroVideoRefresh)> =
+            library.get(b"retro_set_video_refresh").unwrap();
+        set_video_refresh(video_refresh);
+        let set_audio_sample_batch: Symbol<unsafe extern "C" fn(RetroAudioSampleBatch)> =
+            library.get(b"retro_set_audio_sample_batch").unwrap();
+        set_audio_sample_batch(audio_sample_batch);
+        let set_input_poll: Symbol<unsafe extern "C" fn(RetroInputPoll)> =
+            library.get(b"retro_set_input_poll").unwrap();
+        set_input_poll(input_poll);
+        let set_input_state: Symbol<unsafe extern "C" fn(RetroInputState)> =
+            library.get(b"retro_set_input_state").unwrap();
+        set_input_state(input_state);
+
+        let init: Symbol<unsafe extern "C" fn()> = library.get(b"retro_init").unwrap();
+        init();
+
+        let load_game: Symbol<unsafe extern "C" fn(*const RetroGameInfo) -> bool> =
+            library.get(b"retro_load_game").unwrap();
+        let game = RetroGameInfo {
+            path: std::ptr::null(),
+            data: rom.as_ptr() as *const c_void,
+            size: rom.len(),
+            meta: std::ptr::null::<c_char>(),
+        };
+        if !load_game(&game) {
+            eprintln!("The core could not load {}", args[2]);
+            process::exit(1);
+        }
+
+        let get_system_av_info: Symbol<unsafe extern "C" fn(*mut RetroSystemAvInfo)> =
+            library.get(b"retro_get_system_av_info").unwrap();
+        let mut av_info: RetroSystemAvInfo = std::mem::zeroed();
+        get_system_av_info(&mut av_info);
+        println!(
+            "Video: {}x{} at {} fps (pixel format {:?}), audio: {} Hz",
+            av_info.geometry.base_width,
+            av_info.geometry.base_height,
+            av_info.timing.fps,
+            HOST.lock().unwrap().pixel_format,
+            av_info.timing.sample_rate
+        );
+    }
+
+    // Run the first half, save the state, run the second half, then restore the state and run
+    // the second half again: both runs must produce the same frames.
+    run(&library, frames / 2);
+
+    let state = unsafe {
+        let serialize_size: Symbol<unsafe extern "C" fn() -> usize> =
+            library.get(b"retro_serialize_size").unwrap();
+        let serialize: Symbol<unsafe extern "C" fn(*mut c_void, usize) -> bool> =
+            library.get(b"retro_serialize").unwrap();
+        let mut state = vec![0u8; serialize_size()];
+        assert!(serialize(state.as_mut_ptr() as *mut c_void, state.len()));
+        state
+    };
+    let saved_at = HOST.lock().unwrap().frame;
+    let expected = run(&library, frames - frames / 2);
+
+    unsafe {
+        let unserialize: Symbol<unsafe extern "C" fn(*const c_void, usize) -> bool> =
+            library.get(b"retro_unserialize").unwrap();
+        assert!(unserialize(state.as_ptr() as *const c_void, state.len()));
+    }
+    HOST.lock().unwrap().frame = saved_at;
+    let replayed = run(&library, frames - frames / 2);
+
+    let host = HOST.lock().unwrap();
+    println!(
+        "Ran {} frames ({} audio frames), state of {} bytes saved at frame {}",
+        frames + (frames - frames / 2),
+        host.audio_frames,
+        state.len(),
+        saved_at
+    );
+    if expected != replayed {
+        eprintln!("The frames after restoring the state differ");
+        process::exit(1);
+    }
+    println!("The frames after restoring the state are identical");
+
+    let background = host.video[0];
+    for row in host.video.chunks(chip8::WIDTH) {
+        let line: String = row
+            .iter()
+            .map(|pixel| if *pixel == background { '.' } else { '#' })
+            .collect();
+        println!("{}", line);
+    }
+
+    unsafe {
+        let unload_game: Symbol<unsafe extern "C" fn()> =
+            library.get(b"retro_unload_game").unwrap();
+        unload_game();
+        let deinit: Symbol<unsafe extern "C" fn()> = library.get(b"retro_deinit").unwrap();
+        deinit();
+    }
+}
@@ -0,0 +1,108 @@
+#![allow(dead_code)]
+
+// The fuzzing harness, shared by the cargo-fuzz targets and the time-bounded `tests/fuzz.rs` test.
+// Arbitrary input must never panic the interpreter: the only acceptable outcome of a bad ROM is
+// an `InterpreterError`.
+
+use libchipolata::chip8::{Interpreter, InterpreterError, Quirks, Snapshot, MAX_ROM_SIZE};
+
+// The maximum number of instructions executed per input, which keeps each run short.
+const MAX_STEPS: usize = 10_000;
+
+// Runs a ROM with a sequence of key presses. The input is made of:
+//
+//   1 byte      quirks (see `Quirks::to_bits()`)
+//   1 byte      the number N of keypad states
+//   2N bytes    keypad states (bit N is set when key N is pressed), the last one is held until
+//               the end of the run
+//   ...         the ROM
+pub fn run_cpu(data: &[u8]) {
+    if data.len() < 2 {
+        return;
+    }
+    let quirks = Quirks::from_bits(data[0]);
+    let keys_len = (data[1] as usize * 2).min(data.len() - 2);
+    let keys: Vec<u16> = data[2..2 + keys_len]
+        .chunks_exact(2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .collect();
+    let rom = data[2 + keys_len..].to_vec();
+
+    let mut interpreter = match new_interpreter(rom) {
+        Some(interpreter) => interpreter,
+        None => return,
+    };
+    interpreter.set_quirks(quirks);
+
+    for n in 0..MAX_STEPS {
+        let bits = keys
+            .get(n / 8)
+            .or_else(|| keys.last())
+            .copied()
+            .unwrap_or(0);
+        let mut keypad = [false; 16];
+        for (i, pressed) in keypad.iter_mut().enumerate() {
+            *pressed = bits & (1 << i) != 0;
+        }
+        interpreter.update_keypad(keypad);
+
+        if let Err(e) = interpreter.step() {
+            check_halted(&mut interpreter, e);
+            break;
+        }
+        if n % 8 == 7 {
+            interpreter.update_timers();
+        }
+    }
+
+    check_snapshot(&mut interpreter);
+}
+
+// Loads arbitrary bytes as a ROM, then as the content of a snapshot (after its header).
+pub fn run_rom(data: &[u8]) {
+    if let Some(mut interpreter) = new_interpreter(data.to_vec()) {
+        assert!(interpreter.load_rom(data.to_vec()).is_ok());
+
+        let mut bytes = interpreter.save_state().to_bytes();
+        for (byte, value) in bytes[4..].iter_mut().zip(data) {
+            *byte = *value;
+        }
+        if let Ok(snapshot) = Snapshot::from_bytes(&bytes) {
+            interpreter.load_state(&snapshot);
+            for _ in 0..MAX_STEPS / 10 {
+                if let Err(e) = interpreter.step() {
+                    check_halted(&mut interpreter, e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn new_interpreter(rom: Vec<u8>) -> Option<Interpreter> {
+    let len = rom.len();
+    match Interpreter::with_seed(rom, 0) {
+        Ok(interpreter) => {
+            assert!(len <= MAX_ROM_SIZE);
+            Some(interpreter)
+        }
+        Err(e) => {
+            assert_eq!(e, InterpreterError::RomTooLarge(len));
+            None
+        }
+    }
+}
+
+// A failing instruction does not change the state, so stepping again fails the same way.
+fn check_halted(interpreter: &mut Interpreter, error: InterpreterError) {
+    let pc = interpreter.get_pc();
+    assert_eq!(interpreter.step(), Err(error));
+    assert_eq!(interpreter.get_pc(), pc);
+}
+
+fn check_snapshot(interpreter: &mut Interpreter) {
+    let bytes = interpreter.save_state().to_bytes();
+    let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+    interpreter.load_state(&snapshot);
+    assert_eq!(interpreter.save_state().to_bytes(), bytes);
+}
@@ -0,0 +1,176 @@
+#ifndef CHIPOLATA_H
+#define CHIPOLATA_H
+
+/* Generated with cbindgen:0.29.4 */
+
+/* This file is generated by cbindgen (see build.rs), do not edit it. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define CHIPOLATA_WIDTH 64
+
+#define CHIPOLATA_HEIGHT 32
+
+#define CHIPOLATA_STATE_SIZE 6290
+
+typedef struct ChipolataInterpreter ChipolataInterpreter;
+
+typedef struct ChipolataRegisters {
+  uint8_t v[16];
+  uint16_t i;
+  uint16_t pc;
+  uint8_t sp;
+  uint8_t delay;
+  uint8_t sound;
+} ChipolataRegisters;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates an interpreter for a ROM, with a random seed. Returns NULL when the ROM is empty or too
+ * large.
+ *
+ * # Safety
+ *
+ * `rom` must point to `len` readable bytes.
+ */
+struct ChipolataInterpreter *chipolata_new(const uint8_t *rom, size_t len);
+
+/**
+ * Creates an interpreter for a ROM with a fixed seed, so that runs can be reproduced.
+ *
+ * # Safety
+ *
+ * `rom` must point to `len` readable bytes.
+ */
+struct ChipolataInterpreter *chipolata_new_with_seed(const uint8_t *rom, size_t len, uint64_t seed);
+
+/**
+ * # Safety
+ *
+ * `handle` must have been returned by `chipolata_new()` and not freed yet.
+ */
+void chipolata_free(struct ChipolataInterpreter *handle);
+
+/**
+ * Sets the quirks (see `Quirks::to_bits()`).
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL.
+ */
+void chipolata_set_quirks(struct ChipolataInterpreter *handle, uint8_t quirks);
+
+/**
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL.
+ */
+void chipolata_reset(struct ChipolataInterpreter *handle);
+
+/**
+ * Sets the state of the keypad: bit N is set when key N is pressed.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL.
+ */
+void chipolata_update_keypad(struct ChipolataInterpreter *handle, uint16_t keys);
+
+/**
+ * Executes a single instruction. Returns 0 when the ROM fails (e.g. on an unsupported opcode),
+ * in which case the interpreter stays on the faulty instruction.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL.
+ */
+int chipolata_step(struct ChipolataInterpreter *handle);
+
+/**
+ * Decrements the delay and sound timers, which should happen 60 times per second.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL.
+ */
+void chipolata_update_timers(struct ChipolataInterpreter *handle);
+
+/**
+ * Runs a frame, i.e. `speed` instructions then the timers. Returns 1 when the display should be
+ * redrawn, 0 otherwise and -1 when the ROM fails.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL.
+ */
+int chipolata_run_frame(struct ChipolataInterpreter *handle, uint8_t speed);
+
+/**
+ * Returns 1 when the last instruction changed the display.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL.
+ */
+int chipolata_should_redraw(const struct ChipolataInterpreter *handle);
+
+/**
+ * Returns 1 while the sound timer is non-zero.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL.
+ */
+int chipolata_should_beep(const struct ChipolataInterpreter *handle);
+
+/**
+ * Returns the display: `CHIPOLATA_WIDTH * CHIPOLATA_HEIGHT` bytes, row by row, with 1 for the
+ * pixels that are on. The pointer is valid until the handle is freed.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL.
+ */
+const uint8_t *chipolata_get_vram(const struct ChipolataInterpreter *handle);
+
+/**
+ * Copies the registers to `registers`. Returns 0 when `handle` or `registers` is NULL.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL and `registers` must be a valid pointer or NULL.
+ */
+int chipolata_get_registers(const struct ChipolataInterpreter *handle,
+                            struct ChipolataRegisters *registers);
+
+/**
+ * Writes the state of the interpreter to `buffer`, which must hold at least
+ * `CHIPOLATA_STATE_SIZE` bytes. Returns 0 when the buffer is too small.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL and `buffer` must point to `len` writable bytes.
+ */
+int chipolata_save_state(const struct ChipolataInterpreter *handle, uint8_t *buffer, size_t len);
+
+/**
+ * Restores a state written by `chipolata_save_state()`. Returns 0 when the state is invalid.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle or NULL and `buffer` must point to `len` readable bytes.
+ */
+int chipolata_load_state(struct ChipolataInterpreter *handle, const uint8_t *buffer, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIPOLATA_H */
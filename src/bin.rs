@@ -1,72 +1,706 @@
 extern crate minifb;
 extern crate rodio;
 
-use minifb::{Key, Scale, Window, WindowOptions};
-use std::collections::HashSet;
+mod cli;
+
+use minifb::{Key, KeyRepeat, Scale, Window, WindowOptions};
 use std::fs::File;
 use std::io;
 use std::io::Read;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 use structopt::StructOpt;
 
+use cli::audio::BeeperSource;
+use cli::config::{self, Config, Settings};
+use cli::debugger::Debugger;
+use cli::keymap::Keymap;
+use cli::tui;
 use libchipolata::chip8;
+use libchipolata::chip8::analysis::Analysis;
+use libchipolata::chip8::audio::Beeper;
+use libchipolata::chip8::cheats::Cheats;
+use libchipolata::chip8::database;
+use libchipolata::chip8::movie::Movie;
+use libchipolata::chip8::patch;
+use libchipolata::chip8::sprites;
+use libchipolata::chip8::symbols::Symbols;
+
+// The number of subroutines and hot spots in profile reports.
+const PROFILE_LIMIT: usize = 30;
 
 #[derive(StructOpt)]
 struct Cli {
     /// Enable debug mode (debugger).
     #[structopt(short, long)]
     debug: bool,
-    /// The path to a ROM.
+    /// The path to a ROM (required unless a subcommand is given).
     #[structopt(parse(from_os_str))]
-    rom_name: std::path::PathBuf,
-    #[structopt(default_value = "5", long)]
-    speed: u8,
-}
-
-fn read_keypad(window: &Window) -> [bool; 16] {
-    // 1 2 3 C -> 1 2 3 4
-    // 4 5 6 D -> Q W E R
-    // 7 8 9 E -> A S D F
-    // A 0 B F -> Z X C V
-    let keypad: [bool; 16] = [
-        window.is_key_down(Key::X),    // 0
-        window.is_key_down(Key::Key1), // 1
-        window.is_key_down(Key::Key2), // 2
-        window.is_key_down(Key::Key3), // 3
-        window.is_key_down(Key::Q),    // 4
-        window.is_key_down(Key::W),    // 5
-        window.is_key_down(Key::E),    // 6
-        window.is_key_down(Key::A),    // 7
-        window.is_key_down(Key::S),    // 8
-        window.is_key_down(Key::D),    // 9
-        window.is_key_down(Key::Z),    // A
-        window.is_key_down(Key::C),    // B
-        window.is_key_down(Key::Key4), // C
-        window.is_key_down(Key::R),    // D
-        window.is_key_down(Key::F),    // E
-        window.is_key_down(Key::V),    // F
-    ];
-    keypad
+    rom_name: Option<PathBuf>,
+    /// The number of instructions per frame [default: 5].
+    #[structopt(long)]
+    speed: Option<u8>,
+    /// The quirks preset: "default", "chip8" or "schip".
+    #[structopt(long)]
+    quirks: Option<String>,
+    /// The color palette: "default", "green", "amber", "lcd" or "octo".
+    #[structopt(long)]
+    palette: Option<String>,
+    /// Blend recent frames to reduce flicker, like the phosphor of a CRT.
+    #[structopt(long)]
+    phosphor: bool,
+    /// The scale of the window: 1, 2, 4, 8, 16 or 32 [default: 8].
+    #[structopt(long)]
+    scale: Option<u8>,
+    /// Record the keypad state of each frame to a movie file.
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["debug", "play"])]
+    record: Option<PathBuf>,
+    /// Play a movie file back (the speed is read from the movie).
+    #[structopt(long, parse(from_os_str), conflicts_with = "debug")]
+    play: Option<PathBuf>,
+    /// Play the movie back without opening a window, then print the final state.
+    #[structopt(long, requires = "play")]
+    headless: bool,
+    /// Write the sound of the headless playback to a WAV file.
+    #[structopt(long, parse(from_os_str), requires = "headless")]
+    wav: Option<PathBuf>,
+    /// Run in the terminal instead of opening a window.
+    #[structopt(long, conflicts_with_all = &["record", "play"])]
+    tui: bool,
+    /// Draw the display with braille characters instead of half-blocks in the terminal.
+    #[structopt(long, requires = "tui")]
+    braille: bool,
+    /// The path to a configuration file (defaults to config.toml in the user config directory).
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Profile the ROM and write a report of the hot spots to a file when the emulation ends.
+    #[structopt(long, parse(from_os_str))]
+    profile: Option<PathBuf>,
+    /// Profile the ROM and write its call stacks to a file for flame graph tools.
+    #[structopt(long, parse(from_os_str))]
+    folded: Option<PathBuf>,
+    /// Write a JSON map of the bytes executed, read, written and drawn to a file when the
+    /// emulation ends.
+    #[structopt(long, parse(from_os_str))]
+    coverage: Option<PathBuf>,
+    /// Write a listing of the ROM (code and data found by static analysis) to a file and exit.
+    #[structopt(long, parse(from_os_str))]
+    listing: Option<PathBuf>,
+    /// Write the control-flow graph of the ROM in the DOT format of Graphviz to a file and exit.
+    #[structopt(long, parse(from_os_str))]
+    cfg: Option<PathBuf>,
+    /// Write a PNG sheet of the sprites (found by static analysis and drawn during the emulation)
+    /// to a file when the emulation ends.
+    #[structopt(long, parse(from_os_str))]
+    sprites: Option<PathBuf>,
+    /// Write the sprites as source code to a file when the emulation ends: Octo for ".8o" files,
+    /// the assembler of chipolata otherwise.
+    #[structopt(long, parse(from_os_str))]
+    sprite_source: Option<PathBuf>,
+    /// A cheat list ("<address>:<value>" lines) to load, and where the debugger saves the cheats
+    /// [default: cheats/<SHA-1 of the ROM>.txt in the user config directory].
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["record", "play"])]
+    cheats: Option<PathBuf>,
+    /// Apply an IPS or BPS patch to the ROM (can be repeated, the patches are applied in order).
+    #[structopt(long, parse(from_os_str), number_of_values = 1)]
+    patch: Vec<PathBuf>,
+    /// A symbols file ("<address> <name>" lines) to annotate the profile and the listing.
+    #[structopt(long, parse(from_os_str))]
+    symbols: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+// The subcommands run without opening a window, with the options given before them (e.g.
+// `chipolata --speed 10 sprites game.ch8 game.png`).
+#[derive(StructOpt)]
+enum Command {
+    /// Run a ROM for a number of frames without pressing any key, then write a PNG sheet of its
+    /// sprites (found by static analysis and drawn during the run).
+    Sprites {
+        /// The path to a ROM.
+        #[structopt(parse(from_os_str))]
+        rom_name: PathBuf,
+        /// The PNG sheet to write.
+        #[structopt(parse(from_os_str))]
+        sheet: PathBuf,
+        /// Write the sprites as source code to a file too: Octo for ".8o" files, the assembler of
+        /// chipolata otherwise.
+        #[structopt(long, parse(from_os_str))]
+        source: Option<PathBuf>,
+        /// The number of frames to run, 0 for the sprites found by static analysis only.
+        #[structopt(long, default_value = "600")]
+        frames: usize,
+    },
+    /// Write a patch that turns a ROM into a modified one: IPS for ".ips" files, BPS otherwise.
+    CreatePatch {
+        /// The path to the original ROM.
+        #[structopt(parse(from_os_str))]
+        original: PathBuf,
+        /// The path to the modified ROM.
+        #[structopt(parse(from_os_str))]
+        modified: PathBuf,
+        /// The patch to write.
+        #[structopt(parse(from_os_str))]
+        out: PathBuf,
+    },
+}
+
+fn read_rom(path: &Path) -> Vec<u8> {
+    std::fs::read(path).unwrap_or_else(|e| {
+        eprintln!("Cannot read ROM {:?}: {}", path, e);
+        process::exit(1);
+    })
+}
+
+fn load_config(path: Option<&Path>) -> Config {
+    Config::load(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    })
+}
+
+// The quirks preset of the flag, or of the configuration file.
+fn quirks(name: Option<&str>, settings: &Settings) -> chip8::Quirks {
+    match name {
+        Some(name) => chip8::Quirks::preset(name).unwrap_or_else(|| {
+            eprintln!("Invalid quirks preset: {:?}", name);
+            process::exit(1);
+        }),
+        None => settings.quirks.map(|preset| preset.0).unwrap_or_default(),
+    }
+}
+
+// The palette preset of the flag, or of the configuration file, with the colors of the
+// configuration file.
+fn palette(name: Option<&str>, settings: &Settings) -> chip8::Palette {
+    let mut palette = match name {
+        Some(name) => chip8::Palette::preset(name).unwrap_or_else(|| {
+            eprintln!("Invalid palette: {:?}", name);
+            process::exit(1);
+        }),
+        None => settings.palette.map(|preset| preset.0).unwrap_or_default(),
+    };
+    if let Some(color) = settings.foreground {
+        palette.set_foreground(color.0);
+    }
+    if let Some(color) = settings.background {
+        palette.set_background(color.0);
+    }
+
+    palette
+}
+
+fn read_movie(path: &Path) -> Movie {
+    let mut file = File::open(path).unwrap();
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).unwrap();
+
+    Movie::from_bytes(&bytes).unwrap_or_else(|e| {
+        eprintln!("Cannot read movie {:?}: {}", path, e);
+        process::exit(1);
+    })
+}
+
+fn play_headless(
+    interpreter: &mut chip8::Interpreter,
+    movie: &Movie,
+    mut beeper: Beeper,
+    wav: Option<&Path>,
+) {
+    let mut samples = Vec::new();
+    for n in 0..movie.len() {
+        interpreter.update_keypad(movie.frame(n).unwrap());
+        if let Err(e) = interpreter.run_frame(movie.speed) {
+            eprintln!("Error at frame {}: {}", n, e);
+            process::exit(1);
+        }
+
+        if wav.is_some() {
+            beeper.update(interpreter);
+            beeper.render_frame(&mut samples);
+        }
+    }
+
+    println!("Played {} frames", movie.len());
+    if let Some(path) = wav {
+        let file = io::BufWriter::new(File::create(path).unwrap());
+        chip8::audio::write_wav(file, beeper.get_sample_rate(), &samples).unwrap();
+        println!("Wrote {} samples to {:?}", samples.len(), path);
+    }
+    println!("{:?}", interpreter.cpu);
+
+    let vram = interpreter.get_vram();
+    for y in 0..chip8::HEIGHT {
+        let line: String = (0..chip8::WIDTH)
+            .map(|x| {
+                if vram[x + y * chip8::WIDTH] == 1 {
+                    '#'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        println!("{}", line);
+    }
+}
+
+fn apply_patch(rom: &[u8], path: &Path) -> Vec<u8> {
+    let bytes = std::fs::read(path).unwrap_or_else(|e| {
+        eprintln!("Cannot read patch {:?}: {}", path, e);
+        process::exit(1);
+    });
+
+    if bytes.starts_with(b"PATCH") {
+        println!(
+            "Warning: IPS patches have no checksums, {:?} is not verified",
+            path
+        );
+    }
+
+    patch::apply(rom, &bytes).unwrap_or_else(|e| {
+        eprintln!("Cannot apply patch {:?}: {}", path, e);
+        process::exit(1);
+    })
+}
+
+// The `create-patch` subcommand.
+fn create_patch(original: &Path, modified: &Path, out: &Path) {
+    let (original, modified) = (read_rom(original), read_rom(modified));
+    let bytes = if out.extension().is_some_and(|ext| ext == "ips") {
+        patch::create_ips(&original, &modified)
+    } else {
+        patch::create_bps(&original, &modified)
+    };
+
+    match std::fs::write(out, bytes) {
+        Ok(()) => println!("Wrote patch to {:?}", out),
+        Err(e) => {
+            eprintln!("Cannot write patch to {:?}: {}", out, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn read_symbols(path: Option<&Path>) -> Symbols {
+    let path = match path {
+        Some(path) => path,
+        None => return Symbols::new(),
+    };
+    let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Cannot read symbols {:?}: {}", path, e);
+        process::exit(1);
+    });
+
+    Symbols::parse(&text).unwrap_or_else(|e| {
+        eprintln!("Cannot read symbols {:?}: {}", path, e);
+        process::exit(1);
+    })
+}
+
+// Writes the profile report and/or folded call stacks, when profiling.
+fn write_profile(
+    interpreter: &chip8::Interpreter,
+    report: Option<&Path>,
+    folded: Option<&Path>,
+    symbols: &Symbols,
+) {
+    let profile = match interpreter.get_profile() {
+        Some(profile) => profile,
+        None => return,
+    };
+
+    if let Some(path) = report {
+        match std::fs::write(path, profile.report(interpreter, symbols, PROFILE_LIMIT)) {
+            Ok(()) => println!("Wrote profile to {:?}", path),
+            Err(e) => eprintln!("Cannot write profile to {:?}: {}", path, e),
+        }
+    }
+    if let Some(path) = folded {
+        match std::fs::write(path, profile.folded(symbols)) {
+            Ok(()) => println!("Wrote call stacks to {:?}", path),
+            Err(e) => eprintln!("Cannot write call stacks to {:?}: {}", path, e),
+        }
+    }
+}
+
+fn write_coverage(interpreter: &chip8::Interpreter, path: Option<&Path>) {
+    if let (Some(path), Some(coverage)) = (path, interpreter.get_coverage()) {
+        match std::fs::write(path, coverage.to_json()) {
+            Ok(()) => println!("Wrote coverage to {:?}", path),
+            Err(e) => eprintln!("Cannot write coverage to {:?}: {}", path, e),
+        }
+    }
+}
+
+// Writes the sprites found by static analysis and those drawn so far, when asked to.
+fn write_sprites(
+    interpreter: &chip8::Interpreter,
+    rom: &[u8],
+    sheet: Option<&Path>,
+    source: Option<&Path>,
+    palette: &chip8::Palette,
+    scale: u8,
+    symbols: &Symbols,
+) {
+    if sheet.is_none() && source.is_none() {
+        return;
+    }
+
+    let mut found = Analysis::new(rom).sprites();
+    if let Some(coverage) = interpreter.get_coverage() {
+        found.extend(coverage.sprites());
+    }
+    let found = sprites::merge(found);
+
+    if let Some(path) = sheet {
+        match File::create(path)
+            .and_then(|file| sprites::write_sheet(file, &found, interpreter, palette, scale))
+        {
+            Ok(()) => println!("Wrote {} sprites to {:?}", found.len(), path),
+            Err(e) => eprintln!("Cannot write sprites to {:?}: {}", path, e),
+        }
+    }
+    if let Some(path) = source {
+        let text = if path.extension().is_some_and(|ext| ext == "8o") {
+            sprites::to_octo(&found, interpreter, symbols)
+        } else {
+            sprites::to_assembler(&found, interpreter, symbols)
+        };
+        match std::fs::write(path, text) {
+            Ok(()) => println!("Wrote {} sprites to {:?}", found.len(), path),
+            Err(e) => eprintln!("Cannot write sprites to {:?}: {}", path, e),
+        }
+    }
+}
+
+// The `sprites` subcommand: the ROM runs with a seed of 0 so that the same sprites are found
+// each time.
+fn extract_sprites(
+    args: &Cli,
+    rom_name: &Path,
+    sheet: &Path,
+    source: Option<&Path>,
+    frames: usize,
+) {
+    let original = read_rom(rom_name);
+    let mut rom = original.clone();
+    for path in &args.patch {
+        rom = apply_patch(&rom, path);
+    }
+    let settings = load_config(args.config.as_deref()).settings(&original);
+
+    let mut interpreter = chip8::Interpreter::with_seed(rom.clone(), 0).unwrap_or_else(|e| {
+        eprintln!("Cannot load ROM: {}", e);
+        process::exit(1);
+    });
+    interpreter.set_quirks(quirks(args.quirks.as_deref(), &settings));
+    interpreter.set_coverage(true);
+    let speed = args.speed.or(settings.speed).unwrap_or(5);
+    for n in 0..frames {
+        if let Err(e) = interpreter.run_frame(speed) {
+            eprintln!("Error at frame {}: {}", n, e);
+            break;
+        }
+    }
+
+    write_sprites(
+        &interpreter,
+        &rom,
+        Some(sheet),
+        source,
+        &palette(args.palette.as_deref(), &settings),
+        args.scale.or(settings.scale).unwrap_or(8),
+        &read_symbols(args.symbols.as_deref()),
+    );
+}
+
+fn read_cheats(path: &Path) -> Cheats {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Cannot read cheats {:?}: {}", path, e);
+        process::exit(1);
+    });
+
+    Cheats::parse(&text).unwrap_or_else(|e| {
+        eprintln!("Cannot read cheats {:?}: {}", path, e);
+        process::exit(1);
+    })
+}
+
+fn new_beeper(settings: &Settings) -> Beeper {
+    let mut beeper = Beeper::default();
+    if let Some(frequency) = settings.audio.frequency {
+        beeper.set_frequency(frequency as f32);
+    }
+    if let Some(volume) = settings.audio.volume {
+        beeper.set_volume(volume);
+    }
+
+    beeper
+}
+
+// Returns a path in the current directory for a capture of the ROM, e.g. "pong-1600000000.png".
+fn capture_path(rom_name: &Path, extension: &str) -> PathBuf {
+    let stem = rom_name
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "chipolata".to_string());
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    PathBuf::from(format!("{}-{}.{}", stem, timestamp, extension))
+}
+
+// Returns the keypad state of the next frame. A movie being played back provides it, including
+// when advancing frame by frame, otherwise it is the keys toggled while paused or the keys held.
+// Once the movie is over, the user takes over.
+fn next_frame_keypad(
+    playback: Option<&Movie>,
+    frame: usize,
+    paused: bool,
+    next_keypad: [bool; 16],
+    read_keys: impl FnOnce() -> [bool; 16],
+) -> [bool; 16] {
+    match playback.and_then(|movie| movie.frame(frame)) {
+        Some(keypad) => keypad,
+        None if paused => next_keypad,
+        None => read_keys(),
+    }
 }
 
 fn main() {
     // CLI
     let args = Cli::from_args();
-    let rom_name = args.rom_name;
-    let mut file = File::open(&rom_name).unwrap();
-    let mut rom = Vec::new();
-    file.read_to_end(&mut rom).unwrap();
-    let speed = args.speed;
+    match &args.command {
+        Some(Command::Sprites {
+            rom_name,
+            sheet,
+            source,
+            frames,
+        }) => {
+            extract_sprites(&args, rom_name, sheet, source.as_deref(), *frames);
+            return;
+        }
+        Some(Command::CreatePatch {
+            original,
+            modified,
+            out,
+        }) => {
+            create_patch(original, modified, out);
+            return;
+        }
+        None => {}
+    }
+    let rom_name = args.rom_name.clone().unwrap_or_else(|| {
+        structopt::clap::Error::with_description(
+            "The path to a ROM is required",
+            structopt::clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit()
+    });
+    let mut rom = read_rom(&rom_name);
+
+    // Patches (the settings, cheats, etc. of a patched ROM are those of the original ROM)
+    let original = rom.clone();
+    for path in &args.patch {
+        rom = apply_patch(&rom, path);
+        println!("Applied patch {:?}", path);
+    }
+
+    // ROM database
+    if let Some(info) = database::lookup(&original) {
+        println!("Loaded {} ({})", info.title, info.platform);
+        if info.platform != database::Platform::Chip8 {
+            println!(
+                "Warning: {} is not supported, this ROM may not work",
+                info.platform
+            );
+        }
+        for (key, hint) in info.keys {
+            println!("  {:X}: {}", key, hint);
+        }
+    }
+
+    // Config (the flags take precedence over the configuration file)
+    let config = load_config(args.config.as_deref());
+    let settings = config.settings(&original);
+    let quirks = quirks(args.quirks.as_deref(), &settings);
+    let scale_factor = args.scale.or(settings.scale).unwrap_or(8);
+    let scale = match scale_factor {
+        1 => Scale::X1,
+        2 => Scale::X2,
+        4 => Scale::X4,
+        8 => Scale::X8,
+        16 => Scale::X16,
+        32 => Scale::X32,
+        n => {
+            eprintln!("Invalid scale: {}", n);
+            process::exit(1);
+        }
+    };
+    let palette = palette(args.palette.as_deref(), &settings);
+    let mut phosphor = if args.phosphor || settings.phosphor.unwrap_or(false) {
+        Some(chip8::Phosphor::default())
+    } else {
+        None
+    };
+    let mut keymap = Keymap::default();
+    keymap.apply(&settings.keys).unwrap_or_else(|e| {
+        eprintln!("Invalid key bindings: {}", e);
+        process::exit(1);
+    });
+
+    // Movies
+    let playback = args.play.as_deref().map(read_movie);
 
     // Chip8
-    let mut interpreter = chip8::Interpreter::new(rom);
+    let (mut interpreter, speed) = match &playback {
+        Some(movie) => {
+            let interpreter = movie.interpreter(rom.clone()).unwrap_or_else(|e| {
+                eprintln!("Cannot play movie: {}", e);
+                process::exit(1);
+            });
+            (interpreter, movie.speed)
+        }
+        None => {
+            let mut interpreter = chip8::Interpreter::new(rom.clone()).unwrap_or_else(|e| {
+                eprintln!("Cannot load ROM: {}", e);
+                process::exit(1);
+            });
+            interpreter.set_quirks(quirks);
+            (interpreter, args.speed.or(settings.speed).unwrap_or(5))
+        }
+    };
+    if args.profile.is_some() || args.folded.is_some() {
+        interpreter.set_profiling(true);
+    }
+    // The debuggers use the coverage to tell code from data in the disassembly, and the sprites
+    // that are drawn are collected with it.
+    if args.coverage.is_some()
+        || args.sprites.is_some()
+        || args.sprite_source.is_some()
+        || args.debug
+        || args.tui
+    {
+        interpreter.set_coverage(true);
+    }
+    let symbols = read_symbols(args.symbols.as_deref());
+
+    // Cheats (movies are played without them since they would not play back the same)
+    let cheats_path = args
+        .cheats
+        .clone()
+        .or_else(|| config::cheats_path(&original));
+    if let (None, None, Some(path)) = (&playback, &args.record, &cheats_path) {
+        if path.exists() {
+            let cheats = read_cheats(path);
+            println!("Loaded {} cheats from {:?}", cheats.len(), path);
+            interpreter.set_cheats(cheats);
+        }
+    }
+
+    // Static analysis
+    if args.listing.is_some() || args.cfg.is_some() {
+        let analysis = Analysis::new(&rom);
+        if let Some(path) = &args.listing {
+            std::fs::write(path, analysis.listing(&symbols)).unwrap();
+            println!("Wrote listing to {:?}", path);
+        }
+        if let Some(path) = &args.cfg {
+            std::fs::write(path, analysis.to_dot(&symbols)).unwrap();
+            println!("Wrote control-flow graph to {:?}", path);
+        }
+        write_sprites(
+            &interpreter,
+            &rom,
+            args.sprites.as_deref(),
+            args.sprite_source.as_deref(),
+            &palette,
+            scale_factor,
+            &symbols,
+        );
+        return;
+    }
+    let (profile_path, folded_path) = (args.profile.clone(), args.folded.clone());
+    let coverage_path = args.coverage.clone();
+    let (sprites_path, sprite_source_path) = (args.sprites.clone(), args.sprite_source.clone());
+    let write_reports = |interpreter: &chip8::Interpreter| {
+        write_profile(
+            interpreter,
+            profile_path.as_deref(),
+            folded_path.as_deref(),
+            &symbols,
+        );
+        write_coverage(interpreter, coverage_path.as_deref());
+        write_sprites(
+            interpreter,
+            &rom,
+            sprites_path.as_deref(),
+            sprite_source_path.as_deref(),
+            &palette,
+            scale_factor,
+            &symbols,
+        );
+    };
+    let mut recording = args
+        .record
+        .as_ref()
+        .map(|_| Movie::for_interpreter(&rom, &interpreter, speed));
+    let mut frame = 0;
+
+    if let (true, Some(movie)) = (args.headless, &playback) {
+        play_headless(
+            &mut interpreter,
+            movie,
+            new_beeper(&settings),
+            args.wav.as_deref(),
+        );
+        write_reports(&interpreter);
+        return;
+    }
+
+    // The debugger runs in the terminal, for both frontends.
+    let charset = if args.braille {
+        tui::Charset::Braille
+    } else {
+        tui::Charset::HalfBlock
+    };
+    let mut debugger = Debugger::new(
+        settings.breakpoints.clone().unwrap_or_default(),
+        cheats_path.clone(),
+        recording.is_some(),
+    );
+
+    if args.tui {
+        // In debug mode, the emulation starts paused.
+        tui::run(
+            &mut interpreter,
+            speed,
+            charset,
+            &keymap,
+            &mut debugger,
+            args.debug,
+        )
+        .unwrap();
+        write_reports(&interpreter);
+        return;
+    }
 
     // Debugger
-    let mut stepping = false;
     let mut boot = true;
-    let mut address_breakpoints = HashSet::new();
-    let mut opcode_breakpoints: HashSet<u16> = HashSet::new();
+    let mut debug_reason: Option<String> = None;
+
+    // Frame advance: when paused, the keys toggle the keypad state used for the next frame.
+    let mut paused = false;
+    let mut next_keypad = [false; 16];
+    let mut save_state: Option<(chip8::Snapshot, usize)> = None;
+    let mut gif_recorder: Option<(chip8::capture::GifRecorder<io::BufWriter<File>>, PathBuf)> =
+        None;
 
     // Graphics
     let mut window = Window::new(
@@ -76,7 +710,7 @@ fn main() {
         WindowOptions {
             borderless: false,
             resize: false,
-            scale: Scale::X8,
+            scale,
             title: true,
             ..WindowOptions::default()
         },
@@ -84,142 +718,353 @@ fn main() {
     .unwrap_or_else(|e| {
         panic!("{}", e);
     });
-    let mut buffer: Vec<u32> = vec![0; chip8::WIDTH * chip8::HEIGHT];
+    let mut buffer: Vec<u32> = vec![palette.color(0); chip8::WIDTH * chip8::HEIGHT];
 
     // Audio
     let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
     let sink = rodio::Sink::try_new(&stream_handle).unwrap();
-    let source = rodio::source::SineWave::new(400);
-    sink.append(source);
-    sink.pause();
+    let beeper = Arc::new(Mutex::new(new_beeper(&settings)));
+    sink.append(BeeperSource::new(Arc::clone(&beeper)));
 
     while window.is_open() && !window.is_key_down(Key::Escape) {
         let mut redraw = false;
 
-        if args.debug && boot {
-            boot = false;
-        } else {
-            for _ in 0..speed {
-                interpreter.update_keypad(read_keypad(&window));
-                interpreter.step();
+        if window.is_key_pressed(Key::P, KeyRepeat::No) {
+            paused = !paused;
+            next_keypad = [false; 16];
+            if paused {
+                println!("Paused at frame {} (N: next frame, P: resume)", frame);
+            } else {
+                println!("Resumed at frame {}", frame);
+            }
+        }
+
+        if paused {
+            for i in keymap.pressed(&window) {
+                next_keypad[i] = !next_keypad[i];
+                println!(
+                    "Key {:X} is {} for the next frame",
+                    i,
+                    if next_keypad[i] { "down" } else { "up" }
+                );
+            }
+        }
+
+        if window.is_key_pressed(Key::F5, KeyRepeat::No) {
+            save_state = Some((interpreter.save_state(), frame));
+            println!("Saved state at frame {}", frame);
+        }
 
-                if interpreter.should_redraw() {
-                    redraw = true;
+        if window.is_key_pressed(Key::F9, KeyRepeat::No) {
+            if let Some((snapshot, at)) = &save_state {
+                interpreter.load_state(snapshot);
+                frame = *at;
+                redraw = true;
+                if let Some(phosphor) = &mut phosphor {
+                    phosphor.reset();
+                    phosphor.update(&interpreter.get_vram());
                 }
+                // Branch: the recorded frames after the save state are discarded.
+                if let Some(movie) = &mut recording {
+                    movie.truncate(frame);
+                }
+                println!("Loaded state from frame {}", frame);
             }
         }
 
-        if (args.debug && interpreter.get_pc() == 0x200)
-            || address_breakpoints.contains(&interpreter.get_pc())
-            || opcode_breakpoints.contains(&interpreter.cpu.fetch_instruction())
-            || window.is_key_down(Key::O)
-        {
-            stepping = true;
-            interpreter.cpu.enable_debug();
-            println!("Breakpoint hit at 0x{:04X}", interpreter.get_pc());
-        }
-
-        if stepping {
-            loop {
-                let mut input = String::new();
-                print!(">>> ");
-                io::stdout().flush().unwrap();
-                io::stdin().read_line(&mut input).unwrap();
-                input = input[..input.len() - 1].to_string();
-
-                if input.len() == 0 {
-                    continue;
-                } else if input == "q" {
-                    print!("Exiting... o/");
-                    process::exit(0);
-                } else if input == "p cpu" {
-                    println!("{:?}", interpreter.cpu);
-                } else if input.starts_with("p ") {
-                    if let Ok(address) = u16::from_str_radix(&input[2..], 16) {
-                        println!("0x{:04X}", interpreter.cpu.read_byte(address));
-                    } else {
-                        println!("Invalid address: {:?}", &input[2..]);
-                    }
-                } else if input == "s" {
-                    let keypad = read_keypad(&window);
-                    interpreter.cpu.step(keypad);
-                } else if input.starts_with("s ") {
-                    if let Ok(mut n) = u16::from_str_radix(&input[2..], 10) {
-                        while n > 0 {
-                            let keypad = read_keypad(&window);
-                            interpreter.cpu.step(keypad);
-                            n -= 1;
+        if window.is_key_pressed(Key::F12, KeyRepeat::No) {
+            let path = capture_path(&rom_name, "png");
+            match File::create(&path).and_then(|file| {
+                chip8::capture::write_png(file, &interpreter.get_vram(), &palette, scale_factor)
+            }) {
+                Ok(()) => println!("Saved screenshot to {:?}", path),
+                Err(e) => eprintln!("Cannot save screenshot to {:?}: {}", path, e),
+            }
+        }
+
+        if window.is_key_pressed(Key::F11, KeyRepeat::No) {
+            match gif_recorder.take() {
+                Some((recorder, path)) => match recorder.finish() {
+                    Ok(_) => println!("Saved GIF to {:?}", path),
+                    Err(e) => eprintln!("Cannot save GIF to {:?}: {}", path, e),
+                },
+                None => {
+                    let path = capture_path(&rom_name, "gif");
+                    match File::create(&path).and_then(|file| {
+                        chip8::capture::GifRecorder::new(
+                            io::BufWriter::new(file),
+                            &palette,
+                            scale_factor,
+                        )
+                    }) {
+                        Ok(recorder) => {
+                            println!("Recording GIF to {:?} (F11: stop)", path);
+                            gif_recorder = Some((recorder, path));
                         }
-                    } else {
-                        println!("Invalid number: {:?}", &input[3..]);
-                    }
-                } else if input == "c" {
-                    interpreter.cpu.disable_debug();
-                    stepping = false;
-                    break;
-                } else if input.starts_with("ba ") {
-                    if let Ok(address) = u16::from_str_radix(&input[3..], 16) {
-                        println!("Added breakpoint for address 0x{:04X}", address);
-                        address_breakpoints.insert(address);
-                    } else {
-                        println!("Invalid address: {:?}", &input[3..]);
-                    }
-                } else if input.starts_with("bo ") {
-                    if let Ok(op) = u16::from_str_radix(&input[3..], 16) {
-                        println!("Added breakpoint for opcode 0x{:04X}", op);
-                        opcode_breakpoints.insert(op);
-                    } else {
-                        println!("Invalid opcode: {:?}", &input[3..]);
+                        Err(e) => eprintln!("Cannot record GIF to {:?}: {}", path, e),
                     }
-                } else if input == "clear" {
-                    address_breakpoints.clear();
-                    opcode_breakpoints.clear();
-                    println!("cleared breakpoints!");
-                } else if input == "r" {
-                    interpreter.reset();
-                    println!("reset!");
-                } else {
-                    if input != "help" {
-                        print!("Invalid command. ");
-                    }
-                    println!("Available commands:");
-                    println!("");
-                    println!("  ba [u16] : set breakpoint at address [u16]");
-                    println!("  bo [u8]  : set breakpoint for opcode [u8]");
-                    println!("  c        : continue");
-                    println!("  clear    : clear breakpoints");
-                    println!("  p cpu    : print cpu info");
-                    println!("  q        : exit");
-                    println!("  s        : step");
-                    println!("  s [u16]  : step [u16] times");
-                    println!("  r        : reset");
                 }
             }
         }
 
-        if redraw {
-            for x in 0..chip8::WIDTH {
-                for y in 0..chip8::HEIGHT {
-                    let i = x + (y * chip8::WIDTH);
-                    buffer[i] = if interpreter.get_vram()[i] == 1 {
-                        0xFFFFFF
-                    } else {
-                        0x0
-                    };
+        let advance = !paused || window.is_key_pressed(Key::N, KeyRepeat::No);
+
+        if args.debug && boot {
+            boot = false;
+        } else if advance {
+            let keypad = next_frame_keypad(playback.as_ref(), frame, paused, next_keypad, || {
+                keymap.read(&window)
+            });
+            frame += 1;
+
+            if let Some(movie) = &mut recording {
+                movie.record_frame(keypad);
+            }
+
+            interpreter.update_keypad(keypad);
+
+            match interpreter.run_frame(speed) {
+                Ok(true) => redraw = true,
+                Ok(false) => {}
+                Err(e) => {
+                    // Start the debugger on the faulty instruction.
+                    debug_reason = Some(format!("Error: {}", e));
+                }
+            }
+
+            if let Some(phosphor) = &mut phosphor {
+                redraw = redraw || phosphor.is_fading();
+                phosphor.update(&interpreter.get_vram());
+            }
+
+            if let Some((recorder, path)) = &mut gif_recorder {
+                if let Err(e) = recorder.add_frame(&interpreter.get_vram()) {
+                    eprintln!("Cannot record GIF to {:?}: {}", path, e);
+                    gif_recorder = None;
                 }
             }
         }
 
-        if interpreter.should_beep() {
-            sink.play();
-        } else {
-            sink.pause();
+        if (advance
+            && ((args.debug && interpreter.get_pc() == 0x200)
+                || debugger.should_break(&interpreter)))
+            || window.is_key_down(Key::O)
+        {
+            debug_reason = Some(format!("Breakpoint hit at 0x{:04X}", interpreter.get_pc()));
         }
 
-        interpreter.update_timers();
+        if let Some(reason) = debug_reason.take() {
+            let exit = tui::debug(&mut interpreter, charset, &keymap, &mut debugger, reason)
+                .unwrap_or_else(|e| {
+                    eprintln!("Cannot start the debugger: {}", e);
+                    tui::Exit::Continue
+                });
+            if exit == tui::Exit::Quit {
+                break;
+            }
+            redraw = true;
+        }
+
+        if redraw {
+            match &phosphor {
+                Some(phosphor) => phosphor.render(&palette, &mut buffer),
+                None => palette.render(&interpreter.get_vram(), &mut buffer),
+            }
+        }
+
+        {
+            let mut beeper = beeper.lock().unwrap();
+            beeper.update(&interpreter);
+            if paused {
+                beeper.set_active(false);
+            }
+        }
 
         window
             .update_with_buffer(&buffer, chip8::WIDTH, chip8::HEIGHT)
             .unwrap();
     }
+
+    if let Some((recorder, path)) = gif_recorder {
+        match recorder.finish() {
+            Ok(_) => println!("Saved GIF to {:?}", path),
+            Err(e) => eprintln!("Cannot save GIF to {:?}: {}", path, e),
+        }
+    }
+
+    if let (Some(path), Some(movie)) = (&args.record, &recording) {
+        let mut file = File::create(path).unwrap();
+        file.write_all(&movie.to_bytes()).unwrap();
+        println!("Recorded {} frames to {:?}", movie.len(), path);
+    }
+
+    write_reports(&interpreter);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libchipolata::chip8::Quirks;
+
+    fn keys(pressed: &[usize]) -> [bool; 16] {
+        let mut keypad = [false; 16];
+        for &key in pressed {
+            keypad[key] = true;
+        }
+        keypad
+    }
+
+    #[test]
+    fn sprites_command() {
+        let args = Cli::from_iter_safe(&["chipolata", "game.ch8"]).unwrap();
+        assert_eq!(args.rom_name, Some(PathBuf::from("game.ch8")));
+        assert!(args.command.is_none());
+
+        let args = Cli::from_iter_safe(&[
+            "chipolata",
+            "--speed",
+            "10",
+            "sprites",
+            "game.ch8",
+            "game.png",
+            "--frames",
+            "0",
+        ])
+        .unwrap();
+        assert_eq!(args.rom_name, None);
+        assert_eq!(args.speed, Some(10));
+        match args.command {
+            Some(Command::Sprites {
+                rom_name,
+                sheet,
+                source,
+                frames,
+            }) => {
+                assert_eq!(rom_name, PathBuf::from("game.ch8"));
+                assert_eq!(sheet, PathBuf::from("game.png"));
+                assert_eq!(source, None);
+                assert_eq!(frames, 0);
+            }
+            _ => panic!("not the sprites command"),
+        }
+
+        assert!(Cli::from_iter_safe(&["chipolata", "sprites", "game.ch8"]).is_err());
+    }
+
+    #[test]
+    fn sprites_command_runs_headless() {
+        let dir = std::env::temp_dir().join(format!("chipolata-sprites-{}", process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let rom = dir.join("space-invaders.ch8");
+        std::fs::write(&rom, include_bytes!("../docs/space-invaders.ch8")).unwrap();
+        let (sheet, source) = (dir.join("sprites.png"), dir.join("sprites.8o"));
+
+        // Returns the number of sprites found in `frames` frames.
+        let extract = |frames: &str| {
+            let args = Cli::from_iter_safe(&[
+                "chipolata",
+                "--config",
+                "/dev/null",
+                "sprites",
+                rom.to_str().unwrap(),
+                sheet.to_str().unwrap(),
+                "--source",
+                source.to_str().unwrap(),
+                "--frames",
+                frames,
+            ])
+            .unwrap();
+            match &args.command {
+                Some(Command::Sprites {
+                    rom_name,
+                    sheet,
+                    source,
+                    frames,
+                }) => extract_sprites(&args, rom_name, sheet, source.as_deref(), *frames),
+                _ => unreachable!(),
+            }
+
+            assert!(std::fs::read(&sheet).unwrap().starts_with(b"\x89PNG"));
+            std::fs::read_to_string(&source)
+                .unwrap()
+                .lines()
+                .filter(|line| line.starts_with(": "))
+                .count()
+        };
+
+        let found = extract("0");
+        assert!(found > 0);
+        // Running the ROM finds sprites that static analysis misses.
+        assert!(extract("300") > found);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn create_patch_command() {
+        let dir = std::env::temp_dir().join(format!("chipolata-patch-{}", process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (original, modified) = (dir.join("game.ch8"), dir.join("fixed.ch8"));
+        std::fs::write(&original, [0x60, 0x01, 0x12, 0x00]).unwrap();
+        std::fs::write(&modified, [0x60, 0x02, 0x12, 0x00, 0x00]).unwrap();
+
+        for name in &["fix.ips", "fix.bps"] {
+            let out = dir.join(name);
+            let args = Cli::from_iter_safe(&[
+                "chipolata",
+                "create-patch",
+                original.to_str().unwrap(),
+                modified.to_str().unwrap(),
+                out.to_str().unwrap(),
+            ])
+            .unwrap();
+            match &args.command {
+                Some(Command::CreatePatch {
+                    original,
+                    modified,
+                    out,
+                }) => create_patch(original, modified, out),
+                _ => unreachable!(),
+            }
+
+            let patched = apply_patch(&std::fs::read(&original).unwrap(), &out);
+            assert_eq!(patched, std::fs::read(&modified).unwrap());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn next_frame_keypad_during_playback() {
+        let mut movie = Movie::new(&[0x12, 0x00], Quirks::default(), 0, 5);
+        movie.record_frame(keys(&[1]));
+        movie.record_frame(keys(&[2]));
+        let toggled = keys(&[0xF]);
+        let held = || keys(&[0xA]);
+
+        // The recorded keys are used when advancing frame by frame too.
+        assert_eq!(
+            next_frame_keypad(Some(&movie), 0, false, toggled, held),
+            keys(&[1])
+        );
+        assert_eq!(
+            next_frame_keypad(Some(&movie), 1, true, toggled, held),
+            keys(&[2])
+        );
+
+        // Then the user takes over.
+        assert_eq!(
+            next_frame_keypad(Some(&movie), 2, true, toggled, held),
+            toggled
+        );
+        assert_eq!(
+            next_frame_keypad(Some(&movie), 2, false, toggled, held),
+            keys(&[0xA])
+        );
+        assert_eq!(next_frame_keypad(None, 0, true, toggled, held), toggled);
+        assert_eq!(
+            next_frame_keypad(None, 0, false, toggled, held),
+            keys(&[0xA])
+        );
+    }
 }
@@ -0,0 +1,417 @@
+// Static analysis of ROMs. Unlike a linear disassembly, which decodes every other byte from 0x200,
+// the code is found by following the control flow from the entry point: jumps (1NNN), calls
+// (2NNN, which are assumed to return) and both outcomes of the skip instructions. The bytes that
+// are never reached are data. The instructions are grouped into basic blocks, which form the
+// control-flow graph of the ROM.
+//
+// The analysis cannot follow the indirect jumps of BNNN, so code that is only reached this way is
+// seen as data. A jump into the middle of an instruction that has already been found (i.e. code
+// that overlaps itself) is not followed either.
+//
+// The listing re-assembles into the exact same ROM with `assembler::assemble()`, and the
+// control-flow graph can be exported to Graphviz.
+//
+// The sprites are found by looking for ANNN followed by DXYN in the same basic block, which misses
+// those whose address is computed (FX1E) or set in another block.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::assembler;
+use super::disassembler::disassemble;
+use super::mmu::ROM_BASE_ADDR;
+use super::op::Op;
+use super::sprites::Sprite;
+use super::symbols::Symbols;
+
+const ENTRY_POINT: u16 = ROM_BASE_ADDR as u16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    // The next instruction, e.g. when a skip instruction does not skip or after a call returns.
+    Next,
+    Jump,
+    Call,
+    // The instruction after the next one, when a skip instruction skips.
+    Skip,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub start: u16,
+    // The address that follows the last instruction of the block.
+    pub end: u16,
+    pub successors: Vec<(Edge, u16)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Reference {
+    // Sorted by priority, when an address is referenced in several ways.
+    Entry,
+    Call,
+    Jump,
+    Table,
+    Data,
+}
+
+pub struct Analysis {
+    rom: Vec<u8>,
+    // For each byte of the ROM, the address of the instruction it belongs to, if it is code.
+    owners: Vec<Option<u16>>,
+    blocks: BTreeMap<u16, Block>,
+    references: BTreeMap<u16, Reference>,
+}
+
+impl Analysis {
+    pub fn new(rom: &[u8]) -> Self {
+        let mut analysis = Analysis {
+            rom: rom.to_vec(),
+            owners: vec![None; rom.len()],
+            blocks: BTreeMap::new(),
+            references: BTreeMap::new(),
+        };
+        analysis.reference(ENTRY_POINT, Reference::Entry);
+
+        let leaders = analysis.follow();
+        analysis.build_blocks(&leaders);
+
+        analysis
+    }
+
+    fn reference(&mut self, addr: u16, reference: Reference) {
+        let current = self.references.entry(addr).or_insert(reference);
+        *current = (*current).min(reference);
+    }
+
+    fn index(&self, addr: u16) -> Option<usize> {
+        let index = (addr as usize).checked_sub(ROM_BASE_ADDR)?;
+        if index < self.rom.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    // Returns the instruction at `addr`, whether it is code or not.
+    fn opcode(&self, addr: u16) -> Option<u16> {
+        let index = self.index(addr)?;
+        let low = *self.rom.get(index + 1)?;
+
+        Some((self.rom[index] as u16) << 8 | low as u16)
+    }
+
+    // Follows the control flow from the entry point and returns the addresses where basic blocks
+    // start.
+    fn follow(&mut self) -> BTreeSet<u16> {
+        let mut leaders = BTreeSet::new();
+        let mut queue = vec![ENTRY_POINT];
+
+        while let Some(start) = queue.pop() {
+            leaders.insert(start);
+            let mut addr = start;
+
+            while let Some(opcode) = self.opcode(addr) {
+                let index = self.index(addr).unwrap();
+                // Already visited, or in the middle of another instruction.
+                if self.owners[index].is_some() || self.owners[index + 1].is_some() {
+                    break;
+                }
+
+                let op = Op::decode(opcode);
+                if let Op::Unsupported(_) = op {
+                    break;
+                }
+                self.owners[index] = Some(addr);
+                self.owners[index + 1] = Some(addr);
+
+                let next = addr.wrapping_add(2);
+                match op {
+                    Op::Jump(target) => {
+                        self.reference(target, Reference::Jump);
+                        queue.push(target);
+                        break;
+                    }
+                    Op::Call(target) => {
+                        self.reference(target, Reference::Call);
+                        queue.push(target);
+                        leaders.insert(next);
+                    }
+                    Op::Return => break,
+                    Op::JumpWithOffset(_, table) => {
+                        self.reference(table, Reference::Table);
+                        break;
+                    }
+                    Op::SetI(data) => self.reference(data, Reference::Data),
+                    _ if is_skip(op) => {
+                        queue.push(next.wrapping_add(2));
+                        leaders.insert(next);
+                    }
+                    _ => {}
+                }
+
+                addr = next;
+            }
+        }
+
+        leaders.retain(|addr| self.is_code(*addr));
+        leaders
+    }
+
+    fn build_blocks(&mut self, leaders: &BTreeSet<u16>) {
+        let mut addr = ENTRY_POINT;
+        let mut current: Option<Block> = None;
+
+        while self.index(addr).is_some() {
+            if !self.is_code(addr) {
+                self.end_block(current.take());
+                addr += 1;
+                continue;
+            }
+
+            if leaders.contains(&addr) {
+                self.end_block(current.take());
+            }
+            let block = current.get_or_insert(Block {
+                start: addr,
+                end: addr,
+                successors: Vec::new(),
+            });
+
+            let op = Op::decode(self.opcode(addr).unwrap());
+            let next = addr + 2;
+            block.end = next;
+
+            let successors = match op {
+                Op::Jump(target) => Some(vec![(Edge::Jump, target)]),
+                Op::Call(target) => Some(vec![(Edge::Call, target), (Edge::Next, next)]),
+                Op::Return | Op::JumpWithOffset(..) => Some(Vec::new()),
+                _ if is_skip(op) => Some(vec![(Edge::Next, next), (Edge::Skip, next + 2)]),
+                _ => None,
+            };
+            match successors {
+                Some(successors) => {
+                    block.successors = successors;
+                    self.end_block(current.take());
+                }
+                None if leaders.contains(&next) => {
+                    block.successors = vec![(Edge::Next, next)];
+                    self.end_block(current.take());
+                }
+                None => {}
+            }
+
+            addr = next;
+        }
+        self.end_block(current);
+
+        // Only keep the edges to the code that has been found.
+        let starts: BTreeSet<u16> = self.blocks.keys().copied().collect();
+        for block in self.blocks.values_mut() {
+            block.successors.retain(|(_, addr)| starts.contains(addr));
+        }
+    }
+
+    fn end_block(&mut self, block: Option<Block>) {
+        if let Some(block) = block {
+            self.blocks.insert(block.start, block);
+        }
+    }
+
+    // Whether an instruction starts at `addr`.
+    pub fn is_code(&self, addr: u16) -> bool {
+        self.index(addr)
+            .is_some_and(|index| self.owners[index] == Some(addr))
+    }
+
+    // Whether the byte at `addr` belongs to the ROM but not to an instruction.
+    pub fn is_data(&self, addr: u16) -> bool {
+        self.index(addr)
+            .is_some_and(|index| self.owners[index].is_none())
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.values()
+    }
+
+    pub fn get_block(&self, start: u16) -> Option<&Block> {
+        self.blocks.get(&start)
+    }
+
+    // Returns the sprites drawn by the code, sorted by address. When a sprite is drawn with
+    // different heights, the tallest one is kept.
+    pub fn sprites(&self) -> Vec<Sprite> {
+        let mut sprites: BTreeMap<u16, u8> = BTreeMap::new();
+
+        for block in self.blocks.values() {
+            let mut i = None;
+            for addr in (block.start..block.end).step_by(2) {
+                match Op::decode(self.opcode(addr).unwrap()) {
+                    Op::SetI(data) => i = Some(data),
+                    // FX55 and FX65 increment I with the `load_store_increment_i` quirk.
+                    Op::AddI(_)
+                    | Op::SetIToFont(_)
+                    | Op::StoreRegisters(_)
+                    | Op::LoadRegisters(_) => i = None,
+                    Op::Draw(_, _, height) if height > 0 => {
+                        if let Some(data) = i {
+                            let tallest = sprites.entry(data).or_insert(0);
+                            *tallest = (*tallest).max(height);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        sprites
+            .into_iter()
+            .map(|(addr, height)| Sprite::new(addr, height))
+            .collect()
+    }
+
+    // Returns the label of `addr`: its symbol if it has one, or a name generated from the way it
+    // is referenced, e.g. "sub_2A0" for a subroutine. Only the addresses where a line of the
+    // listing starts can have a label.
+    pub fn label(&self, addr: u16, symbols: &Symbols) -> Option<String> {
+        if !self.is_code(addr) && !self.is_data(addr) {
+            return None;
+        }
+        if let Some(name) = symbols.get(addr).filter(|name| assembler::is_label(name)) {
+            return Some(name.to_string());
+        }
+
+        let prefix = match self.references.get(&addr)? {
+            Reference::Entry => return Some("start".to_string()),
+            Reference::Call => "sub",
+            Reference::Jump => "label",
+            Reference::Table => "table",
+            Reference::Data => "data",
+        };
+
+        Some(format!("{}_{:03X}", prefix, addr))
+    }
+
+    // Returns the listing of the ROM, with the labels of `symbols` (when they are valid).
+    pub fn listing(&self, symbols: &Symbols) -> String {
+        let mut listing = String::new();
+        let code = self.owners.iter().filter(|owner| owner.is_some()).count();
+
+        writeln!(
+            listing,
+            "; {} bytes: {} of code in {} blocks, {} of data",
+            self.rom.len(),
+            code,
+            self.blocks.len(),
+            self.rom.len() - code
+        )
+        .unwrap();
+        writeln!(listing, "; Re-assemble with chipolata::chip8::assembler.").unwrap();
+
+        let mut addr = ENTRY_POINT;
+        while let Some(index) = self.index(addr) {
+            if let Some(label) = self.label(addr, symbols) {
+                writeln!(listing).unwrap();
+                writeln!(listing, "{}:", label).unwrap();
+            }
+
+            if self.is_code(addr) {
+                let opcode = self.opcode(addr).unwrap();
+                let text = self.instruction(opcode, symbols);
+                writeln!(listing, "    {:<24}; {:04X}: {:04X}", text, addr, opcode).unwrap();
+                addr += 2;
+            } else {
+                let byte = self.rom[index];
+                let pixels: String = (0..8)
+                    .map(|x| if byte & (0x80 >> x) != 0 { '#' } else { '.' })
+                    .collect();
+                let text = format!("DB {:02X}", byte);
+                writeln!(listing, "    {:<24}; {:04X}: {}", text, addr, pixels).unwrap();
+                addr += 1;
+            }
+        }
+
+        listing
+    }
+
+    // Formats an instruction for the listing, with labels instead of addresses.
+    fn instruction(&self, opcode: u16, symbols: &Symbols) -> String {
+        let nnn = opcode & 0x0FFF;
+        let target = self
+            .label(nnn, symbols)
+            .unwrap_or_else(|| format!("{:04X}", nnn));
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+
+        let text = match Op::decode(opcode) {
+            Op::Jump(_) => format!("JP {}", target),
+            Op::Call(_) => format!("CALL {}", target),
+            Op::SetI(_) => format!("LD I, {}", target),
+            Op::JumpWithOffset(..) => format!("JP V0, {}", target),
+            Op::ShiftRight(..) => format!("SHR V{:X}, V{:X}", x, y),
+            Op::ShiftLeft(..) => format!("SHL V{:X}, V{:X}", x, y),
+            _ => disassemble(opcode),
+        };
+
+        // Some opcodes have several encodings (e.g. 5XY1 behaves like 5XY0), which are kept as
+        // they are so that the ROM can be re-assembled exactly.
+        let canonical = match Op::decode(opcode) {
+            Op::Jump(_) | Op::Call(_) | Op::SetI(_) | Op::JumpWithOffset(..) => true,
+            _ => assembler::assemble(&text).ok() == Some(opcode.to_be_bytes().to_vec()),
+        };
+        if canonical {
+            text
+        } else {
+            format!("DW {:04X}", opcode)
+        }
+    }
+
+    // Returns the control-flow graph in the DOT language of Graphviz, e.g. to render it with
+    // `dot -Tsvg`.
+    pub fn to_dot(&self, symbols: &Symbols) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph cfg {{").unwrap();
+        writeln!(dot, "  node [shape=box, fontname=\"monospace\"];").unwrap();
+
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            if let Some(name) = self.label(block.start, symbols) {
+                write!(label, "{}:\\l", name).unwrap();
+            }
+            for addr in (block.start..block.end).step_by(2) {
+                let text = self.instruction(self.opcode(addr).unwrap(), symbols);
+                write!(label, "{:04X}: {}\\l", addr, text.replace('"', "\\\"")).unwrap();
+            }
+            writeln!(dot, "  b{:03X} [label=\"{}\"];", block.start, label).unwrap();
+
+            for (edge, target) in &block.successors {
+                let attributes = match edge {
+                    Edge::Next => "",
+                    Edge::Jump => " [label=\"jump\"]",
+                    Edge::Call => " [label=\"call\", style=dashed]",
+                    Edge::Skip => " [label=\"skip\"]",
+                };
+                writeln!(
+                    dot,
+                    "  b{:03X} -> b{:03X}{};",
+                    block.start, target, attributes
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
+
+fn is_skip(op: Op) -> bool {
+    matches!(
+        op,
+        Op::SkipIfEqual(..)
+            | Op::SkipIfNotEqual(..)
+            | Op::SkipIfRegistersEqual(..)
+            | Op::SkipIfRegistersNotEqual(..)
+            | Op::SkipIfKeyPressed(_)
+            | Op::SkipIfKeyNotPressed(_)
+    )
+}
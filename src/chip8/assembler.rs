@@ -0,0 +1,297 @@
+// Assembles the listings of `analysis::Analysis` (or hand-written programs) back into ROMs. The
+// syntax is that of the disassembler (see http://devernay.free.fr/hacks/chip8/C8TECH10.HTM):
+//
+//   start:
+//       LD V0, 0A        ; comments start with a semicolon
+//       CALL sub_20A
+//   loop:
+//       JP loop
+//   sub_20A:
+//       DRW V0, V1, 5
+//       RET
+//       DB F0, 90        ; raw bytes
+//       DW 5121          ; raw big-endian words
+//
+// Numbers are hexadecimal, with an optional "0x" or "$" prefix. Labels are the other
+// identifiers, followed by a colon where they are defined. The program starts at 0x200.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::mmu::{MAX_ROM_SIZE, ROM_BASE_ADDR};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AssemblerError {
+    // The line number, starting at 1.
+    InvalidLine(usize),
+    InvalidValue(usize),
+    UnknownLabel(usize, String),
+    DuplicateLabel(usize, String),
+    RomTooLarge(usize),
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblerError::InvalidLine(line) => write!(f, "line {}: invalid instruction", line),
+            AssemblerError::InvalidValue(line) => write!(f, "line {}: value out of range", line),
+            AssemblerError::UnknownLabel(line, label) => {
+                write!(f, "line {}: unknown label {:?}", line, label)
+            }
+            AssemblerError::DuplicateLabel(line, label) => {
+                write!(f, "line {}: duplicate label {:?}", line, label)
+            }
+            AssemblerError::RomTooLarge(size) => {
+                write!(f, "ROM is too large ({} bytes, max {})", size, MAX_ROM_SIZE)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Operand {
+    V(u8),
+    I,
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    B,
+    Audio,
+    Pitch,
+    Number(u16),
+    Label(String),
+}
+
+struct Statement {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<Operand>,
+}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    let mut labels: HashMap<String, u16> = HashMap::new();
+    let mut statements = Vec::new();
+    let mut addr = ROM_BASE_ADDR;
+
+    // First pass: parse the statements and find the address of each label.
+    for (n, line) in source.lines().enumerate() {
+        let line_number = n + 1;
+        let mut line = line.split(';').next().unwrap_or("").trim();
+
+        if let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(AssemblerError::InvalidLine(line_number));
+            }
+            if labels.insert(label.to_string(), addr as u16).is_some() {
+                return Err(AssemblerError::DuplicateLabel(
+                    line_number,
+                    label.to_string(),
+                ));
+            }
+            line = rest.trim();
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(line_number, line)?;
+        addr += size(&statement);
+        statements.push(statement);
+    }
+
+    // Second pass: encode the statements.
+    let mut rom = Vec::new();
+    for statement in &statements {
+        encode(statement, &labels, &mut rom)?;
+    }
+
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(AssemblerError::RomTooLarge(rom.len()));
+    }
+
+    Ok(rom)
+}
+
+// Labels are identifiers that cannot be mistaken for a number or a register.
+pub fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && parse_number(name).is_none()
+        && keyword(name).is_none()
+}
+
+fn keyword(token: &str) -> Option<Operand> {
+    let upper = token.to_ascii_uppercase();
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DT,
+        "ST" => Operand::ST,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "B" => Operand::B,
+        "AUDIO" => Operand::Audio,
+        "PITCH" => Operand::Pitch,
+        _ => match upper.strip_prefix('V') {
+            Some(x) if x.len() == 1 => Operand::V(u8::from_str_radix(x, 16).ok()?),
+            _ => return None,
+        },
+    };
+
+    Some(operand)
+}
+
+fn parse_number(token: &str) -> Option<u16> {
+    let digits = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+        .or_else(|| token.strip_prefix('$'))
+        .unwrap_or(token);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u16::from_str_radix(digits, 16).ok()
+}
+
+fn parse_statement(line: usize, text: &str) -> Result<Statement, AssemblerError> {
+    let (mnemonic, operands) = match text.split_once(char::is_whitespace) {
+        Some((mnemonic, operands)) => (mnemonic, operands.trim()),
+        None => (text, ""),
+    };
+
+    let operands = if operands.is_empty() {
+        Vec::new()
+    } else {
+        operands
+            .split(',')
+            .map(|operand| {
+                let operand = operand.trim();
+                if let Some(keyword) = keyword(operand) {
+                    Ok(keyword)
+                } else if let Some(number) = parse_number(operand) {
+                    Ok(Operand::Number(number))
+                } else if is_label(operand) {
+                    Ok(Operand::Label(operand.to_string()))
+                } else {
+                    Err(AssemblerError::InvalidLine(line))
+                }
+            })
+            .collect::<Result<_, _>>()?
+    };
+
+    Ok(Statement {
+        line,
+        mnemonic: mnemonic.to_ascii_uppercase(),
+        operands,
+    })
+}
+
+fn size(statement: &Statement) -> usize {
+    match statement.mnemonic.as_str() {
+        "DB" => statement.operands.len(),
+        "DW" => statement.operands.len() * 2,
+        _ => 2,
+    }
+}
+
+fn encode(
+    statement: &Statement,
+    labels: &HashMap<String, u16>,
+    rom: &mut Vec<u8>,
+) -> Result<(), AssemblerError> {
+    use Operand::*;
+
+    let line = statement.line;
+    // Returns the value of a number or a label, which must not be greater than `max`. F and B
+    // are numbers too, e.g. the height of a sprite.
+    let value = |operand: &Operand, max: u16| {
+        let value = match operand {
+            Number(n) => *n,
+            F => 0xF,
+            B => 0xB,
+            Label(label) => *labels
+                .get(label)
+                .ok_or_else(|| AssemblerError::UnknownLabel(line, label.clone()))?,
+            _ => return Err(AssemblerError::InvalidLine(line)),
+        };
+        if value > max {
+            return Err(AssemblerError::InvalidValue(line));
+        }
+
+        Ok(value)
+    };
+
+    let opcode = match (statement.mnemonic.as_str(), statement.operands.as_slice()) {
+        ("DB", values) => {
+            for v in values {
+                rom.push(value(v, 0xFF)? as u8);
+            }
+            return Ok(());
+        }
+        ("DW", values) => {
+            for v in values {
+                rom.extend_from_slice(&value(v, 0xFFFF)?.to_be_bytes());
+            }
+            return Ok(());
+        }
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("JP", [V(0), addr]) => 0xB000 | value(addr, 0xFFF)?,
+        ("JP", [addr]) => 0x1000 | value(addr, 0xFFF)?,
+        ("CALL", [addr]) => 0x2000 | value(addr, 0xFFF)?,
+        ("SE", [V(x), V(y)]) => 0x5000 | xy(*x, *y),
+        ("SE", [V(x), byte]) => 0x3000 | x_nn(*x, value(byte, 0xFF)?),
+        ("SNE", [V(x), V(y)]) => 0x9000 | xy(*x, *y),
+        ("SNE", [V(x), byte]) => 0x4000 | x_nn(*x, value(byte, 0xFF)?),
+        ("LD", [V(x), V(y)]) => 0x8000 | xy(*x, *y),
+        ("LD", [V(x), DT]) => 0xF007 | xy(*x, 0),
+        ("LD", [V(x), K]) => 0xF00A | xy(*x, 0),
+        ("LD", [V(x), IndirectI]) => 0xF065 | xy(*x, 0),
+        ("LD", [V(x), byte]) => 0x6000 | x_nn(*x, value(byte, 0xFF)?),
+        ("LD", [I, addr]) => 0xA000 | value(addr, 0xFFF)?,
+        ("LD", [DT, V(x)]) => 0xF015 | xy(*x, 0),
+        ("LD", [ST, V(x)]) => 0xF018 | xy(*x, 0),
+        ("LD", [F, V(x)]) => 0xF029 | xy(*x, 0),
+        ("LD", [B, V(x)]) => 0xF033 | xy(*x, 0),
+        ("LD", [Pitch, V(x)]) => 0xF03A | xy(*x, 0),
+        ("LD", [IndirectI, V(x)]) => 0xF055 | xy(*x, 0),
+        ("LD", [Audio, IndirectI]) => 0xF002,
+        ("ADD", [V(x), V(y)]) => 0x8004 | xy(*x, *y),
+        ("ADD", [V(x), byte]) => 0x7000 | x_nn(*x, value(byte, 0xFF)?),
+        ("ADD", [I, V(x)]) => 0xF01E | xy(*x, 0),
+        ("OR", [V(x), V(y)]) => 0x8001 | xy(*x, *y),
+        ("AND", [V(x), V(y)]) => 0x8002 | xy(*x, *y),
+        ("XOR", [V(x), V(y)]) => 0x8003 | xy(*x, *y),
+        ("SUB", [V(x), V(y)]) => 0x8005 | xy(*x, *y),
+        ("SHR", [V(x)]) => 0x8006 | xy(*x, 0),
+        ("SHR", [V(x), V(y)]) => 0x8006 | xy(*x, *y),
+        ("SUBN", [V(x), V(y)]) => 0x8007 | xy(*x, *y),
+        ("SHL", [V(x)]) => 0x800E | xy(*x, 0),
+        ("SHL", [V(x), V(y)]) => 0x800E | xy(*x, *y),
+        ("RND", [V(x), byte]) => 0xC000 | x_nn(*x, value(byte, 0xFF)?),
+        ("DRW", [V(x), V(y), n]) => 0xD000 | xy(*x, *y) | value(n, 0xF)?,
+        ("SKP", [V(x)]) => 0xE09E | xy(*x, 0),
+        ("SKNP", [V(x)]) => 0xE0A1 | xy(*x, 0),
+        _ => return Err(AssemblerError::InvalidLine(line)),
+    };
+
+    rom.extend_from_slice(&opcode.to_be_bytes());
+
+    Ok(())
+}
+
+fn xy(x: u8, y: u8) -> u16 {
+    (x as u16) << 8 | (y as u16) << 4
+}
+
+fn x_nn(x: u8, nn: u16) -> u16 {
+    (x as u16) << 8 | nn
+}
@@ -0,0 +1,193 @@
+// Generates the sound of the interpreter as mono `f32` samples, so that the frontends only have
+// to play them (or write them to a WAV file). CHIP-8 has a single beeper that is on while the
+// sound timer is non-zero, which is rendered as a square wave. The volume ramps up and down over
+// a couple of milliseconds when the beeper is turned on and off to avoid clicks.
+//
+// XO-CHIP ROMs can load a 128-bit pattern instead, which is played in a loop (one bit after the
+// other, 1 being high and 0 low) at a rate that depends on the pitch register.
+
+use std::io::{self, Write};
+
+use super::{Interpreter, AUDIO_PATTERN_SIZE, DEFAULT_PITCH};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+pub const DEFAULT_FREQUENCY: f32 = 400.0;
+pub const DEFAULT_VOLUME: f32 = 0.25;
+
+// The duration of the volume ramps, in seconds.
+const RAMP_DURATION: f32 = 0.002;
+
+// The timers (and thus the beeper) are updated 60 times per second.
+const FRAMES_PER_SECOND: u32 = 60;
+
+const AUDIO_PATTERN_BITS: usize = AUDIO_PATTERN_SIZE * 8;
+
+pub struct Beeper {
+    sample_rate: u32,
+    frequency: f32,
+    volume: f32,
+    active: bool,
+    pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pitch: u8,
+    // The position in the current period of the wave (or in the pattern), between 0.0 and 1.0.
+    phase: f32,
+    // The current volume, which follows `volume` (or 0.0) with a ramp.
+    amplitude: f32,
+    // The fraction of a sample that `render_frame()` could not produce yet.
+    remainder: u32,
+}
+
+impl Default for Beeper {
+    fn default() -> Self {
+        Beeper::new(DEFAULT_SAMPLE_RATE)
+    }
+}
+
+impl Beeper {
+    pub fn new(sample_rate: u32) -> Self {
+        Beeper {
+            sample_rate: sample_rate.max(1),
+            frequency: DEFAULT_FREQUENCY,
+            volume: DEFAULT_VOLUME,
+            active: false,
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+            phase: 0.0,
+            amplitude: 0.0,
+            remainder: 0,
+        }
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn get_frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency.max(0.0);
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        self.volume
+    }
+
+    // Sets the volume, between 0.0 and 1.0.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    // Turns the beeper on or off, usually with `Interpreter::should_beep()` once per frame.
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    // Plays an XO-CHIP audio pattern instead of the square wave, or the square wave again when
+    // `pattern` is `None`.
+    pub fn set_pattern(&mut self, pattern: Option<[u8; AUDIO_PATTERN_SIZE]>) {
+        self.pattern = pattern;
+    }
+
+    pub fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+    }
+
+    // Returns the number of bits of the pattern played per second.
+    pub fn get_pattern_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    // Reads the sound state of the interpreter: the sound timer, the audio pattern and the
+    // pitch. It should be called once per frame.
+    pub fn update(&mut self, interpreter: &Interpreter) {
+        self.set_active(interpreter.should_beep());
+        self.set_pattern(interpreter.get_audio_pattern());
+        self.set_pitch(interpreter.get_pitch());
+    }
+
+    // Fills `buffer` with the next samples.
+    pub fn fill(&mut self, buffer: &mut [f32]) {
+        let target = if self.active { self.volume } else { 0.0 };
+        let ramp_step = 1.0 / (RAMP_DURATION * self.sample_rate as f32);
+        let phase_step = match self.pattern {
+            Some(_) => {
+                self.get_pattern_rate() / (AUDIO_PATTERN_BITS as f32 * self.sample_rate as f32)
+            }
+            None => self.frequency / self.sample_rate as f32,
+        };
+
+        for sample in buffer.iter_mut() {
+            if self.amplitude < target {
+                self.amplitude = (self.amplitude + ramp_step).min(target);
+            } else if self.amplitude > target {
+                self.amplitude = (self.amplitude - ramp_step).max(target);
+            }
+
+            if self.amplitude == 0.0 {
+                // Each beep starts at the beginning of a period.
+                self.phase = 0.0;
+                *sample = 0.0;
+                continue;
+            }
+
+            let high = match &self.pattern {
+                Some(pattern) => {
+                    let bit = ((self.phase * AUDIO_PATTERN_BITS as f32) as usize)
+                        .min(AUDIO_PATTERN_BITS - 1);
+                    pattern[bit / 8] & (0x80 >> (bit % 8)) != 0
+                }
+                None => self.phase < 0.5,
+            };
+            *sample = if high {
+                self.amplitude
+            } else {
+                -self.amplitude
+            };
+            self.phase = (self.phase + phase_step).fract();
+        }
+    }
+
+    // Appends the samples of a frame (1/60 s) to `samples`.
+    pub fn render_frame(&mut self, samples: &mut Vec<f32>) {
+        let total = self.sample_rate + self.remainder;
+        let len = (total / FRAMES_PER_SECOND) as usize;
+        self.remainder = total % FRAMES_PER_SECOND;
+
+        let start = samples.len();
+        samples.resize(start + len, 0.0);
+        self.fill(&mut samples[start..]);
+    }
+}
+
+// Writes mono samples to `writer` as a 16-bit PCM WAV file.
+pub fn write_wav<W: Write>(mut writer: W, sample_rate: u32, samples: &[f32]) -> io::Result<()> {
+    let data_size = (samples.len() * 2) as u32;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?; // PCM
+    writer.write_all(&1u16.to_le_bytes())?; // mono
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * 2).to_le_bytes())?; // bytes per second
+    writer.write_all(&2u16.to_le_bytes())?; // bytes per sample
+    writer.write_all(&16u16.to_le_bytes())?; // bits per sample
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())?;
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_all(&value.to_le_bytes())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,163 @@
+// Runs many interpreters side by side on several threads, e.g. to fuzz a ROM or to train an agent
+// on many environments at once. Each interpreter gets its own keypad state at every frame, and
+// the batch reports how many instructions it executed per second overall. An interpreter that
+// fails (see `InterpreterError`) is halted and the others keep running.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Interpreter, InterpreterError};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BatchStats {
+    pub frames: u64,
+    pub instructions: u64,
+    pub elapsed: Duration,
+    // The number of interpreters that are halted at the end of the run.
+    pub halted: usize,
+}
+
+impl BatchStats {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+pub struct Batch {
+    interpreters: Vec<Interpreter>,
+    errors: Vec<Option<InterpreterError>>,
+    threads: usize,
+}
+
+impl Batch {
+    // Creates a batch that uses as many threads as the machine has cores.
+    pub fn new(interpreters: Vec<Interpreter>) -> Self {
+        Batch {
+            errors: vec![None; interpreters.len()],
+            interpreters,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    // Creates `count` interpreters for the same ROM, seeded with `seed`, `seed + 1`, etc.
+    pub fn with_rom(rom: &[u8], count: usize, seed: u64) -> Result<Self, InterpreterError> {
+        let interpreters = (0..count)
+            .map(|i| Interpreter::with_seed(rom.to_vec(), seed.wrapping_add(i as u64)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Batch::new(interpreters))
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    // Enables or disables the block cache of every interpreter (see
+    // `Interpreter::set_block_cache()`).
+    pub fn set_block_cache(&mut self, enabled: bool) {
+        for interpreter in &mut self.interpreters {
+            interpreter.set_block_cache(enabled);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.interpreters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.interpreters.is_empty()
+    }
+
+    pub fn get_interpreters(&self) -> &[Interpreter] {
+        &self.interpreters
+    }
+
+    pub fn get_interpreters_mut(&mut self) -> &mut [Interpreter] {
+        &mut self.interpreters
+    }
+
+    pub fn into_interpreters(self) -> Vec<Interpreter> {
+        self.interpreters
+    }
+
+    // Returns the error that halted each interpreter, if any.
+    pub fn get_errors(&self) -> &[Option<InterpreterError>] {
+        &self.errors
+    }
+
+    // Runs `frames` frames of `speed` instructions on every interpreter. `input` returns the keys
+    // pressed (bit N is set when key N is pressed) given the index of an interpreter in the batch
+    // and the frame number, starting at 0.
+    pub fn run<F>(&mut self, frames: usize, speed: u8, input: F) -> BatchStats
+    where
+        F: Fn(usize, usize) -> u16 + Sync,
+    {
+        let start = Instant::now();
+        let chunk_size = self.interpreters.len().div_ceil(self.threads).max(1);
+        let input = &input;
+
+        // Each thread returns the number of frames and instructions it ran. The frame that fails
+        // is not counted, but the instructions executed before the error are.
+        let (frames_run, instructions): (u64, u64) = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .interpreters
+                .chunks_mut(chunk_size)
+                .zip(self.errors.chunks_mut(chunk_size))
+                .enumerate()
+                .map(|(chunk, (interpreters, errors))| {
+                    scope.spawn(move || {
+                        let mut frames_run = 0;
+                        let mut instructions = 0;
+
+                        for (i, (interpreter, error)) in
+                            interpreters.iter_mut().zip(errors.iter_mut()).enumerate()
+                        {
+                            let index = chunk * chunk_size + i;
+                            let instruction_count = interpreter.get_instruction_count();
+
+                            for frame in 0..frames {
+                                if error.is_some() {
+                                    break;
+                                }
+
+                                let keys = input(index, frame);
+                                let mut keypad = [false; 16];
+                                for (key, pressed) in keypad.iter_mut().enumerate() {
+                                    *pressed = keys & (1 << key) != 0;
+                                }
+
+                                interpreter.update_keypad(keypad);
+                                match interpreter.run_frame(speed) {
+                                    Ok(_) => frames_run += 1,
+                                    Err(e) => *error = Some(e),
+                                }
+                            }
+
+                            instructions += interpreter.get_instruction_count() - instruction_count;
+                        }
+
+                        (frames_run, instructions)
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .fold((0, 0), |(frames, instructions), (f, i)| {
+                    (frames + f, instructions + i)
+                })
+        });
+
+        BatchStats {
+            frames: frames_run,
+            instructions,
+            elapsed: start.elapsed(),
+            halted: self.errors.iter().filter(|e| e.is_some()).count(),
+        }
+    }
+}
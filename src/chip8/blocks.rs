@@ -0,0 +1,83 @@
+// A cache of basic blocks, i.e. sequences of instructions that are always executed one after the
+// other, to run ROMs faster. Each block is decoded once into `Op`s the first time its address is
+// reached, and executed from the cache afterwards without fetching and decoding its opcodes
+// again.
+//
+// A block ends with the first instruction that can jump, skip, wait for a key or write to RAM
+// (see `Op::ends_block()`). It remembers the version of the RAM pages it was decoded from, and it
+// is decoded again when one of them has been written to since (self-modifying code).
+
+use super::mmu::{self, MMU};
+use super::op::Op;
+
+// Long blocks are split so that decoding data by mistake does not cost too much.
+const MAX_BLOCK_LEN: usize = 32;
+
+#[derive(Clone)]
+struct Block {
+    ops: Vec<Op>,
+    // The pages the block has been decoded from, with their versions at that time.
+    pages: Vec<(usize, u64)>,
+}
+
+impl Block {
+    fn decode(mmu: &MMU, start: usize) -> Self {
+        let mut ops = Vec::new();
+        let mut pages: Vec<(usize, u64)> = Vec::new();
+        let mut addr = start;
+
+        loop {
+            let op = Op::decode(mmu.read_word(addr));
+            ops.push(op);
+
+            for byte in [addr, (addr + 1) % mmu::RAM_SIZE] {
+                let page = byte / mmu::PAGE_SIZE;
+                if !pages.iter().any(|(p, _)| *p == page) {
+                    pages.push((page, mmu.get_page_version(page)));
+                }
+            }
+
+            addr += 2;
+            // The program counter is not wrapped around in the middle of a block, since it would
+            // not hold the same value as with `CPU::step()`.
+            if op.ends_block() || ops.len() == MAX_BLOCK_LEN || addr >= mmu::RAM_SIZE {
+                break;
+            }
+        }
+
+        Block { ops, pages }
+    }
+
+    fn is_valid(&self, mmu: &MMU) -> bool {
+        self.pages
+            .iter()
+            .all(|(page, version)| mmu.get_page_version(*page) == *version)
+    }
+}
+
+#[derive(Clone)]
+pub struct BlockCache {
+    // The blocks indexed by their start address. They are boxed to keep the empty cache small.
+    blocks: Vec<Option<Box<Block>>>,
+}
+
+impl Default for BlockCache {
+    fn default() -> Self {
+        BlockCache {
+            blocks: vec![None; mmu::RAM_SIZE],
+        }
+    }
+}
+
+impl BlockCache {
+    // Returns the decoded instructions of the block that starts at `addr`, which must be lower
+    // than the RAM size.
+    pub fn get(&mut self, mmu: &MMU, addr: usize) -> &[Op] {
+        let slot = &mut self.blocks[addr];
+        if !slot.as_ref().is_some_and(|block| block.is_valid(mmu)) {
+            *slot = Some(Box::new(Block::decode(mmu, addr)));
+        }
+
+        slot.as_ref().map_or(&[], |block| &block.ops)
+    }
+}
@@ -0,0 +1,181 @@
+// Captures the display as a PNG screenshot or as an animated GIF, e.g. for bug reports. Both
+// formats use an indexed image with the 4 colors of the palette, and each pixel of `vram` becomes
+// a `scale` x `scale` square.
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+use super::{Palette, HEIGHT, WIDTH};
+
+// The delays of the GIF frames are expressed in hundredths of a second and the interpreter runs
+// at 60 frames per second.
+const CENTISECONDS_PER_SECOND: u64 = 100;
+const FRAMES_PER_SECOND: u64 = 60;
+
+// Writes `vram` to `writer` as a PNG image.
+pub fn write_png<W: Write>(writer: W, vram: &[u8], palette: &Palette, scale: u8) -> io::Result<()> {
+    write_indexed_png(writer, vram, WIDTH, palette, scale)
+}
+
+// Writes an image of any size whose pixels are palette indexes, `width` pixels per row, e.g. the
+// sprite sheets of `sprites.rs`.
+pub(crate) fn write_indexed_png<W: Write>(
+    writer: W,
+    pixels: &[u8],
+    width: usize,
+    palette: &Palette,
+    scale: u8,
+) -> io::Result<()> {
+    let scale = scale.max(1);
+    let height = pixels.len() / width;
+    let mut encoder = png::Encoder::new(
+        writer,
+        (width * scale as usize) as u32,
+        (height * scale as usize) as u32,
+    );
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(rgb_palette(palette));
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&scale_pixels(pixels, width, scale))?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+// Returns `vram` encoded as a PNG image.
+pub fn png(vram: &[u8], palette: &Palette, scale: u8) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    write_png(&mut bytes, vram, palette, scale)?;
+
+    Ok(bytes)
+}
+
+// Records the display at each frame and writes an animated GIF that loops forever. Consecutive
+// identical frames are merged into a single GIF frame that lasts longer.
+pub struct GifRecorder<W: Write> {
+    encoder: gif::Encoder<W>,
+    scale: u8,
+    // The last frame that has been added, not written yet because it could still be extended.
+    pending: Option<Vec<u8>>,
+    pending_frames: u64,
+    // The number of frames and the time (in centiseconds) written so far. Delays are computed
+    // from these totals so that rounding errors do not accumulate.
+    frames: u64,
+    centiseconds: u64,
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub fn new(writer: W, palette: &Palette, scale: u8) -> io::Result<Self> {
+        let scale = scale.max(1);
+        let mut encoder = gif::Encoder::new(
+            writer,
+            (WIDTH * scale as usize) as u16,
+            (HEIGHT * scale as usize) as u16,
+            &rgb_palette(palette),
+        )
+        .map_err(gif_error)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(gif_error)?;
+
+        Ok(GifRecorder {
+            encoder,
+            scale,
+            pending: None,
+            pending_frames: 0,
+            frames: 0,
+            centiseconds: 0,
+        })
+    }
+
+    // Must be called once per frame (i.e. at 60 Hz).
+    pub fn add_frame(&mut self, vram: &[u8]) -> io::Result<()> {
+        match &self.pending {
+            Some(pending) if pending[..] == vram[..] => {
+                self.pending_frames += 1;
+            }
+            _ => {
+                self.flush()?;
+                self.pending = Some(vram.to_vec());
+                self.pending_frames = 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the number of frames that have been added so far.
+    pub fn len(&self) -> u64 {
+        self.frames + self.pending_frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Writes the last frame and the end of the GIF, then returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush()?;
+
+        self.encoder.into_inner()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let vram = match self.pending.take() {
+            Some(vram) => vram,
+            None => return Ok(()),
+        };
+
+        self.frames += self.pending_frames;
+        self.pending_frames = 0;
+        let centiseconds =
+            (self.frames * CENTISECONDS_PER_SECOND + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND;
+        let delay = centiseconds - self.centiseconds;
+        self.centiseconds = centiseconds;
+
+        let frame = gif::Frame {
+            width: (WIDTH * self.scale as usize) as u16,
+            height: (HEIGHT * self.scale as usize) as u16,
+            delay: delay.min(u16::MAX as u64) as u16,
+            buffer: Cow::Owned(scale_pixels(&vram, WIDTH, self.scale)),
+            ..gif::Frame::default()
+        };
+
+        self.encoder.write_frame(&frame).map_err(gif_error)
+    }
+}
+
+fn rgb_palette(palette: &Palette) -> Vec<u8> {
+    palette
+        .colors
+        .iter()
+        .flat_map(|color| [(color >> 16) as u8, (color >> 8) as u8, *color as u8])
+        .collect()
+}
+
+// Returns the palette index of each pixel of the scaled image.
+fn scale_pixels(vram: &[u8], width: usize, scale: u8) -> Vec<u8> {
+    let scale = scale as usize;
+    let mut pixels = Vec::with_capacity(vram.len() * scale * scale);
+
+    for row in vram.chunks_exact(width) {
+        let line: Vec<u8> = row
+            .iter()
+            .flat_map(|pixel| std::iter::repeat_n(pixel & 0x3, scale))
+            .collect();
+        for _ in 0..scale {
+            pixels.extend_from_slice(&line);
+        }
+    }
+
+    pixels
+}
+
+fn gif_error(e: gif::EncodingError) -> io::Error {
+    match e {
+        gif::EncodingError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    }
+}
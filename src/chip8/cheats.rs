@@ -0,0 +1,178 @@
+// Cheats freeze bytes of RAM to fixed values (e.g. the number of lives), like the RAM cheat codes of
+// classic consoles. The frozen values are written back by the MMU at each frame, when the timers
+// are updated, so that the ROM cannot change them for long.
+//
+// A cheat list has one code per line, an address and a value in hexadecimal separated by a colon.
+// Blank lines and lines starting with "#" are ignored:
+//
+//   # Space Invaders
+//   2F0:03
+//
+// The address of a value is usually unknown: `Search` finds it by comparing snapshots of RAM, e.g.
+// by keeping the addresses whose value has decreased after losing a life.
+//
+// Set them with `Interpreter::set_cheats()`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::mmu::RAM_SIZE;
+use super::Interpreter;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CheatsError {
+    // The line number, starting at 1.
+    InvalidLine(usize),
+}
+
+impl fmt::Display for CheatsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheatsError::InvalidLine(line) => write!(f, "line {}: invalid cheat code", line),
+        }
+    }
+}
+
+impl std::error::Error for CheatsError {}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cheats {
+    values: BTreeMap<u16, u8>,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Cheats::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, CheatsError> {
+        let mut cheats = Cheats::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let code = line
+                .split_once(':')
+                .and_then(|(addr, value)| {
+                    let addr = u16::from_str_radix(addr.trim(), 16).ok()?;
+                    let value = u8::from_str_radix(value.trim(), 16).ok()?;
+                    Some((addr, value))
+                })
+                .filter(|(addr, _)| (*addr as usize) < RAM_SIZE);
+            match code {
+                Some((addr, value)) => cheats.insert(addr, value),
+                None => return Err(CheatsError::InvalidLine(n + 1)),
+            }
+        }
+
+        Ok(cheats)
+    }
+
+    // Freezes the byte at `addr` to `value`, replacing the previous cheat for this address.
+    pub fn insert(&mut self, addr: u16, value: u8) {
+        self.values.insert(addr % RAM_SIZE as u16, value);
+    }
+
+    pub fn remove(&mut self, addr: u16) -> Option<u8> {
+        self.values.remove(&(addr % RAM_SIZE as u16))
+    }
+
+    pub fn get(&self, addr: u16) -> Option<u8> {
+        self.values.get(&(addr % RAM_SIZE as u16)).copied()
+    }
+
+    // Iterates over the cheats, sorted by address.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u8)> + '_ {
+        self.values.iter().map(|(addr, value)| (*addr, *value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+// Formats the cheats as a list that `Cheats::parse()` reads back.
+impl fmt::Display for Cheats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (addr, value) in self.iter() {
+            writeln!(f, "{:03X}:{:02X}", addr, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+// How the current value of a byte compares to its value in the previous snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    // The current value is equal to the given value.
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Comparison {
+    fn matches(self, previous: u8, current: u8) -> bool {
+        match self {
+            Comparison::Equal(value) => current == value,
+            Comparison::Changed => current != previous,
+            Comparison::Unchanged => current == previous,
+            Comparison::Increased => current > previous,
+            Comparison::Decreased => current < previous,
+        }
+    }
+}
+
+// Searches the address of a value by filtering the bytes of RAM, one snapshot at a time. Every
+// address is a candidate at first.
+#[derive(Clone)]
+pub struct Search {
+    snapshot: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl Search {
+    pub fn new(interpreter: &Interpreter) -> Self {
+        Search {
+            snapshot: ram(interpreter),
+            candidates: (0..RAM_SIZE as u16).collect(),
+        }
+    }
+
+    // Keeps the candidates whose current value matches `comparison`, then takes a new snapshot
+    // for the next filter.
+    pub fn filter(&mut self, interpreter: &Interpreter, comparison: Comparison) {
+        let current = ram(interpreter);
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|addr| {
+            let addr = *addr as usize;
+            comparison.matches(snapshot[addr], current[addr])
+        });
+        self.snapshot = current;
+    }
+
+    // Returns the addresses that matched every filter so far.
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    // Returns the value of a byte in the last snapshot.
+    pub fn get_value(&self, addr: u16) -> u8 {
+        self.snapshot[addr as usize % RAM_SIZE]
+    }
+}
+
+fn ram(interpreter: &Interpreter) -> Vec<u8> {
+    (0..RAM_SIZE as u16)
+        .map(|addr| interpreter.read_byte(addr))
+        .collect()
+}
@@ -0,0 +1,152 @@
+// Tags each byte of RAM with the ways it has been accessed, to tell code from data when reverse
+// engineering a ROM: executed as an instruction, read by FX65 (or F002), written by FX33 or FX55,
+// or drawn as a sprite by DXYN. A byte can have several tags, e.g. self-modifying code.
+//
+// The sprites that have been drawn are collected too, with the address in I and the height N of
+// each DXYN.
+//
+// Enable it with `Interpreter::set_coverage()` and get it with `Interpreter::get_coverage()`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::cpu::AUDIO_PATTERN_SIZE;
+use super::mmu;
+use super::op::Op;
+use super::sprites::Sprite;
+
+pub const EXECUTED: u8 = 1 << 0;
+pub const READ: u8 = 1 << 1;
+pub const WRITTEN: u8 = 1 << 2;
+pub const DRAWN: u8 = 1 << 3;
+
+const TAGS: [(u8, &str); 4] = [
+    (EXECUTED, "executed"),
+    (READ, "read"),
+    (WRITTEN, "written"),
+    (DRAWN, "drawn"),
+];
+
+#[derive(Clone)]
+pub struct Coverage {
+    tags: Vec<u8>,
+    // The number of times a byte got a new tag, to know when the coverage has changed.
+    changes: u64,
+    // The tallest sprite drawn at each address.
+    sprites: BTreeMap<u16, u8>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Coverage {
+            tags: vec![0; mmu::RAM_SIZE],
+            changes: 0,
+            sprites: BTreeMap::new(),
+        }
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Coverage::default()
+    }
+
+    // Records the instruction that has been executed at `addr`, with `i` the value of I before
+    // its execution.
+    pub(crate) fn record(&mut self, addr: usize, op: Op, i: usize) {
+        self.tag(addr, 2, EXECUTED);
+
+        match op {
+            Op::Draw(_, _, height) => {
+                self.tag(i, height as usize, DRAWN);
+                if height > 0 {
+                    let addr = (i % mmu::RAM_SIZE) as u16;
+                    let tallest = self.sprites.entry(addr).or_insert(0);
+                    *tallest = (*tallest).max(height);
+                }
+            }
+            Op::LoadRegisters(x) => self.tag(i, x as usize + 1, READ),
+            Op::LoadAudioPattern => self.tag(i, AUDIO_PATTERN_SIZE, READ),
+            Op::StoreRegisters(x) => self.tag(i, x as usize + 1, WRITTEN),
+            Op::StoreBcd(_) => self.tag(i, 3, WRITTEN),
+            _ => {}
+        }
+    }
+
+    fn tag(&mut self, start: usize, len: usize, tag: u8) {
+        for addr in start..start + len {
+            let tags = &mut self.tags[addr % mmu::RAM_SIZE];
+            if *tags & tag == 0 {
+                *tags |= tag;
+                self.changes += 1;
+            }
+        }
+    }
+
+    // Returns the tags of the byte at `addr`, e.g. `EXECUTED | WRITTEN`.
+    pub fn get(&self, addr: u16) -> u8 {
+        self.tags[addr as usize % mmu::RAM_SIZE]
+    }
+
+    // Whether the byte at `addr` has been read or drawn, but never executed.
+    pub fn is_data(&self, addr: u16) -> bool {
+        let tags = self.get(addr);
+        tags & EXECUTED == 0 && tags & (READ | DRAWN) != 0
+    }
+
+    // Returns the sprites that have been drawn, sorted by address.
+    pub fn sprites(&self) -> Vec<Sprite> {
+        self.sprites
+            .iter()
+            .map(|(addr, height)| Sprite::new(*addr, *height))
+            .collect()
+    }
+
+    pub fn get_changes(&self) -> u64 {
+        self.changes
+    }
+
+    // The tags of every byte of RAM.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.tags
+    }
+
+    // Returns the ranges of consecutive bytes that have `tag`, as (start, end) with `end`
+    // excluded.
+    pub fn ranges(&self, tag: u8) -> Vec<(u16, u16)> {
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+
+        for (addr, tags) in self.tags.iter().enumerate() {
+            if tags & tag == 0 {
+                continue;
+            }
+
+            let addr = addr as u16;
+            match ranges.last_mut() {
+                Some((_, end)) if *end == addr => *end += 1,
+                _ => ranges.push((addr, addr + 1)),
+            }
+        }
+
+        ranges
+    }
+
+    // Exports the ranges of each tag, e.g. `{"executed": [[512, 600]], "read": [], ...}`. The end
+    // of a range is excluded.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\n");
+
+        for (n, (tag, name)) in TAGS.iter().enumerate() {
+            let ranges: Vec<String> = self
+                .ranges(*tag)
+                .iter()
+                .map(|(start, end)| format!("[{}, {}]", start, end))
+                .collect();
+            write!(json, "  \"{}\": [{}]", name, ranges.join(", ")).unwrap();
+            json.push_str(if n + 1 < TAGS.len() { ",\n" } else { "\n" });
+        }
+        json.push_str("}\n");
+
+        json
+    }
+}
@@ -1,12 +1,27 @@
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::convert::TryInto;
 use std::fmt;
 
+use super::blocks::BlockCache;
+use super::coverage::Coverage;
 use super::mmu;
+use super::op::Op;
+use super::profiler::Profile;
+use super::quirks::Quirks;
 
 pub const WIDTH: usize = 64;
 pub const HEIGHT: usize = 32;
 
-#[derive(Default)]
+// The size of the XO-CHIP audio pattern buffer (128 bits).
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+// The XO-CHIP pitch register defaults to 64, i.e. a playback rate of 4000 bits per second.
+pub const DEFAULT_PITCH: u8 = 64;
+// The index register is 16-bit: it wraps around when it is incremented past 0xFFFF (which is also
+// how it is stored in save states).
+const I_MASK: usize = 0xFFFF;
+
+#[derive(Clone, Default)]
 pub struct Registers {
     // Note: the VF register doubles as a flag for some instructions; thus, it should be avoided.
     // In an addition operation, VF is the carry flag, while in subtraction, it is the "no borrow"
@@ -14,7 +29,8 @@ pub struct Registers {
     pub v: [u8; 16],
     // Index register
     pub i: usize,
-    // Program counter
+    // Program counter. It is reduced modulo the size of the RAM before each instruction is
+    // fetched, so it can be past 0xFFF after the last instruction of the RAM or a BNNN jump.
     pub pc: usize,
     // Stack pointer
     pub sp: usize,
@@ -59,7 +75,7 @@ impl fmt::Debug for Registers {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 struct Keypad {
     pub state: [bool; 16],
     pub waiting: bool,
@@ -101,6 +117,233 @@ impl fmt::Debug for Keypad {
     }
 }
 
+// A snapshot (or save state) of the whole machine, which can be restored later on. The ROM is not
+// part of it because it never changes.
+#[derive(Clone)]
+pub struct Snapshot {
+    ram: [u8; mmu::RAM_SIZE],
+    vram: [u8; HEIGHT * WIDTH],
+    registers: Registers,
+    stack: [u16; 16],
+    keypad: Keypad,
+    audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pitch: u8,
+    quirks: Quirks,
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    InvalidSize,
+    InvalidStack,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::InvalidMagic => write!(f, "not a snapshot"),
+            SnapshotError::UnsupportedVersion(v) => write!(f, "unsupported snapshot version {}", v),
+            SnapshotError::InvalidSize => write!(f, "snapshot has an invalid size"),
+            SnapshotError::InvalidStack => write!(f, "snapshot has an invalid stack pointer"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+// The errors that stop the execution of a ROM. The interpreter stays on the faulty instruction, so
+// stepping again returns the same error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterpreterError {
+    RomTooLarge(usize),
+    UnsupportedOpcode { opcode: u16, addr: u16 },
+    // A subroutine was called while the 16 levels of the stack were in use.
+    StackOverflow { addr: u16 },
+    // A subroutine returned while the stack was empty.
+    StackUnderflow { addr: u16 },
+}
+
+impl fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterpreterError::RomTooLarge(size) => write!(
+                f,
+                "ROM is too large ({} bytes, the maximum is {})",
+                size,
+                mmu::MAX_ROM_SIZE
+            ),
+            InterpreterError::UnsupportedOpcode { opcode, addr } => {
+                write!(f, "unsupported opcode 0x{:04X} @ ${:04X}", opcode, addr)
+            }
+            InterpreterError::StackOverflow { addr } => write!(f, "stack overflow @ ${:04X}", addr),
+            InterpreterError::StackUnderflow { addr } => {
+                write!(f, "stack underflow @ ${:04X}", addr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InterpreterError {}
+
+const SNAPSHOT_MAGIC: &[u8; 3] = b"C8S";
+const SNAPSHOT_VERSION: u8 = 1;
+
+// Serialized snapshots always have the same size (all integers are little-endian):
+//
+//   4 bytes     magic: "C8S" followed by the format version
+//   4096 bytes  RAM
+//   2048 bytes  VRAM
+//   16 bytes    V0 to VF
+//   7 bytes     I (2 bytes), PC (2 bytes), SP, delay timer and sound timer
+//   32 bytes    stack (16 x 2 bytes)
+//   4 bytes     keypad state (2 bytes, bit N is set when key N is pressed), whether FX0A is
+//               waiting for a key and its register
+//   17 bytes    whether there is an XO-CHIP audio pattern, then the pattern
+//   1 byte      pitch
+//   1 byte      quirks (see `Quirks::to_bits()`)
+//   8 bytes     RNG seed
+//   56 bytes    RNG state: ChaCha seed (32 bytes), stream (8 bytes) and word position (16 bytes)
+impl Snapshot {
+    pub const SIZE: usize = 4
+        + mmu::RAM_SIZE
+        + HEIGHT * WIDTH
+        + 16
+        + 7
+        + 32
+        + 4
+        + 1
+        + AUDIO_PATTERN_SIZE
+        + 1
+        + 1
+        + 8
+        + 56;
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Snapshot::SIZE);
+        bytes.extend_from_slice(SNAPSHOT_MAGIC);
+        bytes.push(SNAPSHOT_VERSION);
+        bytes.extend_from_slice(&self.ram);
+        bytes.extend_from_slice(&self.vram);
+
+        bytes.extend_from_slice(&self.registers.v);
+        bytes.extend_from_slice(&(self.registers.i as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.registers.pc as u16).to_le_bytes());
+        bytes.push(self.registers.sp as u8);
+        bytes.push(self.registers.delay);
+        bytes.push(self.registers.sound);
+
+        for addr in self.stack.iter() {
+            bytes.extend_from_slice(&addr.to_le_bytes());
+        }
+
+        let keypad = self
+            .keypad
+            .state
+            .iter()
+            .enumerate()
+            .fold(0u16, |bits, (i, pressed)| bits | ((*pressed as u16) << i));
+        bytes.extend_from_slice(&keypad.to_le_bytes());
+        bytes.push(self.keypad.waiting as u8);
+        bytes.push(self.keypad.register as u8);
+
+        bytes.push(self.audio_pattern.is_some() as u8);
+        bytes.extend_from_slice(&self.audio_pattern.unwrap_or_default());
+        bytes.push(self.pitch);
+
+        bytes.push(self.quirks.to_bits());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.rng.get_seed());
+        bytes.extend_from_slice(&self.rng.get_stream().to_le_bytes());
+        bytes.extend_from_slice(&self.rng.get_word_pos().to_le_bytes());
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+        if bytes.len() < 4 || &bytes[0..3] != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        if bytes[3] != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(bytes[3]));
+        }
+        if bytes.len() != Snapshot::SIZE {
+            return Err(SnapshotError::InvalidSize);
+        }
+
+        // The size has been checked above so the reads below cannot fail.
+        let mut offset = 4;
+        let mut take = |len: usize| {
+            let slice = &bytes[offset..offset + len];
+            offset += len;
+            slice
+        };
+
+        let ram = take(mmu::RAM_SIZE).try_into().unwrap();
+        let vram = take(HEIGHT * WIDTH).try_into().unwrap();
+
+        let registers = Registers {
+            v: take(16).try_into().unwrap(),
+            i: u16::from_le_bytes(take(2).try_into().unwrap()) as usize,
+            pc: u16::from_le_bytes(take(2).try_into().unwrap()) as usize,
+            sp: take(1)[0] as usize,
+            delay: take(1)[0],
+            sound: take(1)[0],
+        };
+
+        if registers.sp > 16 {
+            return Err(SnapshotError::InvalidStack);
+        }
+
+        let mut stack = [0; 16];
+        for addr in stack.iter_mut() {
+            *addr = u16::from_le_bytes(take(2).try_into().unwrap());
+        }
+
+        let bits = u16::from_le_bytes(take(2).try_into().unwrap());
+        let mut keypad = Keypad {
+            state: [false; 16],
+            waiting: take(1)[0] != 0,
+            register: (take(1)[0] & 0xF) as usize,
+        };
+        for (i, pressed) in keypad.state.iter_mut().enumerate() {
+            *pressed = bits & (1 << i) != 0;
+        }
+
+        let has_audio_pattern = take(1)[0] != 0;
+        let pattern: [u8; AUDIO_PATTERN_SIZE] = take(AUDIO_PATTERN_SIZE).try_into().unwrap();
+        let audio_pattern = if has_audio_pattern {
+            Some(pattern)
+        } else {
+            None
+        };
+        let pitch = take(1)[0];
+
+        let quirks = Quirks::from_bits(take(1)[0]);
+        let seed = u64::from_le_bytes(take(8).try_into().unwrap());
+        let mut rng = ChaCha8Rng::from_seed(take(32).try_into().unwrap());
+        rng.set_stream(u64::from_le_bytes(take(8).try_into().unwrap()));
+        rng.set_word_pos(u128::from_le_bytes(take(16).try_into().unwrap()));
+
+        Ok(Snapshot {
+            ram,
+            vram,
+            registers,
+            stack,
+            keypad,
+            audio_pattern,
+            pitch,
+            quirks,
+            seed,
+            rng,
+        })
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone)]
 pub struct CPU {
     pub mmu: mmu::MMU,
 
@@ -111,13 +354,26 @@ pub struct CPU {
     // The stack is only used to store return addresses when subroutines are called.
     stack: [u16; 16],
     keypad: Keypad,
-
-    rng: rand::rngs::ThreadRng,
-    debug: bool,
+    // XO-CHIP audio: a 1-bit pattern that is played in a loop while the sound timer is non-zero
+    // (instead of the beeper) once a ROM has loaded one, and its playback rate.
+    audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pitch: u8,
+
+    pub quirks: Quirks,
+    // The random number generator is seeded so that a run can be reproduced exactly (given the
+    // same inputs). It is re-seeded on reset.
+    seed: u64,
+    rng: ChaCha8Rng,
+    // The number of instructions executed successfully since the CPU has been created.
+    instruction_count: u64,
+    // Decoded instructions, when the block cache is enabled.
+    block_cache: Option<Box<BlockCache>>,
+    profile: Option<Box<Profile>>,
+    coverage: Option<Box<Coverage>>,
 }
 
 impl CPU {
-    pub fn new(mmu: mmu::MMU) -> Self {
+    pub fn new(mmu: mmu::MMU, seed: u64) -> Self {
         let mut cpu = CPU {
             mmu,
             vram: [0; HEIGHT * WIDTH],
@@ -125,51 +381,176 @@ impl CPU {
             registers: Registers::default(),
             stack: [0; 16],
             keypad: Keypad::default(),
-            rng: rand::thread_rng(),
-            debug: false,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            quirks: Quirks::default(),
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            instruction_count: 0,
+            block_cache: None,
+            profile: None,
+            coverage: None,
         };
         cpu.reset();
         cpu
     }
 
-    pub fn enable_debug(&mut self) {
-        self.debug = true;
-    }
-
-    pub fn disable_debug(&mut self) {
-        self.debug = false;
-    }
-
     pub fn fetch_instruction(&mut self) -> u16 {
-        return self.mmu.read_word(self.registers.pc);
+        self.mmu.read_word(self.registers.pc)
     }
 
-    pub fn step(&mut self, keypad: [bool; 16]) {
+    pub fn step(&mut self, keypad: [bool; 16]) -> Result<(), InterpreterError> {
         self.vram_changed = false;
         self.keypad.state = keypad;
 
         if self.keypad.waiting {
+            // FX0A has already been executed, and the program counter points to the next
+            // instruction.
+            let addr = self.registers.pc.wrapping_sub(2);
+
             for i in 0..=15 {
                 if self.keypad.state[i] {
                     self.keypad.waiting = false;
                     self.registers.v[self.keypad.register] = i as u8;
-                    self.registers.pc += 2;
                     break;
                 }
             }
+
+            let op = Op::WaitForKey(self.keypad.register as u8);
+            self.instruction_count += 1;
+            if let Some(profile) = &mut self.profile {
+                profile.record(addr, op);
+            }
+            if let Some(coverage) = &mut self.coverage {
+                coverage.record(addr, op, self.registers.i);
+            }
         } else {
+            self.registers.pc %= mmu::RAM_SIZE;
             let opcode = self.fetch_instruction();
 
-            if self.debug {
-                println!(
-                    "Executing opcode=0x{:04X} (pc=0x{:04X})",
-                    opcode, self.registers.pc
-                );
+            let addr = self.registers.pc;
+            let i = self.registers.i;
+            let op = Op::decode(opcode);
+            self.registers.pc += 2;
+
+            if let Err(e) = self.exec(op) {
+                self.registers.pc -= 2;
+                return Err(e);
             }
+            self.instruction_count += 1;
 
-            self.registers.pc += 2;
+            if let Some(profile) = &mut self.profile {
+                profile.record(addr, op);
+            }
+            if let Some(coverage) = &mut self.coverage {
+                coverage.record(addr, op, i);
+            }
+        }
+
+        Ok(())
+    }
 
-            self.execute(opcode);
+    // Executes `count` instructions, or until one of them fails, and returns whether any of them
+    // changed the display. This is the same as calling `step()` `count` times, but faster when
+    // the block cache is enabled.
+    pub fn run(&mut self, keypad: [bool; 16], count: usize) -> Result<bool, InterpreterError> {
+        let mut cache = match self.block_cache.take() {
+            Some(cache) if !self.is_tracing() => cache,
+            cache => {
+                self.block_cache = cache;
+
+                let mut redraw = false;
+                for _ in 0..count {
+                    self.step(keypad)?;
+                    redraw |= self.vram_changed;
+                }
+                return Ok(redraw);
+            }
+        };
+
+        let result = self.run_blocks(&mut cache, keypad, count);
+        self.block_cache = Some(cache);
+        result
+    }
+
+    fn run_blocks(
+        &mut self,
+        cache: &mut BlockCache,
+        keypad: [bool; 16],
+        count: usize,
+    ) -> Result<bool, InterpreterError> {
+        let mut redraw = false;
+        let mut executed = 0;
+
+        while executed < count {
+            if self.keypad.waiting {
+                self.step(keypad)?;
+                redraw |= self.vram_changed;
+                executed += 1;
+                continue;
+            }
+
+            self.keypad.state = keypad;
+            self.registers.pc %= mmu::RAM_SIZE;
+
+            for op in cache
+                .get(&self.mmu, self.registers.pc)
+                .iter()
+                .take(count - executed)
+            {
+                self.vram_changed = false;
+                self.registers.pc += 2;
+
+                if let Err(e) = self.exec(*op) {
+                    self.registers.pc -= 2;
+                    return Err(e);
+                }
+
+                redraw |= self.vram_changed;
+                executed += 1;
+                self.instruction_count += 1;
+            }
+        }
+
+        Ok(redraw)
+    }
+
+    pub fn get_instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+
+    pub fn is_block_cache_enabled(&self) -> bool {
+        self.block_cache.is_some()
+    }
+
+    pub fn set_block_cache(&mut self, enabled: bool) {
+        if enabled != self.block_cache.is_some() {
+            self.block_cache = if enabled { Some(Box::default()) } else { None };
+        }
+    }
+
+    // Whether each instruction has to be looked at, which the block cache does not support.
+    fn is_tracing(&self) -> bool {
+        self.profile.is_some() || self.coverage.is_some()
+    }
+
+    pub fn get_profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    pub fn set_profiling(&mut self, enabled: bool) {
+        if enabled != self.profile.is_some() {
+            self.profile = if enabled { Some(Box::default()) } else { None };
+        }
+    }
+
+    pub fn get_coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_deref()
+    }
+
+    pub fn set_coverage(&mut self, enabled: bool) {
+        if enabled != self.coverage.is_some() {
+            self.coverage = if enabled { Some(Box::default()) } else { None };
         }
     }
 
@@ -201,9 +582,60 @@ impl CPU {
         };
         self.stack = [0; 16];
         self.keypad = Keypad::default();
+        self.audio_pattern = None;
+        self.pitch = DEFAULT_PITCH;
+        self.rng = ChaCha8Rng::seed_from_u64(self.seed);
+        if let Some(profile) = &mut self.profile {
+            profile.clear_call_stack();
+        }
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_audio_pattern(&self) -> Option<[u8; AUDIO_PATTERN_SIZE]> {
+        self.audio_pattern
+    }
+
+    pub fn get_pitch(&self) -> u8 {
+        self.pitch
     }
 
-    pub fn read_byte(&mut self, addr: u16) -> u8 {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            ram: self.mmu.get_ram(),
+            vram: self.vram,
+            registers: self.registers.clone(),
+            stack: self.stack,
+            keypad: self.keypad.clone(),
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            quirks: self.quirks,
+            seed: self.seed,
+            rng: self.rng.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.mmu.set_ram(snapshot.ram);
+        self.vram = snapshot.vram;
+        // The display has to be redrawn entirely.
+        self.vram_changed = true;
+        self.registers = snapshot.registers.clone();
+        self.stack = snapshot.stack;
+        self.keypad = snapshot.keypad.clone();
+        self.audio_pattern = snapshot.audio_pattern;
+        self.pitch = snapshot.pitch;
+        self.quirks = snapshot.quirks;
+        self.seed = snapshot.seed;
+        self.rng = snapshot.rng.clone();
+        if let Some(profile) = &mut self.profile {
+            profile.clear_call_stack();
+        }
+    }
+
+    pub fn read_byte(&self, addr: u16) -> u8 {
         self.mmu.read_byte(addr as usize)
     }
 
@@ -212,149 +644,96 @@ impl CPU {
         self.mmu.load_rom(rom);
     }
 
-    fn execute(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
-
-        return match opcode & 0xF000 {
-            0x0000 => match opcode & 0x000F {
-                // disp_clear() (0x00E0)
-                0x0000 => {
-                    for x in 0..WIDTH {
-                        for y in 0..HEIGHT {
-                            self.vram[x + y * WIDTH] = 0;
-                        }
-                    }
-                    self.vram_changed = true;
-                }
-                // return (0x00EE)
-                0x000E => {
-                    self.registers.sp -= 1;
-                    self.registers.pc = self.stack[self.registers.sp] as usize;
+    // Executes a decoded instruction, PC already points to the next one.
+    fn exec(&mut self, op: Op) -> Result<(), InterpreterError> {
+        match op {
+            // disp_clear()
+            Op::ClearScreen => {
+                self.vram = [0; HEIGHT * WIDTH];
+                self.vram_changed = true;
+            }
+            // return
+            Op::Return => {
+                if self.registers.sp == 0 {
+                    return Err(InterpreterError::StackUnderflow {
+                        addr: self.current_addr(),
+                    });
                 }
-                _ => self.unsupported_opcode(opcode),
-            },
+                self.registers.sp -= 1;
+                self.registers.pc = self.stack[self.registers.sp] as usize;
+            }
             // goto NNN;
-            0x1000 => {
-                self.registers.pc = (opcode & 0x0FFF) as usize;
+            Op::Jump(nnn) => {
+                self.registers.pc = nnn as usize;
             }
             // *(0xNNN)()
-            0x2000 => {
+            Op::Call(nnn) => {
+                if self.registers.sp == self.stack.len() {
+                    return Err(InterpreterError::StackOverflow {
+                        addr: self.current_addr(),
+                    });
+                }
                 self.stack[self.registers.sp] = self.registers.pc as u16;
                 self.registers.sp += 1;
-                self.registers.pc = (opcode & 0x0FFF) as usize;
+                self.registers.pc = nnn as usize;
             }
             // if (Vx == NN)
-            0x3000 => {
-                let val = (opcode & 0x00FF) as u8;
-
-                if self.registers.v[x] == val {
+            Op::SkipIfEqual(x, nn) => {
+                if self.registers.v[x as usize] == nn {
                     self.registers.pc += 2;
                 }
             }
             // if (Vx != NN)
-            0x4000 => {
-                let val = (opcode & 0x00FF) as u8;
-
-                if self.registers.v[x] != val {
+            Op::SkipIfNotEqual(x, nn) => {
+                if self.registers.v[x as usize] != nn {
                     self.registers.pc += 2;
                 }
             }
             // if (Vx == Vy)
-            0x5000 => {
-                if self.registers.v[x] == self.registers.v[y] {
+            Op::SkipIfRegistersEqual(x, y) => {
+                if self.registers.v[x as usize] == self.registers.v[y as usize] {
                     self.registers.pc += 2;
                 }
             }
             // Vx = NN
-            0x6000 => {
-                self.registers.v[x] = (opcode & 0x00FF) as u8;
+            Op::Set(x, nn) => {
+                self.registers.v[x as usize] = nn;
             }
             // Vx += NN
-            0x7000 => {
-                let val = self.registers.v[x] as u16 + (opcode & 0x00FF) as u16;
-                self.registers.v[x] = val as u8;
-            }
-            0x8000 => self.op_8xy(opcode, x, y),
-
-            // if (Vx != Vy)
-            0x9000 => {
-                if self.registers.v[x] != self.registers.v[y] {
-                    self.registers.pc += 2;
-                }
-            }
-            // I = NNN
-            0xA000 => {
-                self.registers.i = (opcode & 0x0FFF) as usize;
-            }
-            // PC = V0 + NNN
-            0xB000 => {
-                self.registers.pc = (self.registers.v[0] as u16 + (opcode & 0x0FFF)) as usize;
+            Op::AddImmediate(x, nn) => {
+                let x = x as usize;
+                self.registers.v[x] = self.registers.v[x].wrapping_add(nn);
             }
-            // Vx = rand() & NN
-            0xC000 => {
-                self.registers.v[x] = self.rng.gen::<u8>() & (opcode & 0x00FF) as u8;
-            }
-            // draw(Vx, Vy, N)
-            0xD000 => {
-                let height = (opcode & 0x000F) as usize;
-                let vx = self.registers.v[x] as usize;
-                let vy = self.registers.v[y] as usize;
-
-                self.registers.v[0xF] = 0;
-
-                for yline in 0..height {
-                    let pixel = self.mmu.read_byte(self.registers.i + yline);
-
-                    for xline in 0..8 {
-                        let vram_x = (vx + xline) % WIDTH;
-                        let vram_y = (vy + yline) % HEIGHT;
-
-                        if (pixel & (0x80 >> xline)) != 0 {
-                            if self.vram[vram_x + vram_y * WIDTH] == 1 {
-                                self.registers.v[0xF] |= 1;
-                            }
-
-                            self.vram[vram_x + vram_y * WIDTH] ^= 1;
-                        }
-                    }
-                }
-
-                self.vram_changed = true;
-            }
-            0xE000 => self.op_ex(opcode, x),
-            0xF000 => self.op_fx(opcode, x),
-            _ => self.unsupported_opcode(opcode),
-        };
-    }
-
-    fn op_8xy(&mut self, opcode: u16, x: usize, y: usize) {
-        match opcode & 0x000F {
             // Vx = Vy
-            0 => {
-                self.registers.v[x] = self.registers.v[y];
+            Op::Copy(x, y) => {
+                self.registers.v[x as usize] = self.registers.v[y as usize];
             }
             // Vx = Vx | Vy
-            1 => {
-                self.registers.v[x] |= self.registers.v[y];
+            Op::Or(x, y) => {
+                self.registers.v[x as usize] |= self.registers.v[y as usize];
+                self.reset_vf();
             }
             // Vx = Vx & Vy
-            2 => {
-                self.registers.v[x] &= self.registers.v[y];
+            Op::And(x, y) => {
+                self.registers.v[x as usize] &= self.registers.v[y as usize];
+                self.reset_vf();
             }
             // Vx = Vx ^ Vy
-            3 => {
-                self.registers.v[x] ^= self.registers.v[y];
+            Op::Xor(x, y) => {
+                self.registers.v[x as usize] ^= self.registers.v[y as usize];
+                self.reset_vf();
             }
             // Vx += Vy
-            4 => {
+            Op::Add(x, y) => {
+                let (x, y) = (x as usize, y as usize);
                 let val = self.registers.v[x] as u16 + self.registers.v[y] as u16;
 
                 self.registers.v[x] = val as u8;
                 self.registers.v[0xF] = if val > 0xFF { 1 } else { 0 };
             }
             // Vx -= Vy
-            5 => {
+            Op::Sub(x, y) => {
+                let (x, y) = (x as usize, y as usize);
                 self.registers.v[0xF] = if self.registers.v[x] > self.registers.v[y] {
                     1
                 } else {
@@ -363,12 +742,19 @@ impl CPU {
                 self.registers.v[x] = self.registers.v[x].wrapping_sub(self.registers.v[y]);
             }
             // Vx = Vx >> 1
-            6 => {
-                self.registers.v[0xF] = self.registers.v[x] & 1;
-                self.registers.v[x] >>= 1;
+            Op::ShiftRight(x, y) => {
+                let (x, y) = (x as usize, y as usize);
+                if self.quirks.shift_vy {
+                    self.registers.v[x] = self.registers.v[y];
+                }
+                let val = self.registers.v[x];
+
+                self.registers.v[x] = val >> 1;
+                self.registers.v[0xF] = val & 1;
             }
             // Vx = Vy - Vx
-            7 => {
+            Op::SubReversed(x, y) => {
+                let (x, y) = (x as usize, y as usize);
                 self.registers.v[0xF] = if self.registers.v[x] > self.registers.v[y] {
                     0
                 } else {
@@ -377,93 +763,185 @@ impl CPU {
                 self.registers.v[x] = self.registers.v[y].wrapping_sub(self.registers.v[x]);
             }
             // Vx = Vx << 1
-            0xE => {
-                self.registers.v[0xF] = (self.registers.v[x] >> 7) & 1;
-                self.registers.v[x] <<= 1;
-            }
-            _ => self.unsupported_opcode(opcode),
-        };
-    }
+            Op::ShiftLeft(x, y) => {
+                let (x, y) = (x as usize, y as usize);
+                if self.quirks.shift_vy {
+                    self.registers.v[x] = self.registers.v[y];
+                }
+                let val = self.registers.v[x];
 
-    fn op_ex(&mut self, opcode: u16, x: usize) {
-        match (opcode & 0x00FF) as u8 {
-            // if (key() == Vx)
-            0x009E => {
-                if self.keypad.state[self.registers.v[x] as usize] {
+                self.registers.v[x] = val << 1;
+                self.registers.v[0xF] = (val >> 7) & 1;
+            }
+            // if (Vx != Vy)
+            Op::SkipIfRegistersNotEqual(x, y) => {
+                if self.registers.v[x as usize] != self.registers.v[y as usize] {
                     self.registers.pc += 2;
                 }
             }
+            // I = NNN
+            Op::SetI(nnn) => {
+                self.registers.i = nnn as usize;
+            }
+            // PC = V0 + NNN
+            Op::JumpWithOffset(x, nnn) => {
+                let offset = if self.quirks.jump_vx {
+                    self.registers.v[x as usize]
+                } else {
+                    self.registers.v[0]
+                };
+                self.registers.pc = (offset as u16 + nnn) as usize;
+            }
+            // Vx = rand() & NN
+            Op::Random(x, nn) => {
+                self.registers.v[x as usize] = self.rng.random::<u8>() & nn;
+            }
+            // draw(Vx, Vy, N)
+            Op::Draw(x, y, height) => self.draw(x as usize, y as usize, height as usize),
             // if (key() == Vx)
-            0x00A1 => {
-                if !self.keypad.state[self.registers.v[x] as usize] {
+            Op::SkipIfKeyPressed(x) => {
+                if self.keypad.state[self.key(x)] {
                     self.registers.pc += 2;
                 }
             }
-            _ => self.unsupported_opcode(opcode),
-        };
-    }
-
-    fn op_fx(&mut self, opcode: u16, x: usize) {
-        return match (opcode & 0x00FF) as u8 {
+            // if (key() != Vx)
+            Op::SkipIfKeyNotPressed(x) => {
+                if !self.keypad.state[self.key(x)] {
+                    self.registers.pc += 2;
+                }
+            }
+            // audio_pattern = *I (XO-CHIP)
+            Op::LoadAudioPattern => {
+                let mut pattern = [0; AUDIO_PATTERN_SIZE];
+                for (i, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.mmu.read_byte(self.registers.i + i);
+                }
+                self.audio_pattern = Some(pattern);
+            }
             // Vx = get_delay()
-            0x0007 => {
-                self.registers.v[x] = self.registers.delay;
+            Op::GetDelay(x) => {
+                self.registers.v[x as usize] = self.registers.delay;
             }
             // Vx = get_key()
-            0x000A => {
+            Op::WaitForKey(x) => {
                 self.keypad.waiting = true;
-                self.keypad.register = x;
+                self.keypad.register = x as usize;
             }
             // delay_timer(Vx)
-            0x0015 => {
-                self.registers.delay = self.registers.v[x];
+            Op::SetDelay(x) => {
+                self.registers.delay = self.registers.v[x as usize];
             }
             // sound_timer(Vx)
-            0x0018 => {
-                self.registers.sound = self.registers.v[x];
+            Op::SetSound(x) => {
+                self.registers.sound = self.registers.v[x as usize];
             }
             // I += Vx
-            0x001E => {
-                self.registers.i += self.registers.v[x] as usize;
-                self.registers.v[0xF] = if self.registers.i > 0x0F00 { 1 } else { 0 };
+            Op::AddI(x) => {
+                let sum = self.registers.i + self.registers.v[x as usize] as usize;
+                self.registers.i = sum & I_MASK;
+                self.registers.v[0xF] = if sum > 0x0F00 { 1 } else { 0 };
             }
             // I = sprite_addr[Vx]
-            0x0029 => {
-                self.registers.i = mmu::FONT_BASE_ADDR + (self.registers.v[x] as usize) * 5;
+            Op::SetIToFont(x) => {
+                self.registers.i =
+                    mmu::FONT_BASE_ADDR + (self.registers.v[x as usize] as usize) * 5;
             }
             // set_BCD(Vx)
             // *(I+0) = BCD(3)
             // *(I+1) = BCD(2)
             // *(I+2) = BCD(1)
-            0x0033 => {
-                let val = self.registers.v[x];
+            Op::StoreBcd(x) => {
+                let val = self.registers.v[x as usize];
 
                 self.mmu.write_byte(self.registers.i, val / 100);
                 self.mmu.write_byte(self.registers.i + 1, (val % 100) / 10);
                 self.mmu.write_byte(self.registers.i + 2, val % 10);
             }
+            // pitch = Vx (XO-CHIP)
+            Op::SetPitch(x) => {
+                self.pitch = self.registers.v[x as usize];
+            }
             // reg_dump(Vx, &I)
-            0x0055 => {
+            Op::StoreRegisters(x) => {
+                let x = x as usize;
                 for i in 0..=x {
                     self.mmu
                         .write_byte(self.registers.i + i, self.registers.v[i]);
                 }
+                if self.quirks.load_store_increment_i {
+                    self.registers.i = (self.registers.i + x + 1) & I_MASK;
+                }
             }
             // reg_load(Vx, &I)
-            0x0065 => {
+            Op::LoadRegisters(x) => {
+                let x = x as usize;
                 for i in 0..=x {
                     self.registers.v[i] = self.mmu.read_byte(self.registers.i + i);
                 }
+                if self.quirks.load_store_increment_i {
+                    self.registers.i = (self.registers.i + x + 1) & I_MASK;
+                }
             }
-            _ => self.unsupported_opcode(opcode),
-        };
+            Op::Unsupported(opcode) => {
+                return Err(InterpreterError::UnsupportedOpcode {
+                    opcode,
+                    addr: self.current_addr(),
+                })
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw(&mut self, x: usize, y: usize, height: usize) {
+        let mut vx = self.registers.v[x] as usize;
+        let mut vy = self.registers.v[y] as usize;
+
+        if self.quirks.clip_sprites {
+            vx %= WIDTH;
+            vy %= HEIGHT;
+        }
+
+        self.registers.v[0xF] = 0;
+
+        for yline in 0..height {
+            let pixel = self.mmu.read_byte(self.registers.i + yline);
+
+            for xline in 0..8 {
+                if self.quirks.clip_sprites && (vx + xline >= WIDTH || vy + yline >= HEIGHT) {
+                    continue;
+                }
+
+                let vram_x = (vx + xline) % WIDTH;
+                let vram_y = (vy + yline) % HEIGHT;
+
+                if (pixel & (0x80 >> xline)) != 0 {
+                    if self.vram[vram_x + vram_y * WIDTH] == 1 {
+                        self.registers.v[0xF] |= 1;
+                    }
+
+                    self.vram[vram_x + vram_y * WIDTH] ^= 1;
+                }
+            }
+        }
+
+        self.vram_changed = true;
+    }
+
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.registers.v[0xF] = 0;
+        }
+    }
+
+    // Only the lowest nibble of Vx is used to pick a key.
+    fn key(&self, x: u8) -> usize {
+        (self.registers.v[x as usize] & 0xF) as usize
     }
 
-    fn unsupported_opcode(&self, opcode: u16) {
-        panic!(
-            "unsupported opcode 0x{:04X} @ ${:04X}\n{:?}",
-            opcode, self.registers.pc, self
-        );
+    // The address of the instruction being executed, since PC already points to the next one.
+    fn current_addr(&self) -> u16 {
+        (self.registers.pc - 2) as u16
     }
 }
 
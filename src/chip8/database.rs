@@ -0,0 +1,60 @@
+// A database of known ROMs, imported from the community chip-8-database
+// (https://github.com/chip-8/chip-8-database). ROMs are identified by the SHA-1 of their bytes
+// so that the frontends can configure the interpreter automatically (quirks, speed, etc.).
+//
+// The entries of `ROMS` are generated from the `programs.json` file of the chip-8-database with
+// `cargo run --example import_database`.
+
+use std::fmt;
+
+use super::{rom_hash_hex, Quirks};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Chip8 => write!(f, "CHIP-8"),
+            Platform::SuperChip => write!(f, "SUPER-CHIP"),
+            Platform::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+pub struct RomInfo {
+    pub sha1: &'static str,
+    pub title: &'static str,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    // The recommended number of instructions per frame.
+    pub tickrate: u8,
+    // The recommended foreground and background colors (0xRRGGBB), if any.
+    pub colors: Option<(u32, u32)>,
+    // What the CHIP-8 keys do in the game.
+    pub keys: &'static [(u8, &'static str)],
+}
+
+// Sorted by SHA-1.
+static ROMS: &[RomInfo] = &[
+    // This is the ROM bundled with the web app.
+    RomInfo {
+        sha1: "5c28a5f85289c9d859f95fd5eadbdcb1c30bb08b",
+        title: "Space Invaders (David Winter)",
+        platform: Platform::Chip8,
+        quirks: Quirks::DEFAULT,
+        tickrate: 9,
+        colors: None,
+        keys: &[(0x4, "left"), (0x5, "fire / start"), (0x6, "right")],
+    },
+];
+
+pub fn lookup(rom: &[u8]) -> Option<&'static RomInfo> {
+    let sha1 = rom_hash_hex(rom);
+
+    ROMS.iter().find(|info| info.sha1 == sha1)
+}
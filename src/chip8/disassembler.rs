@@ -0,0 +1,85 @@
+// See: http://devernay.free.fr/hacks/chip8/C8TECH10.HTM
+//
+// This uses the same mnemonics as the disassembler of the web app.
+
+use super::Interpreter;
+
+// Disassembles the instruction at `addr` in the RAM of `interpreter`, e.g. "6004 LD V0, 04". When
+// the coverage says that the byte is data (see `Coverage::is_data()`), it is shown with its pixels
+// instead, e.g. "F0   DB ####....". Returns the text and its size in bytes.
+pub fn disassemble_at(interpreter: &Interpreter, addr: u16) -> (String, u16) {
+    if interpreter
+        .get_coverage()
+        .is_some_and(|coverage| coverage.is_data(addr))
+    {
+        let byte = interpreter.read_byte(addr);
+        let pixels: String = (0..8)
+            .map(|x| if byte & (0x80 >> x) != 0 { '#' } else { '.' })
+            .collect();
+
+        return (format!("{:02X}   DB {}", byte, pixels), 1);
+    }
+
+    let opcode = (interpreter.read_byte(addr) as u16) << 8 | interpreter.read_byte(addr + 1) as u16;
+
+    (format!("{:04X} {}", opcode, disassemble(opcode)), 2)
+}
+
+pub fn disassemble(opcode: u16) -> String {
+    let nnn = opcode & 0x0FFF;
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let kk = opcode & 0x00FF;
+    let n = opcode & 0x000F;
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => "CLS".to_string(),
+            0x00EE => "RET".to_string(),
+            _ => "-".to_string(),
+        },
+        0x1000 => format!("JP {:04X}", nnn),
+        0x2000 => format!("CALL {:04X}", nnn),
+        0x3000 => format!("SE V{:X}, {:02X}", x, kk),
+        0x4000 => format!("SNE V{:X}, {:02X}", x, kk),
+        0x5000 => format!("SE V{:X}, V{:X}", x, y),
+        0x6000 => format!("LD V{:X}, {:02X}", x, kk),
+        0x7000 => format!("ADD V{:X}, {:02X}", x, kk),
+        0x8000 => match n {
+            0x0 => format!("LD V{:X}, V{:X}", x, y),
+            0x1 => format!("OR V{:X}, V{:X}", x, y),
+            0x2 => format!("AND V{:X}, V{:X}", x, y),
+            0x3 => format!("XOR V{:X}, V{:X}", x, y),
+            0x4 => format!("ADD V{:X}, V{:X}", x, y),
+            0x5 => format!("SUB V{:X}, V{:X}", x, y),
+            0x6 => format!("SHR V{:X} {{, V{:X}}}", x, y),
+            0x7 => format!("SUBN V{:X}, V{:X}", x, y),
+            0xE => format!("SHL V{:X} {{, V{:X}}}", x, y),
+            _ => "-".to_string(),
+        },
+        0x9000 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA000 => format!("LD I, {:04X}", nnn),
+        0xB000 => format!("JP V0, {:04X}", nnn),
+        0xC000 => format!("RND V{:X}, {:02X}", x, kk),
+        0xD000 => format!("DRW V{:X}, V{:X}, {:X}", x, y, n),
+        0xE000 => match kk {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => "-".to_string(),
+        },
+        _ => match kk {
+            0x02 if x == 0 => "LD AUDIO, [I]".to_string(),
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x3A => format!("LD PITCH, V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            _ => "-".to_string(),
+        },
+    }
+}
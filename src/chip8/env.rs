@@ -0,0 +1,267 @@
+// A Gym-style environment to use CHIP-8 games as small reinforcement learning benchmarks. An
+// agent picks an action (a combination of CHIP-8 keys) at each step, which is held for a few
+// frames ("frameskip"), and receives the display as an observation along with a reward and
+// whether the episode is over.
+//
+// Rewards and termination are defined per ROM by reading RAM (see `Game`), e.g. the score that
+// a game stores in BCD before drawing it with FX33, or the registers for the games that keep
+// their whole state in them. Some ROMs have a definition already (see `lookup()`). There is no
+// frontend involved so many environments can run side by side.
+
+use super::{database, rom_hash_hex, Interpreter, InterpreterError, Quirks, HEIGHT, WIDTH};
+
+pub type Observation = [u8; WIDTH * HEIGHT];
+
+// The number of instructions per frame for ROMs that are not in the database.
+const DEFAULT_SPEED: u8 = 9;
+const DEFAULT_FRAMESKIP: usize = 4;
+
+// A value stored in RAM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RamValue {
+    Byte(u16),
+    // A V register, for the games that do not keep their state in RAM.
+    Register(u8),
+    // A big-endian integer of `len` bytes (up to 4).
+    BigEndian { addr: u16, len: u8 },
+    // Decimal digits, one per byte and most significant first, as written by FX33.
+    Digits { addr: u16, len: u8 },
+    // Packed BCD, two digits per byte and most significant first.
+    PackedBcd { addr: u16, len: u8 },
+}
+
+impl RamValue {
+    pub fn read(&self, interpreter: &Interpreter) -> u32 {
+        match *self {
+            RamValue::Byte(addr) => interpreter.read_byte(addr) as u32,
+            RamValue::Register(x) => interpreter.cpu.registers.v[(x & 0xF) as usize] as u32,
+            RamValue::BigEndian { addr, len } => (0..len.min(4) as u16).fold(0, |value, i| {
+                (value << 8) | interpreter.read_byte(addr.wrapping_add(i)) as u32
+            }),
+            RamValue::Digits { addr, len } => (0..len as u16).fold(0, |value, i| {
+                let digit = (interpreter.read_byte(addr.wrapping_add(i)) % 10) as u32;
+                value.wrapping_mul(10).wrapping_add(digit)
+            }),
+            RamValue::PackedBcd { addr, len } => (0..len as u16).fold(0, |value, i| {
+                let byte = interpreter.read_byte(addr.wrapping_add(i));
+                let digits = ((byte >> 4) % 10) as u32 * 10 + ((byte & 0xF) % 10) as u32;
+                value.wrapping_mul(100).wrapping_add(digits)
+            }),
+        }
+    }
+}
+
+// How to play a ROM: the available actions, and where the score and the game state are in RAM.
+#[derive(Clone, Debug)]
+pub struct Game {
+    // The keys held for each action (bit N is set when key N is pressed).
+    pub actions: Vec<u16>,
+    // The reward of a step is the increase of the score.
+    pub score: Option<RamValue>,
+    // A bit mask of the remaining targets (e.g. a row of enemies): each target that disappears is
+    // worth a point, and the mask can be filled again (e.g. for a new wave) at no cost.
+    pub targets: Option<RamValue>,
+    // The episode is over when the number of lives drops to 0.
+    pub lives: Option<RamValue>,
+    // The episode is over when this value is in RAM.
+    pub game_over: Option<(RamValue, u32)>,
+    // The episode is truncated after this number of frames.
+    pub max_frames: Option<usize>,
+}
+
+impl Default for Game {
+    // 17 actions: no key, then each of the 16 keys.
+    fn default() -> Self {
+        Game {
+            actions: (0..=16)
+                .map(|i| if i == 0 { 0 } else { 1 << (i - 1) })
+                .collect(),
+            score: None,
+            targets: None,
+            lives: None,
+            game_over: None,
+            max_frames: None,
+        }
+    }
+}
+
+pub struct Env {
+    rom: Vec<u8>,
+    game: Game,
+    quirks: Quirks,
+    speed: u8,
+    frameskip: usize,
+    interpreter: Interpreter,
+    frame: usize,
+    score: u32,
+    targets: u32,
+    lives: u32,
+    done: bool,
+    error: Option<InterpreterError>,
+}
+
+impl Env {
+    // Creates an environment with the recommended quirks and speed of the ROM when it is in the
+    // database. The first episode uses a seed of 0.
+    pub fn new(rom: Vec<u8>, game: Game) -> Result<Self, InterpreterError> {
+        let (quirks, speed) = match database::lookup(&rom) {
+            Some(info) => (info.quirks, info.tickrate),
+            None => (Quirks::default(), DEFAULT_SPEED),
+        };
+
+        let mut env = Env {
+            interpreter: Interpreter::with_seed(rom.clone(), 0)?,
+            rom,
+            game,
+            quirks,
+            speed,
+            frameskip: DEFAULT_FRAMESKIP,
+            frame: 0,
+            score: 0,
+            targets: 0,
+            lives: 0,
+            done: false,
+            error: None,
+        };
+        env.reset(0);
+
+        Ok(env)
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    // Sets the number of instructions per frame.
+    pub fn set_speed(&mut self, speed: u8) {
+        self.speed = speed;
+    }
+
+    // Sets the number of frames during which an action is held.
+    pub fn set_frameskip(&mut self, frameskip: usize) {
+        self.frameskip = frameskip.max(1);
+    }
+
+    pub fn action_count(&self) -> usize {
+        self.game.actions.len()
+    }
+
+    pub fn get_frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn get_score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    // Returns the error that ended the episode, if the ROM crashed.
+    pub fn get_error(&self) -> Option<InterpreterError> {
+        self.error
+    }
+
+    pub fn get_interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    // Starts a new episode. The seed is the one of the interpreter's random number generator, so
+    // an episode can be replayed exactly with the same seed and actions.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.interpreter = Interpreter::with_seed(self.rom.clone(), seed)
+            .expect("the ROM has been checked by Env::new()");
+        self.interpreter.set_quirks(self.quirks);
+        self.frame = 0;
+        self.score = self.read(self.game.score);
+        self.targets = self.read(self.game.targets);
+        self.lives = self.read(self.game.lives);
+        self.done = false;
+        self.error = None;
+
+        self.interpreter.get_vram()
+    }
+
+    // Holds the keys of `action` for `frameskip` frames (or until the episode is over) and
+    // returns the observation, the sum of the rewards and whether the episode is over. The episode
+    // is also over when the ROM crashes (see `get_error()`). Calling it once the episode is over
+    // does nothing.
+    //
+    // Panics when `action` is not lower than `action_count()`.
+    pub fn step(&mut self, action: usize) -> (Observation, f32, bool) {
+        let keys = self.game.actions[action];
+        let mut keypad = [false; 16];
+        for (i, pressed) in keypad.iter_mut().enumerate() {
+            *pressed = keys & (1 << i) != 0;
+        }
+
+        let mut reward = 0.0;
+        for _ in 0..self.frameskip {
+            if self.done {
+                break;
+            }
+
+            self.interpreter.update_keypad(keypad);
+            if let Err(e) = self.interpreter.run_frame(self.speed) {
+                self.error = Some(e);
+                self.done = true;
+                break;
+            }
+            self.frame += 1;
+
+            let score = self.read(self.game.score);
+            reward += score as f32 - self.score as f32;
+            self.score = score;
+
+            let targets = self.read(self.game.targets);
+            reward += (self.targets & !targets).count_ones() as f32;
+            self.targets = targets;
+
+            let lives = self.read(self.game.lives);
+            let lost = self.lives > 0 && lives == 0;
+            self.lives = lives;
+
+            let game_over = match self.game.game_over {
+                Some((value, expected)) => value.read(&self.interpreter) == expected,
+                None => false,
+            };
+            let truncated = match self.game.max_frames {
+                Some(max_frames) => self.frame >= max_frames,
+                None => false,
+            };
+
+            self.done = lost || game_over || truncated;
+        }
+
+        (self.interpreter.get_vram(), reward, self.done)
+    }
+
+    fn read(&self, value: Option<RamValue>) -> u32 {
+        value.map(|v| v.read(&self.interpreter)).unwrap_or(0)
+    }
+}
+
+// Returns the definition of a known ROM.
+pub fn lookup(rom: &[u8]) -> Option<Game> {
+    match rom_hash_hex(rom).as_str() {
+        "5c28a5f85289c9d859f95fd5eadbdcb1c30bb08b" => Some(space_invaders()),
+        _ => None,
+    }
+}
+
+// Space Invaders (David Winter): the aliens of the current row are the bits of VE and the game is
+// over when the row reaches the bottom (VC = 0x18). The fire key (5) is always held: it starts the
+// game from the title screen, and a new shot is fired as soon as the previous one is gone.
+fn space_invaders() -> Game {
+    const LEFT: u16 = 1 << 0x4;
+    const FIRE: u16 = 1 << 0x5;
+    const RIGHT: u16 = 1 << 0x6;
+
+    Game {
+        actions: vec![FIRE, FIRE | LEFT, FIRE | RIGHT],
+        targets: Some(RamValue::Register(0xE)),
+        game_over: Some((RamValue::Register(0xC), 0x18)),
+        ..Game::default()
+    }
+}
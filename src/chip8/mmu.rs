@@ -3,14 +3,47 @@
 // 0x000-0x1FF - Chip 8 interpreter, which contains the fontset at: 0x050-0x0A0
 // 0x200-0xFFF - Program ROM and work RAM
 
+use super::cheats::Cheats;
+
 pub const FONT_BASE_ADDR: usize = 0x050;
 pub const ROM_BASE_ADDR: usize = 0x200;
 
-const RAM_SIZE: usize = 0x1000;
+pub const RAM_SIZE: usize = 0x1000;
+pub const MAX_ROM_SIZE: usize = RAM_SIZE - ROM_BASE_ADDR;
+
+// The hexadecimal digits, 4x5 pixels each.
+pub const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+// RAM is split into small pages whose version changes with each write, so that the decoded
+// instructions of the block cache can be invalidated when the code changes.
+pub const PAGE_SIZE: usize = 16;
+const PAGE_COUNT: usize = RAM_SIZE / PAGE_SIZE;
 
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone)]
 pub struct MMU {
     rom: Vec<u8>,
     ram: [u8; RAM_SIZE],
+    page_versions: [u64; PAGE_COUNT],
+    // The bytes that are frozen by cheats. They are kept when the RAM is reset or restored.
+    cheats: Cheats,
 }
 
 impl MMU {
@@ -18,6 +51,8 @@ impl MMU {
         let mut mmu = MMU {
             rom,
             ram: [0; RAM_SIZE],
+            page_versions: [0; PAGE_COUNT],
+            cheats: Cheats::new(),
         };
         mmu.reset();
         mmu
@@ -30,49 +65,78 @@ impl MMU {
 
     pub fn reset(&mut self) {
         self.ram = [0; RAM_SIZE];
+        self.invalidate_pages();
         // Load fontset.
-        for (i, b) in [
-            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-            0x20, 0x60, 0x20, 0x20, 0x70, // 1
-            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-        ]
-        .iter()
-        .enumerate()
-        {
-            self.ram[FONT_BASE_ADDR + i] = *b;
-        }
+        self.ram[FONT_BASE_ADDR..FONT_BASE_ADDR + FONT.len()].copy_from_slice(&FONT);
         // Load the ROM in the "work RAM".
         for (i, b) in self.rom.iter().enumerate() {
             self.ram[ROM_BASE_ADDR + i] = *b;
         }
     }
 
-    pub fn read_byte(&mut self, addr: usize) -> u8 {
-        self.ram[addr]
+    // Addresses wrap around at 4 KB, e.g. when I is close to 0xFFF.
+    pub fn read_byte(&self, addr: usize) -> u8 {
+        self.ram[addr % RAM_SIZE]
     }
 
     pub fn write_byte(&mut self, addr: usize, value: u8) {
-        self.ram[addr] = value
+        let addr = addr % RAM_SIZE;
+        self.ram[addr] = value;
+        self.page_versions[addr / PAGE_SIZE] += 1;
+    }
+
+    pub fn get_page_version(&self, page: usize) -> u64 {
+        self.page_versions[page]
     }
 
-    pub fn read_word(&mut self, addr: usize) -> u16 {
+    // Must be called when the RAM may have been written directly, e.g. through
+    // `get_ram_mut_ptr()`, so that the block cache decodes the instructions again.
+    pub fn invalidate_pages(&mut self) {
+        for version in self.page_versions.iter_mut() {
+            *version += 1;
+        }
+    }
+
+    pub fn read_word(&self, addr: usize) -> u16 {
         ((self.read_byte(addr) as u16) << 8) | (self.read_byte(addr + 1) as u16)
     }
 
+    pub fn get_cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    pub fn set_cheats(&mut self, cheats: Cheats) {
+        self.cheats = cheats;
+        self.apply_cheats();
+    }
+
+    // Writes the frozen values back. Bytes that have not changed are not written again, so that
+    // the block cache does not have to decode their page again.
+    pub fn apply_cheats(&mut self) {
+        for (addr, value) in self.cheats.iter() {
+            let addr = addr as usize;
+            if self.ram[addr] != value {
+                self.ram[addr] = value;
+                self.page_versions[addr / PAGE_SIZE] += 1;
+            }
+        }
+    }
+
     pub fn get_ram_ptr(&self) -> *const u8 {
         self.ram.as_ptr()
     }
+
+    pub fn get_ram_mut_ptr(&mut self) -> *mut u8 {
+        self.invalidate_pages();
+        self.ram.as_mut_ptr()
+    }
+
+    pub fn get_ram(&self) -> [u8; RAM_SIZE] {
+        self.ram
+    }
+
+    pub fn set_ram(&mut self, ram: [u8; RAM_SIZE]) {
+        self.ram = ram;
+        self.invalidate_pages();
+    }
 }
@@ -1,32 +1,132 @@
+pub mod analysis;
+pub mod assembler;
+pub mod audio;
+pub mod batch;
+mod blocks;
+pub mod capture;
+pub mod cheats;
+pub mod coverage;
 mod cpu;
+pub mod database;
+pub mod disassembler;
+pub mod env;
 mod mmu;
+pub mod movie;
+mod op;
+mod palette;
+pub mod patch;
+pub mod phosphor;
+pub mod profiler;
+mod quirks;
+pub mod sprites;
+pub mod symbols;
+
+pub use cpu::{InterpreterError, Snapshot, SnapshotError, AUDIO_PATTERN_SIZE, DEFAULT_PITCH};
+pub use mmu::MAX_ROM_SIZE;
+pub use palette::{Palette, PRESETS as PALETTES};
+pub use phosphor::Phosphor;
+pub use quirks::Quirks;
 
 pub const WIDTH: usize = 64;
 pub const HEIGHT: usize = 32;
 
+// ROMs are identified by the SHA-1 digest of their bytes.
+pub fn rom_hash(rom: &[u8]) -> [u8; 20] {
+    sha1_smol::Sha1::from(rom).digest().bytes()
+}
+
+// Same as `rom_hash()` but as a lowercase hexadecimal string, like `sha1sum` prints it.
+pub fn rom_hash_hex(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+// Interpreters hold no shared state: they can be cloned (e.g. to branch from a given state) and
+// moved to or shared between threads.
+#[derive(Clone)]
 pub struct Interpreter {
     // This has to be open for the debugger until I learn about a better way to do it.
     pub cpu: cpu::CPU,
     keypad: [bool; 16],
 }
 
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Interpreter>();
+};
+
 impl Interpreter {
-    pub fn new(rom: Vec<u8>) -> Self {
+    pub fn new(rom: Vec<u8>) -> Result<Self, InterpreterError> {
+        Interpreter::with_seed(rom, rand::random())
+    }
+
+    // Creates an interpreter whose random number generator is seeded with `seed`, which makes
+    // its execution deterministic for a given sequence of inputs.
+    pub fn with_seed(rom: Vec<u8>, seed: u64) -> Result<Self, InterpreterError> {
+        check_rom(&rom)?;
+
         let mmu = mmu::MMU::new(rom);
-        let cpu = cpu::CPU::new(mmu);
+        let cpu = cpu::CPU::new(mmu, seed);
 
-        Interpreter {
+        Ok(Interpreter {
             cpu,
             keypad: [false; 16],
-        }
+        })
     }
 
     pub fn update_keypad(&mut self, keypad: [bool; 16]) {
         self.keypad = keypad;
     }
 
-    pub fn step(&mut self) {
-        self.cpu.step(self.keypad);
+    pub fn step(&mut self) -> Result<(), InterpreterError> {
+        self.cpu.step(self.keypad)
+    }
+
+    // Runs a single 60 Hz frame, i.e. `speed` instructions followed by a timers update, and
+    // returns whether the display should be redrawn.
+    pub fn run_frame(&mut self, speed: u8) -> Result<bool, InterpreterError> {
+        let redraw = self.cpu.run(self.keypad, speed as usize)?;
+
+        self.update_timers();
+
+        Ok(redraw)
+    }
+
+    // Returns the number of instructions executed successfully since the interpreter has been
+    // created, including the ones of a frame that failed.
+    pub fn get_instruction_count(&self) -> u64 {
+        self.cpu.get_instruction_count()
+    }
+
+    pub fn is_block_cache_enabled(&self) -> bool {
+        self.cpu.is_block_cache_enabled()
+    }
+
+    // The block cache makes `run_frame()` faster by keeping the instructions it has decoded, with
+    // the exact same results. It is disabled by default because it uses more memory, which is
+    // wasted on interpreters that are mostly stepped one instruction at a time (debuggers).
+    pub fn set_block_cache(&mut self, enabled: bool) {
+        self.cpu.set_block_cache(enabled);
+    }
+
+    // Returns the profile of the instructions executed since profiling has been enabled.
+    pub fn get_profile(&self) -> Option<&profiler::Profile> {
+        self.cpu.get_profile()
+    }
+
+    // Profiling counts the instructions executed at each address and in each subroutine (see
+    // `profiler::Profile`). It makes the interpreter slower and disables the block cache.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.cpu.set_profiling(enabled);
+    }
+
+    pub fn get_coverage(&self) -> Option<&coverage::Coverage> {
+        self.cpu.get_coverage()
+    }
+
+    // Coverage tags each byte of RAM as executed, read, written or drawn (see
+    // `coverage::Coverage`). Like profiling, it disables the block cache.
+    pub fn set_coverage(&mut self, enabled: bool) {
+        self.cpu.set_coverage(enabled);
     }
 
     pub fn should_redraw(&self) -> bool {
@@ -37,8 +137,10 @@ impl Interpreter {
         self.cpu.should_beep()
     }
 
+    // Must be called once per frame, which also writes back the values frozen by the cheats.
     pub fn update_timers(&mut self) {
         self.cpu.update_timers();
+        self.cpu.mmu.apply_cheats();
     }
 
     pub fn get_vram(&self) -> [u8; HEIGHT * WIDTH] {
@@ -53,12 +155,31 @@ impl Interpreter {
         self.cpu.mmu.get_ram_ptr()
     }
 
+    // Returns a pointer to write the RAM directly. The block cache is invalidated, but the RAM
+    // can still be written through this pointer later on: `invalidate_ram()` has to be called
+    // again after each write.
+    pub fn get_ram_mut_ptr(&mut self) -> *mut u8 {
+        self.cpu.mmu.get_ram_mut_ptr()
+    }
+
+    pub fn invalidate_ram(&mut self) {
+        self.cpu.mmu.invalidate_pages();
+    }
+
     pub fn reset(&mut self) {
         self.cpu.reset()
     }
 
-    pub fn load_rom(&mut self, rom: Vec<u8>) {
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), InterpreterError> {
+        check_rom(&rom)?;
         self.cpu.load_rom(rom);
+
+        Ok(())
+    }
+
+    // Reads a byte of RAM, the address wraps around at 4 KB.
+    pub fn read_byte(&self, addr: u16) -> u8 {
+        self.cpu.read_byte(addr % mmu::RAM_SIZE as u16)
     }
 
     pub fn get_v_ptr(&self) -> *const u8 {
@@ -84,4 +205,52 @@ impl Interpreter {
     pub fn get_sound(&self) -> u8 {
         self.cpu.registers.sound
     }
+
+    pub fn save_state(&self) -> Snapshot {
+        self.cpu.snapshot()
+    }
+
+    pub fn load_state(&mut self, snapshot: &Snapshot) {
+        self.cpu.restore(snapshot);
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.cpu.get_seed()
+    }
+
+    // Returns the XO-CHIP audio pattern, if the ROM has loaded one.
+    pub fn get_audio_pattern(&self) -> Option<[u8; AUDIO_PATTERN_SIZE]> {
+        self.cpu.get_audio_pattern()
+    }
+
+    pub fn get_pitch(&self) -> u8 {
+        self.cpu.get_pitch()
+    }
+
+    pub fn get_quirks(&self) -> Quirks {
+        self.cpu.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.cpu.quirks = quirks;
+    }
+
+    pub fn get_cheats(&self) -> &cheats::Cheats {
+        self.cpu.mmu.get_cheats()
+    }
+
+    // The cheats are applied right away, and then at each frame (see `cheats::Cheats`). They are
+    // kept on reset and when a state is loaded.
+    pub fn set_cheats(&mut self, cheats: cheats::Cheats) {
+        self.cpu.mmu.set_cheats(cheats);
+    }
+}
+
+// The ROM is loaded at 0x200 and has to fit in the 4 KB of RAM.
+fn check_rom(rom: &[u8]) -> Result<(), InterpreterError> {
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(InterpreterError::RomTooLarge(rom.len()));
+    }
+
+    Ok(())
 }
@@ -0,0 +1,198 @@
+// A movie is a recording of the keypad state for each frame of a session, which can be played
+// back bit-exactly because the interpreter is deterministic given its ROM, quirks, RNG seed and
+// speed (the number of instructions per frame).
+//
+// File format (all integers are little-endian):
+//
+//   0x00  4 bytes   magic: "C8M" followed by the format version
+//   0x04  20 bytes  SHA-1 of the ROM
+//   0x18  1 byte    quirks (see `Quirks::to_bits()`)
+//   0x19  8 bytes   RNG seed
+//   0x21  1 byte    speed
+//   0x22  4 bytes   number of frames
+//   0x26  ...       runs of frames: 2 bytes for the keypad state (bit N is set when key N is
+//                   pressed) followed by 2 bytes for the number of consecutive frames with this
+//                   state
+
+use std::convert::TryInto;
+use std::fmt;
+
+use super::{rom_hash, Interpreter, InterpreterError, Quirks};
+
+const MAGIC: &[u8; 3] = b"C8M";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 0x26;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MovieError {
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    RomMismatch,
+    InvalidRom(InterpreterError),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::InvalidMagic => write!(f, "not a movie file"),
+            MovieError::UnsupportedVersion(v) => write!(f, "unsupported movie version {}", v),
+            MovieError::Truncated => write!(f, "movie file is truncated"),
+            MovieError::RomMismatch => write!(f, "movie was recorded with a different ROM"),
+            MovieError::InvalidRom(e) => write!(f, "invalid ROM: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: [u8; 20],
+    pub quirks: Quirks,
+    pub seed: u64,
+    pub speed: u8,
+    frames: Vec<u16>,
+}
+
+impl Movie {
+    pub fn new(rom: &[u8], quirks: Quirks, seed: u64, speed: u8) -> Self {
+        Movie {
+            rom_hash: rom_hash(rom),
+            quirks,
+            seed,
+            speed,
+            frames: Vec::new(),
+        }
+    }
+
+    // Creates a movie for a new recording of what `interpreter` is about to run.
+    pub fn for_interpreter(rom: &[u8], interpreter: &Interpreter, speed: u8) -> Self {
+        Movie::new(rom, interpreter.get_quirks(), interpreter.get_seed(), speed)
+    }
+
+    pub fn record_frame(&mut self, keypad: [bool; 16]) {
+        self.frames.push(keypad_to_bits(keypad));
+    }
+
+    // Drops all the frames after the first `len` ones, e.g. to branch from a save state taken at
+    // frame `len` while recording.
+    pub fn truncate(&mut self, len: usize) {
+        self.frames.truncate(len);
+    }
+
+    pub fn frame(&self, n: usize) -> Option<[bool; 16]> {
+        self.frames.get(n).map(|bits| keypad_from_bits(*bits))
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn matches_rom(&self, rom: &[u8]) -> bool {
+        self.rom_hash == rom_hash(rom)
+    }
+
+    // Creates an interpreter configured exactly like the one used to record this movie.
+    pub fn interpreter(&self, rom: Vec<u8>) -> Result<Interpreter, MovieError> {
+        if !self.matches_rom(&rom) {
+            return Err(MovieError::RomMismatch);
+        }
+
+        let mut interpreter =
+            Interpreter::with_seed(rom, self.seed).map_err(MovieError::InvalidRom)?;
+        interpreter.set_quirks(self.quirks);
+
+        Ok(interpreter)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.rom_hash);
+        bytes.push(self.quirks.to_bits());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.push(self.speed);
+        bytes.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+
+        let mut frames = self.frames.iter().peekable();
+        while let Some(bits) = frames.next() {
+            let mut count: u16 = 1;
+            while count < u16::MAX && frames.peek() == Some(&bits) {
+                frames.next();
+                count += 1;
+            }
+
+            bytes.extend_from_slice(&bits.to_le_bytes());
+            bytes.extend_from_slice(&count.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Movie, MovieError> {
+        if bytes.len() < 4 || &bytes[0..3] != MAGIC {
+            return Err(MovieError::InvalidMagic);
+        }
+        if bytes[3] != VERSION {
+            return Err(MovieError::UnsupportedVersion(bytes[3]));
+        }
+        if bytes.len() < HEADER_SIZE {
+            return Err(MovieError::Truncated);
+        }
+
+        let rom_hash = bytes[0x04..0x18].try_into().unwrap();
+        let quirks = Quirks::from_bits(bytes[0x18]);
+        let seed = u64::from_le_bytes(bytes[0x19..0x21].try_into().unwrap());
+        let speed = bytes[0x21];
+        let len = u32::from_le_bytes(bytes[0x22..0x26].try_into().unwrap()) as usize;
+
+        // The runs are checked against the number of frames before they are expanded, so that a
+        // malformed file cannot allocate more frames than it declares.
+        let mut frames = Vec::new();
+        for run in bytes[HEADER_SIZE..].chunks(4) {
+            if run.len() != 4 {
+                return Err(MovieError::Truncated);
+            }
+
+            let bits = u16::from_le_bytes([run[0], run[1]]);
+            let count = u16::from_le_bytes([run[2], run[3]]) as usize;
+            if frames.len() + count > len {
+                return Err(MovieError::Truncated);
+            }
+            frames.extend(std::iter::repeat_n(bits, count));
+        }
+
+        if frames.len() != len {
+            return Err(MovieError::Truncated);
+        }
+
+        Ok(Movie {
+            rom_hash,
+            quirks,
+            seed,
+            speed,
+            frames,
+        })
+    }
+}
+
+fn keypad_to_bits(keypad: [bool; 16]) -> u16 {
+    keypad
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (i, pressed)| bits | ((*pressed as u16) << i))
+}
+
+fn keypad_from_bits(bits: u16) -> [bool; 16] {
+    let mut keypad = [false; 16];
+    for (i, pressed) in keypad.iter_mut().enumerate() {
+        *pressed = bits & (1 << i) != 0;
+    }
+    keypad
+}
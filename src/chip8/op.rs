@@ -0,0 +1,165 @@
+// Decoded instructions. The CPU decodes each opcode into an `Op` before executing it, and the
+// block cache (see `blocks.rs`) keeps the decoded instructions of the code it has already seen so
+// that they do not have to be fetched and decoded again.
+//
+// X and Y are register indexes, NN and NNN immediate values.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    // 00E0 (only the last nibble is decoded)
+    ClearScreen,
+    // 00EE (only the last nibble is decoded)
+    Return,
+    // 1NNN
+    Jump(u16),
+    // 2NNN
+    Call(u16),
+    // 3XNN
+    SkipIfEqual(u8, u8),
+    // 4XNN
+    SkipIfNotEqual(u8, u8),
+    // 5XY0 (the last nibble is ignored)
+    SkipIfRegistersEqual(u8, u8),
+    // 6XNN
+    Set(u8, u8),
+    // 7XNN
+    AddImmediate(u8, u8),
+    // 8XY0
+    Copy(u8, u8),
+    // 8XY1
+    Or(u8, u8),
+    // 8XY2
+    And(u8, u8),
+    // 8XY3
+    Xor(u8, u8),
+    // 8XY4
+    Add(u8, u8),
+    // 8XY5
+    Sub(u8, u8),
+    // 8XY6
+    ShiftRight(u8, u8),
+    // 8XY7
+    SubReversed(u8, u8),
+    // 8XYE
+    ShiftLeft(u8, u8),
+    // 9XY0 (the last nibble is ignored)
+    SkipIfRegistersNotEqual(u8, u8),
+    // ANNN
+    SetI(u16),
+    // BNNN (X is only used with the `jump_vx` quirk)
+    JumpWithOffset(u8, u16),
+    // CXNN
+    Random(u8, u8),
+    // DXYN
+    Draw(u8, u8, u8),
+    // EX9E
+    SkipIfKeyPressed(u8),
+    // EXA1
+    SkipIfKeyNotPressed(u8),
+    // F002 (XO-CHIP)
+    LoadAudioPattern,
+    // FX07
+    GetDelay(u8),
+    // FX0A
+    WaitForKey(u8),
+    // FX15
+    SetDelay(u8),
+    // FX18
+    SetSound(u8),
+    // FX1E
+    AddI(u8),
+    // FX29
+    SetIToFont(u8),
+    // FX33
+    StoreBcd(u8),
+    // FX3A (XO-CHIP)
+    SetPitch(u8),
+    // FX55
+    StoreRegisters(u8),
+    // FX65
+    LoadRegisters(u8),
+    Unsupported(u16),
+}
+
+impl Op {
+    pub fn decode(opcode: u16) -> Op {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+
+        match opcode & 0xF000 {
+            0x0000 => match n {
+                0x0 => Op::ClearScreen,
+                0xE => Op::Return,
+                _ => Op::Unsupported(opcode),
+            },
+            0x1000 => Op::Jump(nnn),
+            0x2000 => Op::Call(nnn),
+            0x3000 => Op::SkipIfEqual(x, nn),
+            0x4000 => Op::SkipIfNotEqual(x, nn),
+            0x5000 => Op::SkipIfRegistersEqual(x, y),
+            0x6000 => Op::Set(x, nn),
+            0x7000 => Op::AddImmediate(x, nn),
+            0x8000 => match n {
+                0x0 => Op::Copy(x, y),
+                0x1 => Op::Or(x, y),
+                0x2 => Op::And(x, y),
+                0x3 => Op::Xor(x, y),
+                0x4 => Op::Add(x, y),
+                0x5 => Op::Sub(x, y),
+                0x6 => Op::ShiftRight(x, y),
+                0x7 => Op::SubReversed(x, y),
+                0xE => Op::ShiftLeft(x, y),
+                _ => Op::Unsupported(opcode),
+            },
+            0x9000 => Op::SkipIfRegistersNotEqual(x, y),
+            0xA000 => Op::SetI(nnn),
+            0xB000 => Op::JumpWithOffset(x, nnn),
+            0xC000 => Op::Random(x, nn),
+            0xD000 => Op::Draw(x, y, n),
+            0xE000 => match nn {
+                0x9E => Op::SkipIfKeyPressed(x),
+                0xA1 => Op::SkipIfKeyNotPressed(x),
+                _ => Op::Unsupported(opcode),
+            },
+            _ => match nn {
+                0x02 if x == 0 => Op::LoadAudioPattern,
+                0x07 => Op::GetDelay(x),
+                0x0A => Op::WaitForKey(x),
+                0x15 => Op::SetDelay(x),
+                0x18 => Op::SetSound(x),
+                0x1E => Op::AddI(x),
+                0x29 => Op::SetIToFont(x),
+                0x33 => Op::StoreBcd(x),
+                0x3A => Op::SetPitch(x),
+                0x55 => Op::StoreRegisters(x),
+                0x65 => Op::LoadRegisters(x),
+                _ => Op::Unsupported(opcode),
+            },
+        }
+    }
+
+    // Whether the instruction can change the program counter, wait for a key, fail or write to
+    // RAM (possibly the code that follows). A basic block ends with such an instruction.
+    pub fn ends_block(&self) -> bool {
+        matches!(
+            self,
+            Op::Return
+                | Op::Jump(_)
+                | Op::Call(_)
+                | Op::SkipIfEqual(..)
+                | Op::SkipIfNotEqual(..)
+                | Op::SkipIfRegistersEqual(..)
+                | Op::SkipIfRegistersNotEqual(..)
+                | Op::JumpWithOffset(..)
+                | Op::SkipIfKeyPressed(_)
+                | Op::SkipIfKeyNotPressed(_)
+                | Op::WaitForKey(_)
+                | Op::StoreBcd(_)
+                | Op::StoreRegisters(_)
+                | Op::Unsupported(_)
+        )
+    }
+}
@@ -0,0 +1,81 @@
+// A palette maps the value of a pixel in `vram` to a 0xRRGGBB color. CHIP-8 only uses 0
+// (background) and 1 (foreground) but there is room for the 4 colors of XO-CHIP, which has two
+// bit planes: index 2 is the second plane and index 3 is the blend of both planes.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [u32; 4],
+}
+
+pub const PRESETS: &[(&str, Palette)] = &[
+    ("default", Palette::new(0x000000, 0xFFFFFF)),
+    ("green", Palette::new(0x0C1A0C, 0x41FF00)),
+    ("amber", Palette::new(0x1A1000, 0xFFB000)),
+    (
+        "lcd",
+        Palette {
+            colors: [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F],
+        },
+    ),
+    (
+        "octo",
+        Palette {
+            colors: [0x996600, 0xFFCC00, 0xFF6600, 0x662200],
+        },
+    ),
+];
+
+impl Default for Palette {
+    fn default() -> Self {
+        PRESETS[0].1
+    }
+}
+
+impl Palette {
+    // Creates a two-color palette.
+    pub const fn new(background: u32, foreground: u32) -> Self {
+        Palette {
+            colors: [background, foreground, foreground, foreground],
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|(_, palette)| *palette)
+    }
+
+    pub fn set_background(&mut self, color: u32) {
+        self.colors[0] = color;
+    }
+
+    // Sets the color of all the non-background pixels.
+    pub fn set_foreground(&mut self, color: u32) {
+        for c in self.colors.iter_mut().skip(1) {
+            *c = color;
+        }
+    }
+
+    pub fn color(&self, pixel: u8) -> u32 {
+        self.colors[(pixel & 0x3) as usize]
+    }
+
+    // Fills a 0x00RRGGBB framebuffer (e.g. for minifb) with the pixels of `vram`.
+    pub fn render(&self, vram: &[u8], framebuffer: &mut [u32]) {
+        for (dst, pixel) in framebuffer.iter_mut().zip(vram.iter()) {
+            *dst = self.color(*pixel);
+        }
+    }
+
+    // Fills an RGBA framebuffer (4 bytes per pixel, e.g. for a canvas) with the pixels of `vram`.
+    pub fn render_rgba(&self, vram: &[u8], framebuffer: &mut [u8]) {
+        for (dst, pixel) in framebuffer.chunks_exact_mut(4).zip(vram.iter()) {
+            let color = self.color(*pixel);
+            dst[0] = (color >> 16) as u8;
+            dst[1] = (color >> 8) as u8;
+            dst[2] = color as u8;
+            dst[3] = 0xFF;
+        }
+    }
+}
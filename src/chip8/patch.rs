@@ -0,0 +1,364 @@
+// Patches fix or modify a ROM without distributing the ROM itself. Two formats are supported, and
+// detected from their header:
+//
+// - IPS (https://zerosoft.zophar.net/ips.php): records of bytes to write at given offsets. It has
+//   no checksums, so it applies to any ROM.
+// - BPS (https://www.romhacking.net/documents/746/): copies from the original ROM and new bytes,
+//   with the CRC32 of the original ROM, of the patched ROM and of the patch. The checksums of the
+//   patch and of the original ROM are verified before applying it, and the one of the patched ROM
+//   after.
+//
+// `create_ips()` and `create_bps()` create a patch from an original and a modified ROM.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+// The size of an IPS record is stored in 16 bits, and a size of 0 is used by RLE records.
+const IPS_MAX_RECORD_SIZE: usize = 0xFFFF;
+
+const BPS_MAGIC: &[u8] = b"BPS1";
+const BPS_SOURCE_READ: u64 = 0;
+const BPS_TARGET_READ: u64 = 1;
+const BPS_SOURCE_COPY: u64 = 2;
+const BPS_TARGET_COPY: u64 = 3;
+// The patch ends with the CRC32 of the source, of the target and of the patch itself.
+const BPS_FOOTER_SIZE: usize = 12;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PatchError {
+    UnknownFormat,
+    // The patch is truncated or malformed.
+    InvalidPatch,
+    // The patch is corrupted.
+    PatchChecksum,
+    // The patch has been made for another ROM.
+    SourceChecksum,
+    TargetChecksum,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::UnknownFormat => write!(f, "unknown patch format (expected IPS or BPS)"),
+            PatchError::InvalidPatch => write!(f, "invalid patch"),
+            PatchError::PatchChecksum => write!(f, "the checksum of the patch does not match"),
+            PatchError::SourceChecksum => {
+                write!(
+                    f,
+                    "the checksum of the ROM does not match the one of the patch"
+                )
+            }
+            PatchError::TargetChecksum => {
+                write!(
+                    f,
+                    "the checksum of the patched ROM does not match the one of the patch"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+// Applies an IPS or a BPS patch to `rom` and returns the patched ROM.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat)
+    }
+}
+
+// Reads a patch from the start of a byte slice.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], offset: usize) -> Self {
+        Reader { bytes, offset }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PatchError> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .ok_or(PatchError::InvalidPatch)?;
+        let bytes = self
+            .bytes
+            .get(self.offset..end)
+            .ok_or(PatchError::InvalidPatch)?;
+        self.offset = end;
+
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, PatchError> {
+        Ok(self.take(1)?[0])
+    }
+
+    // Reads a big-endian number of `len` bytes.
+    fn number(&mut self, len: usize) -> Result<usize, PatchError> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0, |n, byte| n << 8 | *byte as usize))
+    }
+
+    // Reads a variable-length number of BPS.
+    fn varint(&mut self) -> Result<u64, PatchError> {
+        let mut value: u64 = 0;
+        let mut shift: u64 = 1;
+        loop {
+            let byte = self.byte()?;
+            value = (byte as u64 & 0x7F)
+                .checked_mul(shift)
+                .and_then(|n| n.checked_add(value))
+                .ok_or(PatchError::InvalidPatch)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_shl(7).ok_or(PatchError::InvalidPatch)?;
+            value = value.checked_add(shift).ok_or(PatchError::InvalidPatch)?;
+        }
+    }
+
+    fn is_at(&self, bytes: &[u8]) -> bool {
+        self.bytes[self.offset..].starts_with(bytes)
+    }
+}
+
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !patch.starts_with(IPS_MAGIC) {
+        return Err(PatchError::UnknownFormat);
+    }
+
+    let mut patched = rom.to_vec();
+    let mut reader = Reader::new(patch, IPS_MAGIC.len());
+
+    while !reader.is_at(IPS_EOF) {
+        let offset = reader.number(3)?;
+        let bytes = match reader.number(2)? {
+            // Run-length encoded record.
+            0 => {
+                let len = reader.number(2)?;
+                vec![reader.byte()?; len]
+            }
+            len => reader.take(len)?.to_vec(),
+        };
+
+        if patched.len() < offset + bytes.len() {
+            patched.resize(offset + bytes.len(), 0);
+        }
+        patched[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+    reader.take(IPS_EOF.len())?;
+
+    // An extension of the format truncates the ROM to the size that follows EOF.
+    if let Ok(size) = reader.number(3) {
+        patched.truncate(size);
+    }
+
+    Ok(patched)
+}
+
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !patch.starts_with(BPS_MAGIC) {
+        return Err(PatchError::UnknownFormat);
+    }
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER_SIZE {
+        return Err(PatchError::InvalidPatch);
+    }
+
+    let body_size = patch.len() - BPS_FOOTER_SIZE;
+    let mut footer = Reader::new(patch, body_size);
+    let source_crc = footer.take(4).map(read_u32_le)?;
+    let target_crc = footer.take(4).map(read_u32_le)?;
+    let patch_crc = footer.take(4).map(read_u32_le)?;
+    if crc32(&patch[..patch.len() - 4]) != patch_crc {
+        return Err(PatchError::PatchChecksum);
+    }
+
+    let mut reader = Reader::new(&patch[..body_size], BPS_MAGIC.len());
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    if source_size != rom.len() as u64 || crc32(rom) != source_crc {
+        return Err(PatchError::SourceChecksum);
+    }
+    // The metadata (e.g. an XML document about the patch) is skipped.
+    let metadata_size = reader.varint()?;
+    reader.take(to_usize(metadata_size)?)?;
+
+    let target_size = to_usize(target_size)?;
+    let mut target: Vec<u8> = Vec::with_capacity(target_size.min(patch.len() * 2));
+    let mut source_offset: i64 = 0;
+    let mut target_offset: i64 = 0;
+
+    while reader.offset < body_size {
+        let action = reader.varint()?;
+        let len = to_usize((action >> 2) + 1)?;
+        if target.len() + len > target_size {
+            return Err(PatchError::InvalidPatch);
+        }
+
+        match action & 3 {
+            BPS_SOURCE_READ => {
+                let start = target.len();
+                let bytes = rom
+                    .get(start..start + len)
+                    .ok_or(PatchError::InvalidPatch)?;
+                target.extend_from_slice(bytes);
+            }
+            BPS_TARGET_READ => target.extend_from_slice(reader.take(len)?),
+            BPS_SOURCE_COPY => {
+                source_offset += relative_offset(reader.varint()?);
+                let start = usize::try_from(source_offset).map_err(|_| PatchError::InvalidPatch)?;
+                let bytes = rom
+                    .get(start..start + len)
+                    .ok_or(PatchError::InvalidPatch)?;
+                target.extend_from_slice(bytes);
+                source_offset += len as i64;
+            }
+            BPS_TARGET_COPY => {
+                target_offset += relative_offset(reader.varint()?);
+                // The copy can overlap the bytes it writes, e.g. to repeat a pattern, so it is
+                // done one byte at a time.
+                for _ in 0..len {
+                    let offset =
+                        usize::try_from(target_offset).map_err(|_| PatchError::InvalidPatch)?;
+                    let byte = *target.get(offset).ok_or(PatchError::InvalidPatch)?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if target.len() != target_size || crc32(&target) != target_crc {
+        return Err(PatchError::TargetChecksum);
+    }
+
+    Ok(target)
+}
+
+// Creates an IPS patch that turns `original` into `modified`, with a record for each run of bytes
+// that differ. The bytes that are added at the end of a ROM always differ.
+pub fn create_ips(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    let mut patch = IPS_MAGIC.to_vec();
+    let differs = |i: usize| original.get(i) != Some(&modified[i]);
+
+    let mut i = 0;
+    while i < modified.len() {
+        if !differs(i) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < modified.len() && i - start < IPS_MAX_RECORD_SIZE && differs(i) {
+            i += 1;
+        }
+        patch.extend_from_slice(&(start as u32).to_be_bytes()[1..]);
+        patch.extend_from_slice(&((i - start) as u16).to_be_bytes());
+        patch.extend_from_slice(&modified[start..i]);
+    }
+
+    patch.extend_from_slice(IPS_EOF);
+    if modified.len() < original.len() {
+        patch.extend_from_slice(&(modified.len() as u32).to_be_bytes()[1..]);
+    }
+
+    patch
+}
+
+// Creates a BPS patch that turns `original` into `modified`, which copies the bytes that have not
+// changed from the original ROM.
+pub fn create_bps(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    let mut patch = BPS_MAGIC.to_vec();
+    write_varint(&mut patch, original.len() as u64);
+    write_varint(&mut patch, modified.len() as u64);
+    write_varint(&mut patch, 0);
+
+    let unchanged = |i: usize| original.get(i) == Some(&modified[i]);
+    let mut i = 0;
+    while i < modified.len() {
+        let start = i;
+        let command = if unchanged(i) {
+            while i < modified.len() && unchanged(i) {
+                i += 1;
+            }
+            BPS_SOURCE_READ
+        } else {
+            while i < modified.len() && !unchanged(i) {
+                i += 1;
+            }
+            BPS_TARGET_READ
+        };
+
+        write_varint(&mut patch, ((i - start - 1) as u64) << 2 | command);
+        if command == BPS_TARGET_READ {
+            patch.extend_from_slice(&modified[start..i]);
+        }
+    }
+
+    patch.extend_from_slice(&crc32(original).to_le_bytes());
+    patch.extend_from_slice(&crc32(modified).to_le_bytes());
+    let patch_crc = crc32(&patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+
+    patch
+}
+
+fn write_varint(patch: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            patch.push(0x80 | byte);
+            return;
+        }
+        patch.push(byte);
+        value -= 1;
+    }
+}
+
+// The offsets of the copy commands are relative, with the sign in the lowest bit.
+fn relative_offset(value: u64) -> i64 {
+    let offset = (value >> 1) as i64;
+    if value & 1 != 0 {
+        -offset
+    } else {
+        offset
+    }
+}
+
+fn to_usize(value: u64) -> Result<usize, PatchError> {
+    usize::try_from(value).map_err(|_| PatchError::InvalidPatch)
+}
+
+fn read_u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+// The CRC32 of zlib and PNG (polynomial 0xEDB88320).
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
@@ -0,0 +1,118 @@
+// CHIP-8 games flicker a lot because sprites are erased and drawn again with XOR. This filter
+// simulates the persistence of a CRT phosphor: a pixel that is turned off fades out over a few
+// frames instead of disappearing at once, which hides most of the flicker.
+//
+// `update()` must be called once per frame (i.e. at 60 Hz) and the result is rendered with a
+// palette, blending the color of each pixel with the background according to its intensity.
+
+use super::Palette;
+
+// The fraction of its intensity that a pixel keeps at each frame once it is turned off.
+pub const DEFAULT_DECAY: f32 = 0.5;
+
+// Below this intensity, a pixel is considered to be off.
+const MIN_INTENSITY: f32 = 1.0 / 256.0;
+
+pub struct Phosphor {
+    decay: f32,
+    intensities: Vec<f32>,
+    // The last value of each pixel that was on, so that it fades with the right color.
+    pixels: Vec<u8>,
+}
+
+impl Default for Phosphor {
+    fn default() -> Self {
+        Phosphor::new(DEFAULT_DECAY)
+    }
+}
+
+impl Phosphor {
+    pub fn new(decay: f32) -> Self {
+        Phosphor {
+            decay: decay.clamp(0.0, 1.0),
+            intensities: vec![0.0; super::WIDTH * super::HEIGHT],
+            pixels: vec![0; super::WIDTH * super::HEIGHT],
+        }
+    }
+
+    pub fn get_decay(&self) -> f32 {
+        self.decay
+    }
+
+    pub fn set_decay(&mut self, decay: f32) {
+        self.decay = decay.clamp(0.0, 1.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.intensities.iter_mut().for_each(|i| *i = 0.0);
+        self.pixels.iter_mut().for_each(|p| *p = 0);
+    }
+
+    // Adds a frame to the filter.
+    pub fn update(&mut self, vram: &[u8]) {
+        for ((intensity, last), pixel) in self
+            .intensities
+            .iter_mut()
+            .zip(self.pixels.iter_mut())
+            .zip(vram.iter())
+        {
+            if *pixel != 0 {
+                *intensity = 1.0;
+                *last = *pixel;
+            } else {
+                *intensity *= self.decay;
+                if *intensity < MIN_INTENSITY {
+                    *intensity = 0.0;
+                }
+            }
+        }
+    }
+
+    // Returns `true` while some pixels are fading out, i.e. when the frontends should keep
+    // redrawing the screen even though `vram` did not change.
+    pub fn is_fading(&self) -> bool {
+        self.intensities.iter().any(|i| *i > 0.0 && *i < 1.0)
+    }
+
+    // Returns the intensity of each pixel, between 0.0 (off) and 1.0 (on).
+    pub fn get_intensities(&self) -> &[f32] {
+        &self.intensities
+    }
+
+    // Fills a 0x00RRGGBB framebuffer (e.g. for minifb).
+    pub fn render(&self, palette: &Palette, framebuffer: &mut [u32]) {
+        for (dst, color) in framebuffer.iter_mut().zip(self.colors(palette)) {
+            *dst = color;
+        }
+    }
+
+    // Fills an RGBA framebuffer (4 bytes per pixel, e.g. for a canvas).
+    pub fn render_rgba(&self, palette: &Palette, framebuffer: &mut [u8]) {
+        for (dst, color) in framebuffer.chunks_exact_mut(4).zip(self.colors(palette)) {
+            dst[0] = (color >> 16) as u8;
+            dst[1] = (color >> 8) as u8;
+            dst[2] = color as u8;
+            dst[3] = 0xFF;
+        }
+    }
+
+    fn colors<'a>(&'a self, palette: &'a Palette) -> impl Iterator<Item = u32> + 'a {
+        let background = palette.color(0);
+
+        self.intensities
+            .iter()
+            .zip(self.pixels.iter())
+            .map(move |(intensity, pixel)| blend(background, palette.color(*pixel), *intensity))
+    }
+}
+
+// Mixes two 0xRRGGBB colors, `t` being the weight of `foreground`.
+fn blend(background: u32, foreground: u32, t: f32) -> u32 {
+    let channel = |shift: u32| {
+        let b = ((background >> shift) & 0xFF) as f32;
+        let f = ((foreground >> shift) & 0xFF) as f32;
+        ((b + (f - b) * t).round() as u32) << shift
+    };
+
+    channel(16) | channel(8) | channel(0)
+}
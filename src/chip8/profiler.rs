@@ -0,0 +1,223 @@
+// Counts the instructions executed at each address and in each subroutine, to find the hot spots
+// of a ROM. Subroutines are tracked with 2NNN (call) and 00EE (return): the instructions executed
+// between the two, including those of nested calls, are attributed to the subroutine. One
+// instruction is one cycle, and the instructions executed while waiting for a key (FX0A) are
+// counted at the address of FX0A.
+//
+// Enable it with `Interpreter::set_profiling()` and get it with `Interpreter::get_profile()`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+use super::disassembler::disassemble;
+use super::mmu;
+use super::op::Op;
+use super::symbols::Symbols;
+use super::Interpreter;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Subroutine {
+    pub addr: u16,
+    pub calls: u64,
+    // The instructions executed in the subroutine, including those of the subroutines it calls.
+    pub cycles: u64,
+    // The instructions executed in the subroutine itself.
+    pub self_cycles: u64,
+}
+
+#[derive(Clone)]
+pub struct Profile {
+    counts: Vec<u64>,
+    total: u64,
+    // The subroutines that are currently being executed, innermost last.
+    call_stack: Vec<u16>,
+    calls: BTreeMap<u16, u64>,
+    // The number of instructions executed with each call stack.
+    stacks: HashMap<Vec<u16>, u64>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            counts: vec![0; mmu::RAM_SIZE],
+            total: 0,
+            call_stack: Vec::new(),
+            calls: BTreeMap::new(),
+            stacks: HashMap::new(),
+        }
+    }
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Profile::default()
+    }
+
+    // Records an instruction that has been executed at `addr`.
+    pub(crate) fn record(&mut self, addr: usize, op: Op) {
+        self.counts[addr % mmu::RAM_SIZE] += 1;
+        self.total += 1;
+
+        match self.stacks.get_mut(self.call_stack.as_slice()) {
+            Some(count) => *count += 1,
+            None => {
+                self.stacks.insert(self.call_stack.clone(), 1);
+            }
+        }
+
+        match op {
+            Op::Call(addr) => {
+                self.call_stack.push(addr);
+                *self.calls.entry(addr).or_insert(0) += 1;
+            }
+            Op::Return => {
+                self.call_stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    // Forgets the subroutines that are being executed, when the stack of the CPU is reset or
+    // restored from a snapshot.
+    pub(crate) fn clear_call_stack(&mut self) {
+        self.call_stack.clear();
+    }
+
+    // Returns the number of instructions executed so far.
+    pub fn get_total(&self) -> u64 {
+        self.total
+    }
+
+    // Returns the number of times the instruction at `addr` has been executed.
+    pub fn get_count(&self, addr: u16) -> u64 {
+        self.counts[addr as usize % mmu::RAM_SIZE]
+    }
+
+    // Returns the addresses that have been executed with their counts, most executed first.
+    pub fn hot_spots(&self) -> Vec<(u16, u64)> {
+        let mut hot_spots: Vec<(u16, u64)> = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(addr, count)| (addr as u16, *count))
+            .collect();
+        hot_spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        hot_spots
+    }
+
+    // Returns the subroutines that have been called, most expensive first.
+    pub fn subroutines(&self) -> Vec<Subroutine> {
+        let mut subroutines: BTreeMap<u16, Subroutine> = self
+            .calls
+            .iter()
+            .map(|(addr, calls)| {
+                let subroutine = Subroutine {
+                    addr: *addr,
+                    calls: *calls,
+                    cycles: 0,
+                    self_cycles: 0,
+                };
+                (*addr, subroutine)
+            })
+            .collect();
+
+        for (stack, count) in &self.stacks {
+            // A recursive subroutine appears several times in the same stack but its cycles
+            // must only be counted once.
+            let unique: HashSet<&u16> = stack.iter().collect();
+            for addr in unique {
+                if let Some(subroutine) = subroutines.get_mut(addr) {
+                    subroutine.cycles += count;
+                }
+            }
+            if let Some(subroutine) = stack.last().and_then(|addr| subroutines.get_mut(addr)) {
+                subroutine.self_cycles += count;
+            }
+        }
+
+        let mut subroutines: Vec<Subroutine> = subroutines.into_values().collect();
+        subroutines.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.addr.cmp(&b.addr)));
+
+        subroutines
+    }
+
+    // Returns a human-readable report with the `limit` most expensive subroutines and the `limit`
+    // most executed instructions, disassembled from the RAM of `interpreter`.
+    pub fn report(&self, interpreter: &Interpreter, symbols: &Symbols, limit: usize) -> String {
+        let percent = |count: u64| 100.0 * count as f64 / self.total.max(1) as f64;
+        let mut report = String::new();
+
+        writeln!(report, "{} instructions", self.total).unwrap();
+
+        writeln!(report).unwrap();
+        writeln!(report, "Subroutines").unwrap();
+        writeln!(
+            report,
+            "{:>12} {:>6} {:>12} {:>6} {:>10}  subroutine",
+            "cycles", "%", "self", "%", "calls"
+        )
+        .unwrap();
+        for subroutine in self.subroutines().iter().take(limit) {
+            writeln!(
+                report,
+                "{:>12} {:>6.2} {:>12} {:>6.2} {:>10}  {}",
+                subroutine.cycles,
+                percent(subroutine.cycles),
+                subroutine.self_cycles,
+                percent(subroutine.self_cycles),
+                subroutine.calls,
+                symbols.name(subroutine.addr)
+            )
+            .unwrap();
+        }
+
+        writeln!(report).unwrap();
+        writeln!(report, "Hot spots").unwrap();
+        writeln!(
+            report,
+            "{:>12} {:>6}  address  opcode  {:<18}  symbol",
+            "count", "%", "instruction"
+        )
+        .unwrap();
+        for (addr, count) in self.hot_spots().into_iter().take(limit) {
+            let opcode = (interpreter.read_byte(addr) as u16) << 8
+                | interpreter.read_byte(addr.wrapping_add(1)) as u16;
+            writeln!(
+                report,
+                "{:>12} {:>6.2}  0x{:03X}    {:04X}    {:<18}  {}",
+                count,
+                percent(count),
+                addr,
+                opcode,
+                disassemble(opcode),
+                symbols.describe(addr).unwrap_or_default()
+            )
+            .unwrap();
+        }
+
+        report
+    }
+
+    // Returns the call stacks in the "folded" format of flame graph tools, e.g. `flamegraph.pl` or
+    // `inferno-flamegraph`: one line per call stack, with the subroutines separated by semicolons
+    // and followed by the number of instructions executed in the innermost one.
+    pub fn folded(&self, symbols: &Symbols) -> String {
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| {
+                let mut line = "main".to_string();
+                for addr in stack {
+                    line.push(';');
+                    line.push_str(&symbols.name(*addr));
+                }
+                format!("{} {}\n", line, count)
+            })
+            .collect();
+        lines.sort();
+
+        lines.concat()
+    }
+}
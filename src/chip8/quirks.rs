@@ -0,0 +1,88 @@
+// CHIP-8 has been implemented many times since the COSMAC VIP and the various interpreters do not
+// agree on the behavior of a handful of instructions. ROMs written for one interpreter sometimes
+// rely on its "quirks", so we make them configurable.
+//
+// The default value matches what chipolata has always done, i.e. a mix of CHIP-48 and original
+// CHIP-8 behaviors that works with most ROMs.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY6 and 8XYE shift VY and store the result in VX (original) instead of shifting VX in
+    // place (CHIP-48).
+    pub shift_vy: bool,
+    // FX55 and FX65 increment I by X + 1 (original) instead of leaving it untouched (CHIP-48).
+    pub load_store_increment_i: bool,
+    // BNNN jumps to VX + NNN where X is the highest nibble of NNN (CHIP-48) instead of V0 + NNN.
+    pub jump_vx: bool,
+    // 8XY1, 8XY2 and 8XY3 reset VF to 0 (original).
+    pub vf_reset: bool,
+    // DXYN clips sprites at the edges of the screen instead of wrapping them around.
+    pub clip_sprites: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::DEFAULT
+    }
+}
+
+impl Quirks {
+    // Same as `Quirks::default()`, for constants (e.g. the ROM database).
+    pub const DEFAULT: Quirks = Quirks {
+        shift_vy: false,
+        load_store_increment_i: false,
+        jump_vx: false,
+        vf_reset: false,
+        clip_sprites: false,
+    };
+
+    // The behavior of the original COSMAC VIP interpreter.
+    pub const fn chip8() -> Self {
+        Quirks {
+            shift_vy: true,
+            load_store_increment_i: true,
+            jump_vx: false,
+            vf_reset: true,
+            clip_sprites: true,
+        }
+    }
+
+    // The behavior of CHIP-48 and SUPER-CHIP on the HP-48 calculators.
+    pub const fn schip() -> Self {
+        Quirks {
+            shift_vy: false,
+            load_store_increment_i: false,
+            jump_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+        }
+    }
+
+    // Returns the quirks for a preset name: "default", "chip8" or "schip".
+    pub fn preset(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "default" => Some(Quirks::default()),
+            "chip8" | "chip-8" => Some(Quirks::chip8()),
+            "schip" | "superchip" => Some(Quirks::schip()),
+            _ => None,
+        }
+    }
+
+    pub fn to_bits(self) -> u8 {
+        (self.shift_vy as u8)
+            | (self.load_store_increment_i as u8) << 1
+            | (self.jump_vx as u8) << 2
+            | (self.vf_reset as u8) << 3
+            | (self.clip_sprites as u8) << 4
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Quirks {
+            shift_vy: bits & 1 != 0,
+            load_store_increment_i: bits & (1 << 1) != 0,
+            jump_vx: bits & (1 << 2) != 0,
+            vf_reset: bits & (1 << 3) != 0,
+            clip_sprites: bits & (1 << 4) != 0,
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// Sprites are the graphics of a ROM: N bytes at the address in I, drawn by DXYN as 8xN pixels.
+// They are collected while the ROM runs (see `Coverage::sprites()`) or found by static analysis
+// (see `Analysis::sprites()`), then rendered to a PNG sheet or exported as sprite literals for
+// Octo or for `assembler::assemble()`.
+//
+// The bytes of the sprites are read from the RAM of an interpreter, so the sprites that are
+// generated at runtime are exported as they were at that time.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use super::assembler;
+use super::capture;
+use super::mmu::FONT;
+use super::symbols::Symbols;
+use super::{Interpreter, Palette};
+
+// The sheet is a grid of cells, each with a sprite (at most 8x15 pixels) and its address below.
+const COLUMNS: usize = 8;
+const CELL_WIDTH: usize = 20;
+const CELL_HEIGHT: usize = 24;
+const SPRITE_X: usize = 6;
+const SPRITE_Y: usize = 1;
+const LABEL_X: usize = 3;
+const LABEL_Y: usize = 18;
+const SHEET_WIDTH: usize = COLUMNS * CELL_WIDTH;
+
+// The palette indexes of the sheet: the addresses use the color of the second XO-CHIP plane, which
+// is the foreground color of two-color palettes.
+const SPRITE_PIXEL: u8 = 1;
+const LABEL_PIXEL: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sprite {
+    pub addr: u16,
+    pub height: u8,
+}
+
+impl Sprite {
+    pub fn new(addr: u16, height: u8) -> Self {
+        Sprite { addr, height }
+    }
+
+    // Returns the rows of the sprite, one byte (8 pixels) per row.
+    pub fn rows(&self, interpreter: &Interpreter) -> Vec<u8> {
+        (0..self.height as u16)
+            .map(|y| interpreter.read_byte(self.addr.wrapping_add(y)))
+            .collect()
+    }
+
+    // Returns the sprite as text, one line per row, e.g. "####....".
+    pub fn to_text(&self, interpreter: &Interpreter) -> String {
+        self.rows(interpreter)
+            .iter()
+            .map(|row| format!("{}\n", pixels(*row)))
+            .collect()
+    }
+
+    // The name of the sprite in the exported literals: its symbol if it has one that is a valid
+    // label, or e.g. "sprite_2A0".
+    fn name(&self, symbols: &Symbols) -> String {
+        match symbols
+            .get(self.addr)
+            .filter(|name| assembler::is_label(name))
+        {
+            Some(name) => name.to_string(),
+            None => format!("sprite_{:03X}", self.addr),
+        }
+    }
+}
+
+// Merges lists of sprites, e.g. those found by static analysis and those drawn at runtime. When a
+// sprite appears with different heights, the tallest one is kept.
+pub fn merge<I: IntoIterator<Item = Sprite>>(sprites: I) -> Vec<Sprite> {
+    let mut tallest: BTreeMap<u16, u8> = BTreeMap::new();
+    for sprite in sprites {
+        let height = tallest.entry(sprite.addr).or_insert(0);
+        *height = (*height).max(sprite.height);
+    }
+
+    tallest
+        .into_iter()
+        .map(|(addr, height)| Sprite::new(addr, height))
+        .collect()
+}
+
+fn pixels(row: u8) -> String {
+    (0..8)
+        .map(|x| if row & (0x80 >> x) != 0 { '#' } else { '.' })
+        .collect()
+}
+
+// Writes the sprites to `writer` as a PNG sheet, 8 sprites per row, with the address of each
+// sprite below it.
+pub fn write_sheet<W: Write>(
+    writer: W,
+    sprites: &[Sprite],
+    interpreter: &Interpreter,
+    palette: &Palette,
+    scale: u8,
+) -> io::Result<()> {
+    let rows = sprites.len().div_ceil(COLUMNS).max(1);
+    let mut image = vec![0; SHEET_WIDTH * rows * CELL_HEIGHT];
+
+    for (n, sprite) in sprites.iter().enumerate() {
+        let left = (n % COLUMNS) * CELL_WIDTH;
+        let top = (n / COLUMNS) * CELL_HEIGHT;
+
+        for (y, row) in sprite.rows(interpreter).iter().enumerate() {
+            blit(
+                &mut image,
+                left + SPRITE_X,
+                top + SPRITE_Y + y,
+                *row,
+                SPRITE_PIXEL,
+            );
+        }
+
+        let label = format!("{:03X}", sprite.addr & 0xFFF);
+        for (i, digit) in label.chars().enumerate() {
+            let digit = digit.to_digit(16).unwrap() as usize;
+            for (y, row) in FONT[digit * 5..digit * 5 + 5].iter().enumerate() {
+                blit(
+                    &mut image,
+                    left + LABEL_X + i * 5,
+                    top + LABEL_Y + y,
+                    *row,
+                    LABEL_PIXEL,
+                );
+            }
+        }
+    }
+
+    capture::write_indexed_png(writer, &image, SHEET_WIDTH, palette, scale)
+}
+
+// Returns the sprite sheet encoded as a PNG image.
+pub fn sheet_png(
+    sprites: &[Sprite],
+    interpreter: &Interpreter,
+    palette: &Palette,
+    scale: u8,
+) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    write_sheet(&mut bytes, sprites, interpreter, palette, scale)?;
+
+    Ok(bytes)
+}
+
+// Sets the pixels of an 8-pixel row of the sheet to `color`.
+fn blit(image: &mut [u8], x: usize, y: usize, row: u8, color: u8) {
+    for dx in 0..8 {
+        if row & (0x80 >> dx) != 0 {
+            image[x + dx + y * SHEET_WIDTH] = color;
+        }
+    }
+}
+
+// Returns the sprites as Octo source code, with a label and one byte per row:
+//
+//   : sprite_2A0 # 0x2A0, 8x5
+//     0xF0 # ####....
+pub fn to_octo(sprites: &[Sprite], interpreter: &Interpreter, symbols: &Symbols) -> String {
+    let mut octo = String::new();
+
+    for sprite in sprites {
+        writeln!(
+            octo,
+            ": {} # 0x{:03X}, 8x{}",
+            sprite.name(symbols),
+            sprite.addr,
+            sprite.height
+        )
+        .unwrap();
+        for row in sprite.rows(interpreter) {
+            writeln!(octo, "  0x{:02X} # {}", row, pixels(row)).unwrap();
+        }
+        writeln!(octo).unwrap();
+    }
+
+    octo
+}
+
+// Returns the sprites in the syntax of `assembler::assemble()`, with a label and one DB per row:
+//
+//   sprite_2A0:                 ; 0x2A0, 8x5
+//       DB F0                   ; ####....
+pub fn to_assembler(sprites: &[Sprite], interpreter: &Interpreter, symbols: &Symbols) -> String {
+    let mut source = String::new();
+
+    for sprite in sprites {
+        let label = format!("{}:", sprite.name(symbols));
+        writeln!(
+            source,
+            "{:<28}; 0x{:03X}, 8x{}",
+            label, sprite.addr, sprite.height
+        )
+        .unwrap();
+        for row in sprite.rows(interpreter) {
+            let text = format!("DB {:02X}", row);
+            writeln!(source, "    {:<24}; {}", text, pixels(row)).unwrap();
+        }
+        writeln!(source).unwrap();
+    }
+
+    source
+}
@@ -0,0 +1,104 @@
+// Names for addresses in RAM (subroutines, data, etc.) to annotate reports and listings.
+//
+// A symbols file has one symbol per line: an address in hexadecimal, optionally prefixed with
+// "0x" or "$", followed by a name. Blank lines and lines starting with "#" are ignored:
+//
+//   # Space Invaders
+//   0x200 main
+//   $2A0  draw_aliens
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SymbolsError {
+    // The line number, starting at 1.
+    InvalidLine(usize),
+}
+
+impl fmt::Display for SymbolsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolsError::InvalidLine(line) => write!(f, "invalid symbol at line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for SymbolsError {}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Symbols {
+    names: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Symbols::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SymbolsError> {
+        let mut symbols = Symbols::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let (addr, name) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(addr), Some(name), None) => (addr, name),
+                _ => return Err(SymbolsError::InvalidLine(n + 1)),
+            };
+            let addr = addr
+                .strip_prefix("0x")
+                .or_else(|| addr.strip_prefix('$'))
+                .unwrap_or(addr);
+            let addr =
+                u16::from_str_radix(addr, 16).map_err(|_| SymbolsError::InvalidLine(n + 1))?;
+
+            symbols.insert(addr, name);
+        }
+
+        Ok(symbols)
+    }
+
+    pub fn insert(&mut self, addr: u16, name: &str) {
+        self.names.insert(addr, name.to_string());
+    }
+
+    pub fn get(&self, addr: u16) -> Option<&str> {
+        self.names.get(&addr).map(|name| name.as_str())
+    }
+
+    // Describes an address relative to the closest symbol before it, e.g. "draw_aliens+0x4".
+    pub fn describe(&self, addr: u16) -> Option<String> {
+        self.names
+            .range(..=addr)
+            .next_back()
+            .map(|(start, name)| match addr - start {
+                0 => name.clone(),
+                offset => format!("{}+0x{:X}", name, offset),
+            })
+    }
+
+    // Returns the name of `addr`, or the address itself when it has no symbol.
+    pub fn name(&self, addr: u16) -> String {
+        match self.get(addr) {
+            Some(name) => name.to_string(),
+            None => format!("0x{:03X}", addr),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.names.iter().map(|(addr, name)| (*addr, name.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
@@ -0,0 +1,65 @@
+// Plays the samples generated by `chip8::audio::Beeper` with rodio. The beeper is shared with the
+// main loop, which turns it on and off, while the audio thread pulls the samples.
+
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use libchipolata::chip8::audio::Beeper;
+
+// The number of samples generated at once, so that the mutex is not locked for every sample.
+const CHUNK_SIZE: usize = 256;
+
+pub struct BeeperSource {
+    beeper: Arc<Mutex<Beeper>>,
+    sample_rate: u32,
+    chunk: [f32; CHUNK_SIZE],
+    position: usize,
+}
+
+impl BeeperSource {
+    pub fn new(beeper: Arc<Mutex<Beeper>>) -> Self {
+        let sample_rate = beeper.lock().unwrap().get_sample_rate();
+
+        BeeperSource {
+            beeper,
+            sample_rate,
+            chunk: [0.0; CHUNK_SIZE],
+            position: CHUNK_SIZE,
+        }
+    }
+}
+
+impl Iterator for BeeperSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position == CHUNK_SIZE {
+            self.beeper.lock().unwrap().fill(&mut self.chunk);
+            self.position = 0;
+        }
+
+        let sample = self.chunk[self.position];
+        self.position += 1;
+
+        Some(sample)
+    }
+}
+
+impl Source for BeeperSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
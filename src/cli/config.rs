@@ -0,0 +1,367 @@
+// The configuration file of the desktop frontend, written in TOML. By default, it is read from
+// the user config directory (e.g. `~/.config/chipolata/config.toml` on Linux) when it exists.
+// All the settings are optional and the command line flags take precedence over them. Known ROMs
+// (see `chip8::database`) get their recommended settings unless a ROM section overrides them.
+//
+// Example:
+//
+//   speed = 8
+//   quirks = "chip8"          # "default", "chip8" or "schip"
+//   scale = 8                 # 1, 2, 4, 8, 16 or 32
+//   palette = "amber"         # "default", "green", "amber", "lcd" or "octo"
+//   foreground = "#FFB000"    # overrides the colors of the palette
+//   background = "#1A1000"
+//   phosphor = true           # blends recent frames to reduce flicker
+//   breakpoints = [0x2A0]     # addresses that start the debugger when reached
+//
+//   [audio]
+//   frequency = 440
+//   volume = 0.5
+//
+//   # Bindings of the CHIP-8 keys (0-F) to host keys.
+//   [keys]
+//   5 = ["Z", "Up"]
+//   8 = ["S", "Down"]
+//
+//   # Overrides for a specific ROM, identified by the SHA-1 of its bytes. It accepts the same
+//   # settings as above.
+//   [roms.0123456789abcdef0123456789abcdef01234567]
+//   speed = 15
+//   keys = { 4 = "Left", 6 = "Right" }
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::keymap::{parse_chip8_key, parse_host_key};
+use libchipolata::chip8;
+use libchipolata::chip8::database::RomInfo;
+
+pub type Bindings = BTreeMap<String, KeyNames>;
+
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyNames {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyNames {
+    pub fn names(&self) -> &[String] {
+        match self {
+            KeyNames::One(name) => std::slice::from_ref(name),
+            KeyNames::Many(names) => names,
+        }
+    }
+}
+
+// A 0xRRGGBB color, written "#RRGGBB" in the configuration file.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color(pub u32);
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let hex = value.trim_start_matches('#');
+        match u32::from_str_radix(hex, 16) {
+            Ok(rgb) if hex.len() == 6 => Ok(Color(rgb)),
+            _ => Err(format!("invalid color {:?} (expected \"#RRGGBB\")", value)),
+        }
+    }
+}
+
+// A quirks preset (see `chip8::Quirks::preset()`).
+#[derive(Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct QuirksPreset(pub chip8::Quirks);
+
+impl TryFrom<String> for QuirksPreset {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        chip8::Quirks::preset(&value)
+            .map(QuirksPreset)
+            .ok_or(format!("invalid quirks preset {:?}", value))
+    }
+}
+
+// A palette preset (see `chip8::PALETTES`).
+#[derive(Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct PalettePreset(pub chip8::Palette);
+
+impl TryFrom<String> for PalettePreset {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        chip8::Palette::preset(&value)
+            .map(PalettePreset)
+            .ok_or(format!("invalid palette {:?}", value))
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Audio {
+    pub frequency: Option<u32>,
+    pub volume: Option<f32>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub speed: Option<u8>,
+    pub quirks: Option<QuirksPreset>,
+    pub scale: Option<u8>,
+    pub palette: Option<PalettePreset>,
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub phosphor: Option<bool>,
+    pub audio: Audio,
+    pub breakpoints: Option<Vec<u16>>,
+    pub keys: Bindings,
+}
+
+impl Settings {
+    // Overrides the current settings with the ones that are set in `other`.
+    fn merge(&mut self, other: &Settings) {
+        self.speed = other.speed.or(self.speed);
+        self.quirks = other.quirks.or(self.quirks);
+        self.scale = other.scale.or(self.scale);
+        self.palette = other.palette.or(self.palette);
+        self.foreground = other.foreground.or(self.foreground);
+        self.background = other.background.or(self.background);
+        self.phosphor = other.phosphor.or(self.phosphor);
+        self.audio.frequency = other.audio.frequency.or(self.audio.frequency);
+        self.audio.volume = other.audio.volume.or(self.audio.volume);
+        self.breakpoints = other
+            .breakpoints
+            .clone()
+            .or_else(|| self.breakpoints.clone());
+
+        // A host key that is bound in `other` cannot stay bound to another CHIP-8 key.
+        let rebound: Vec<&String> = other.keys.values().flat_map(|k| k.names()).collect();
+        for names in self.keys.values_mut() {
+            *names = KeyNames::Many(
+                names
+                    .names()
+                    .iter()
+                    .filter(|name| !rebound.contains(name))
+                    .cloned()
+                    .collect(),
+            );
+        }
+        self.keys
+            .extend(other.keys.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+// Returns the bindings with the canonical names of the keys, e.g. "a" and "A" are both the CHIP-8
+// key "A", and "z" and "1" are the host keys "Z" and "Key1". This way, the bindings of a ROM
+// section override the global ones whatever the case of their names.
+fn normalize(bindings: &Bindings) -> Result<Bindings, String> {
+    let mut normalized = Bindings::new();
+
+    for (name, host_names) in bindings {
+        let key = format!("{:X}", parse_chip8_key(name)?);
+        let host_names = host_names
+            .names()
+            .iter()
+            .map(|name| parse_host_key(name).map(|key| format!("{:?}", key)))
+            .collect::<Result<Vec<String>, String>>()?;
+
+        if normalized.insert(key, KeyNames::Many(host_names)).is_some() {
+            return Err(format!("CHIP-8 key {:?} is bound twice", name));
+        }
+    }
+
+    Ok(normalized)
+}
+
+impl From<&RomInfo> for Settings {
+    fn from(info: &RomInfo) -> Self {
+        Settings {
+            speed: Some(info.tickrate),
+            quirks: Some(QuirksPreset(info.quirks)),
+            foreground: info.colors.map(|(foreground, _)| Color(foreground)),
+            background: info.colors.map(|(_, background)| Color(background)),
+            ..Settings::default()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Config {
+    pub global: Settings,
+    pub roms: HashMap<String, Settings>,
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("chipolata").join("config.toml"))
+}
+
+// Returns the path of the cheat list of a ROM (see `chip8::cheats`), named after the SHA-1 of the
+// ROM in the user config directory, e.g. `~/.config/chipolata/cheats/0123...4567.txt`.
+pub fn cheats_path(rom: &[u8]) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| {
+        dir.join("chipolata")
+            .join("cheats")
+            .join(format!("{}.txt", chip8::rom_hash_hex(rom)))
+    })
+}
+
+impl Config {
+    // Loads the configuration from `path` or, when there is no path, from the default location
+    // if the file exists.
+    pub fn load(path: Option<&Path>) -> Result<Config, String> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+
+        let content =
+            fs::read_to_string(&path).map_err(|e| format!("cannot read {:?}: {}", path, e))?;
+
+        Config::parse(&content).map_err(|e| format!("invalid config {:?}: {}", path, e))
+    }
+
+    fn parse(content: &str) -> Result<Config, String> {
+        // The ROM sections are taken out first so that the remaining keys are parsed (and
+        // validated) as the global settings.
+        let mut table: toml::Table = toml::from_str(content).map_err(|e| e.to_string())?;
+        let mut roms: HashMap<String, Settings> = match table.remove("roms") {
+            Some(roms) => roms.try_into().map_err(|e| e.to_string())?,
+            None => HashMap::new(),
+        };
+        let mut global: Settings = table.try_into().map_err(|e| e.to_string())?;
+
+        global.keys = normalize(&global.keys)?;
+        for (hash, settings) in roms.iter_mut() {
+            settings.keys =
+                normalize(&settings.keys).map_err(|e| format!("[roms.{}]: {}", hash, e))?;
+        }
+
+        Ok(Config { global, roms })
+    }
+
+    // Returns the settings for a ROM, i.e. the global settings overridden by the recommended
+    // ones of the ROM database and then by the ones of the ROM section, if any.
+    pub fn settings(&self, rom: &[u8]) -> Settings {
+        let hash = chip8::rom_hash_hex(rom);
+
+        let mut settings = self.global.clone();
+        if let Some(info) = chip8::database::lookup(rom) {
+            settings.merge(&Settings::from(info));
+        }
+        if let Some((_, rom_settings)) = self
+            .roms
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(&hash))
+        {
+            settings.merge(rom_settings);
+        }
+
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A ROM that is not in the database.
+    const ROM: &[u8] = &[0x12, 0x00];
+
+    fn config(rom_section: &str) -> Config {
+        let content = format!(
+            "speed = 8\n\
+            phosphor = true\n\
+            [keys]\n\
+            a = \"Q\"\n\
+            5 = [\"z\", \"Up\"]\n\
+            [roms.{}]\n\
+            {}",
+            chip8::rom_hash_hex(ROM),
+            rom_section
+        );
+
+        Config::parse(&content).unwrap()
+    }
+
+    fn names(settings: &Settings, key: &str) -> Vec<String> {
+        settings.keys[key].names().to_vec()
+    }
+
+    #[test]
+    fn rom_section_overrides_global_settings() {
+        let settings = config("speed = 15\n").settings(ROM);
+
+        assert_eq!(settings.speed, Some(15));
+        assert_eq!(settings.phosphor, Some(true));
+        assert_eq!(config("").settings(&[0x00, 0xE0]).speed, Some(8));
+    }
+
+    #[test]
+    fn rom_section_overrides_global_keys_whatever_their_case() {
+        let settings = config("keys = { A = \"Left\" }\n").settings(ROM);
+
+        assert_eq!(settings.keys.len(), 2);
+        assert_eq!(names(&settings, "A"), vec!["Left"]);
+        assert_eq!(names(&settings, "5"), vec!["Z", "Up"]);
+    }
+
+    #[test]
+    fn rebound_host_keys_are_removed_from_global_keys() {
+        let settings = config("keys = { 8 = \"up\" }\n").settings(ROM);
+
+        assert_eq!(names(&settings, "5"), vec!["Z"]);
+        assert_eq!(names(&settings, "8"), vec!["Up"]);
+        assert_eq!(names(&settings, "A"), vec!["Q"]);
+    }
+
+    #[test]
+    fn database_settings_are_between_global_and_rom_settings() {
+        let rom = include_bytes!("../../docs/space-invaders.ch8");
+        let info = chip8::database::lookup(rom).unwrap();
+        let content = format!(
+            "speed = 8\n[roms.{}]\nphosphor = true\n",
+            chip8::rom_hash_hex(rom)
+        );
+        let settings = Config::parse(&content).unwrap().settings(rom);
+
+        assert_eq!(settings.speed, Some(info.tickrate));
+        assert_eq!(settings.phosphor, Some(true));
+
+        let content = format!("[roms.{}]\nspeed = 20\n", chip8::rom_hash_hex(rom));
+        let settings = Config::parse(&content).unwrap().settings(rom);
+        assert_eq!(settings.speed, Some(20));
+    }
+
+    #[test]
+    fn palette_and_colors() {
+        let settings = config("palette = \"Amber\"\nforeground = \"#41ff00\"\n").settings(ROM);
+
+        assert_eq!(
+            settings.palette.map(|preset| preset.0),
+            chip8::Palette::preset("amber")
+        );
+        assert_eq!(settings.foreground.map(|color| color.0), Some(0x41FF00));
+        assert!(Config::parse("palette = \"nope\"\n").is_err());
+        assert!(Config::parse("background = \"#FFF\"\n").is_err());
+        assert!(Config::parse("background = \"#GGGGGG\"\n").is_err());
+    }
+
+    #[test]
+    fn invalid_keys() {
+        assert!(Config::parse("[keys]\nG = \"Q\"\n").is_err());
+        assert!(Config::parse("[keys]\n5 = \"NotAKey\"\n").is_err());
+        assert!(Config::parse("[keys]\na = \"Q\"\nA = \"W\"\n").is_err());
+    }
+}
@@ -0,0 +1,324 @@
+// The commands of the debugger, which runs in the terminal (see `tui`): in the `--tui` frontend,
+// or in a session started from the display window when a breakpoint is hit. The commands return
+// the lines they print instead of writing them to the console.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use libchipolata::chip8;
+use libchipolata::chip8::cheats::{Cheats, Comparison, Search};
+use libchipolata::chip8::disassembler::disassemble_at;
+use libchipolata::chip8::sprites::Sprite;
+
+// The number of lines printed by the "d" command.
+const DISASSEMBLY_LINES: usize = 10;
+// The number of candidate addresses printed by the "search" command.
+const SEARCH_LINES: usize = 16;
+
+const HELP: &[&str] = &[
+    "  ba [u16] : set breakpoint at address [u16]",
+    "  bo [u8]  : set breakpoint for opcode [u8]",
+    "  c        : continue",
+    "  cheats   : list the cheats",
+    "  cheats save : save the cheats of the ROM",
+    "  d [u16]  : disassemble at address [u16] (default: pc)",
+    "  freeze [u16] [u8] : freeze the byte at address [u16] to [u8]",
+    "  clear    : clear breakpoints",
+    "  p [u16]  : print the byte at address [u16]",
+    "  p cpu    : print cpu info",
+    "  q        : exit",
+    "  s        : step",
+    "  s [u16]  : step [u16] times",
+    "  search   : start a RAM search",
+    "  search [u8] : keep the addresses whose value is [u8]",
+    "  search changed|unchanged|inc|dec : keep the addresses whose value has changed, etc.",
+    "  sprite [u16] [u8] : print the [u8] rows of the sprite at [u16]",
+    "  r        : reset",
+    "  unfreeze [u16] : remove the cheat at address [u16]",
+];
+
+// What the frontend does after a command.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Stay,
+    Continue,
+    Quit,
+}
+
+pub struct Debugger {
+    address_breakpoints: HashSet<u16>,
+    opcode_breakpoints: HashSet<u16>,
+    search: Option<Search>,
+    // Where "cheats save" writes the cheats.
+    cheats_path: Option<PathBuf>,
+    // A movie only records the keypad of whole frames, so the instructions run outside of a frame
+    // (when stepping), a reset or the cheats would not be played back.
+    recording: bool,
+}
+
+impl Debugger {
+    pub fn new(breakpoints: Vec<u16>, cheats_path: Option<PathBuf>, recording: bool) -> Self {
+        Debugger {
+            address_breakpoints: breakpoints.into_iter().collect(),
+            opcode_breakpoints: HashSet::new(),
+            search: None,
+            cheats_path,
+            recording,
+        }
+    }
+
+    // Returns whether the next instruction has a breakpoint, on its address or its opcode.
+    pub fn should_break(&self, interpreter: &chip8::Interpreter) -> bool {
+        let pc = interpreter.get_pc();
+        let opcode = u16::from_be_bytes([
+            interpreter.read_byte(pc),
+            interpreter.read_byte(pc.wrapping_add(1)),
+        ]);
+
+        self.address_breakpoints.contains(&pc) || self.opcode_breakpoints.contains(&opcode)
+    }
+
+    pub fn has_breakpoint(&self, address: u16) -> bool {
+        self.address_breakpoints.contains(&address)
+    }
+
+    // Adds a breakpoint at `address` or removes it, and returns whether it has been added.
+    pub fn toggle_breakpoint(&mut self, address: u16) -> bool {
+        if self.address_breakpoints.remove(&address) {
+            false
+        } else {
+            self.address_breakpoints.insert(address);
+            true
+        }
+    }
+
+    // Runs a command with the keypad state of the frontend, and returns what to do next and the
+    // lines to print.
+    pub fn execute(
+        &mut self,
+        interpreter: &mut chip8::Interpreter,
+        keypad: [bool; 16],
+        input: &str,
+    ) -> (Action, Vec<String>) {
+        let input = input.trim();
+        let mut output = Vec::new();
+
+        if input.is_empty() {
+            return (Action::Stay, output);
+        }
+
+        if input == "q" {
+            return (Action::Quit, output);
+        } else if input == "c" {
+            return (Action::Continue, output);
+        } else if input == "p cpu" {
+            output.extend(format!("{:?}", interpreter.cpu).lines().map(String::from));
+        } else if input == "d" || input.starts_with("d ") {
+            let start = match input.get(2..) {
+                Some(address) => u16::from_str_radix(address, 16).ok(),
+                None => Some(interpreter.get_pc()),
+            };
+            if let Some(mut address) = start {
+                for _ in 0..DISASSEMBLY_LINES {
+                    let (text, size) = disassemble_at(interpreter, address);
+                    output.push(format!("{:04X}: {}", address, text));
+                    address = address.wrapping_add(size);
+                }
+            } else {
+                output.push(format!("Invalid address: {:?}", &input[2..]));
+            }
+        } else if let Some(args) = input.strip_prefix("sprite ") {
+            let mut args = args.split_whitespace();
+            match (
+                args.next().and_then(|a| u16::from_str_radix(a, 16).ok()),
+                args.next().and_then(|n| u8::from_str_radix(n, 16).ok()),
+            ) {
+                (Some(address), Some(height)) if height <= 0xF => {
+                    let text = Sprite::new(address, height).to_text(interpreter);
+                    output.extend(text.lines().map(String::from));
+                }
+                _ => output.push(format!("Invalid sprite: {:?}", &input[7..])),
+            }
+        } else if input == "search" {
+            let search = Search::new(interpreter);
+            output.push(format!(
+                "Started a search: {} candidates",
+                search.candidates().len()
+            ));
+            self.search = Some(search);
+        } else if let Some(filter) = input.strip_prefix("search ") {
+            let comparison = match filter {
+                "changed" => Some(Comparison::Changed),
+                "unchanged" => Some(Comparison::Unchanged),
+                "inc" => Some(Comparison::Increased),
+                "dec" => Some(Comparison::Decreased),
+                value => u8::from_str_radix(value, 16).ok().map(Comparison::Equal),
+            };
+            match (&mut self.search, comparison) {
+                (Some(search), Some(comparison)) => {
+                    search.filter(interpreter, comparison);
+                    output.extend(candidates(search));
+                }
+                (None, _) => output.push("No search, start one with \"search\"".to_string()),
+                (_, None) => output.push(format!("Invalid filter: {:?}", filter)),
+            }
+        } else if self.recording && (input.starts_with("freeze ") || input.starts_with("unfreeze "))
+        {
+            output.push("Cannot change the cheats while recording".to_string());
+        } else if let Some(args) = input.strip_prefix("freeze ") {
+            let mut args = args.split_whitespace();
+            match (
+                args.next().and_then(|a| u16::from_str_radix(a, 16).ok()),
+                args.next().and_then(|v| u8::from_str_radix(v, 16).ok()),
+            ) {
+                (Some(address), Some(value)) => {
+                    let mut cheats = interpreter.get_cheats().clone();
+                    cheats.insert(address, value);
+                    interpreter.set_cheats(cheats);
+                    output.push(format!("Froze 0x{:03X} to 0x{:02X}", address, value));
+                }
+                _ => output.push(format!("Invalid cheat: {:?}", &input[7..])),
+            }
+        } else if let Some(address) = input.strip_prefix("unfreeze ") {
+            if let Ok(address) = u16::from_str_radix(address, 16) {
+                let mut cheats = interpreter.get_cheats().clone();
+                cheats.remove(address);
+                interpreter.set_cheats(cheats);
+                output.push(format!("Unfroze 0x{:03X}", address));
+            } else {
+                output.push(format!("Invalid address: {:?}", address));
+            }
+        } else if input == "cheats" {
+            output.extend(
+                interpreter
+                    .get_cheats()
+                    .to_string()
+                    .lines()
+                    .map(String::from),
+            );
+        } else if input == "cheats save" {
+            match &self.cheats_path {
+                Some(path) => match write_cheats(interpreter.get_cheats(), path) {
+                    Ok(()) => output.push(format!("Saved cheats to {:?}", path)),
+                    Err(e) => output.push(format!("Cannot save cheats to {:?}: {}", path, e)),
+                },
+                None => output.push("No cheats file, use --cheats".to_string()),
+            }
+        } else if let Some(address) = input.strip_prefix("p ") {
+            if let Ok(address) = u16::from_str_radix(address, 16) {
+                output.push(format!("0x{:04X}", interpreter.cpu.read_byte(address)));
+            } else {
+                output.push(format!("Invalid address: {:?}", address));
+            }
+        } else if self.recording && (input == "s" || input.starts_with("s ") || input == "r") {
+            output
+                .push("Cannot step or reset while recording, advance a frame instead".to_string());
+        } else if input == "s" || input.starts_with("s ") {
+            match input.get(2..).map(|n| n.parse::<u16>()).unwrap_or(Ok(1)) {
+                Ok(n) => {
+                    interpreter.update_keypad(keypad);
+                    for _ in 0..n {
+                        if let Err(e) = interpreter.step() {
+                            output.push(format!("Error: {}", e));
+                            break;
+                        }
+                    }
+                    output.push(format!("Stepped to 0x{:04X}", interpreter.get_pc()));
+                }
+                Err(_) => output.push(format!("Invalid number: {:?}", &input[2..])),
+            }
+        } else if let Some(address) = input.strip_prefix("ba ") {
+            if let Ok(address) = u16::from_str_radix(address, 16) {
+                output.push(format!("Added breakpoint for address 0x{:04X}", address));
+                self.address_breakpoints.insert(address);
+            } else {
+                output.push(format!("Invalid address: {:?}", address));
+            }
+        } else if let Some(op) = input.strip_prefix("bo ") {
+            if let Ok(op) = u16::from_str_radix(op, 16) {
+                output.push(format!("Added breakpoint for opcode 0x{:04X}", op));
+                self.opcode_breakpoints.insert(op);
+            } else {
+                output.push(format!("Invalid opcode: {:?}", op));
+            }
+        } else if input == "clear" {
+            self.address_breakpoints.clear();
+            self.opcode_breakpoints.clear();
+            output.push("cleared breakpoints!".to_string());
+        } else if input == "r" {
+            interpreter.reset();
+            output.push("reset!".to_string());
+        } else {
+            if input == "help" {
+                output.push("Available commands:".to_string());
+            } else {
+                output.push("Invalid command. Available commands:".to_string());
+            }
+            output.extend(HELP.iter().map(|line| line.to_string()));
+        }
+
+        (Action::Stay, output)
+    }
+}
+
+fn candidates(search: &Search) -> Vec<String> {
+    let candidates = search.candidates();
+    let mut lines = vec![format!("{} candidates", candidates.len())];
+    for addr in candidates.iter().take(SEARCH_LINES) {
+        lines.push(format!(
+            "  0x{:03X} = 0x{:02X}",
+            addr,
+            search.get_value(*addr)
+        ));
+    }
+    if candidates.len() > SEARCH_LINES {
+        lines.push("  ...".to_string());
+    }
+
+    lines
+}
+
+fn write_cheats(cheats: &Cheats, path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    std::fs::write(path, cheats.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interpreter() -> chip8::Interpreter {
+        chip8::Interpreter::with_seed(vec![0x12, 0x00], 0).unwrap()
+    }
+
+    #[test]
+    fn cheats_cannot_change_while_recording() {
+        let mut interpreter = interpreter();
+        let mut debugger = Debugger::new(Vec::new(), None, true);
+
+        for input in &["freeze 300 42", "unfreeze 300", "s", "r"] {
+            let (action, output) = debugger.execute(&mut interpreter, [false; 16], input);
+            assert!(action == Action::Stay);
+            assert!(output[0].starts_with("Cannot"), "{}: {:?}", input, output);
+        }
+        assert!(interpreter.get_cheats().is_empty());
+        assert_eq!(interpreter.read_byte(0x300), 0);
+    }
+
+    #[test]
+    fn freeze_and_unfreeze() {
+        let mut interpreter = interpreter();
+        let mut debugger = Debugger::new(Vec::new(), None, false);
+
+        debugger.execute(&mut interpreter, [false; 16], "freeze 300 42");
+        assert_eq!(interpreter.get_cheats().len(), 1);
+        assert_eq!(interpreter.read_byte(0x300), 0x42);
+
+        debugger.execute(&mut interpreter, [false; 16], "unfreeze 300");
+        assert!(interpreter.get_cheats().is_empty());
+    }
+}
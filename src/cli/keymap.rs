@@ -0,0 +1,190 @@
+// Maps the keys of the host keyboard to the 16 keys of the CHIP-8 keypad. Several host keys can
+// be bound to the same CHIP-8 key.
+
+use minifb::{Key, KeyRepeat, Window};
+
+use super::config::Bindings;
+
+// The keys that can be bound, named after the `minifb::Key` variants (e.g. "Key1", "A", "Up",
+// "NumPad5"). The Escape key and the keys used by the frontend (O, P, N, F5, F9, F11 and F12) can
+// be bound too but they keep their original function.
+#[rustfmt::skip]
+const HOST_KEYS: [Key; 106] = [
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7,
+    Key::Key8, Key::Key9, Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+    Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U,
+    Key::V, Key::W, Key::X, Key::Y, Key::Z, Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6,
+    Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12, Key::F13, Key::F14, Key::F15,
+    Key::Down, Key::Left, Key::Right, Key::Up, Key::Apostrophe, Key::Backquote, Key::Backslash,
+    Key::Comma, Key::Equal, Key::LeftBracket, Key::Minus, Key::Period, Key::RightBracket,
+    Key::Semicolon, Key::Slash, Key::Backspace, Key::Delete, Key::End, Key::Enter, Key::Escape,
+    Key::Home, Key::Insert, Key::Menu, Key::PageDown, Key::PageUp, Key::Pause, Key::Space,
+    Key::Tab, Key::NumLock, Key::CapsLock, Key::ScrollLock, Key::LeftShift, Key::RightShift,
+    Key::LeftCtrl, Key::RightCtrl, Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3,
+    Key::NumPad4, Key::NumPad5, Key::NumPad6, Key::NumPad7, Key::NumPad8, Key::NumPad9,
+    Key::NumPadDot, Key::NumPadSlash, Key::NumPadAsterisk, Key::NumPadMinus, Key::NumPadPlus,
+    Key::NumPadEnter, Key::LeftAlt, Key::RightAlt, Key::LeftSuper, Key::RightSuper,
+];
+
+pub struct Keymap {
+    bindings: [Vec<Key>; 16],
+}
+
+impl Default for Keymap {
+    // 1 2 3 C -> 1 2 3 4
+    // 4 5 6 D -> Q W E R
+    // 7 8 9 E -> A S D F
+    // A 0 B F -> Z X C V
+    fn default() -> Self {
+        Keymap {
+            bindings: [
+                vec![Key::X],    // 0
+                vec![Key::Key1], // 1
+                vec![Key::Key2], // 2
+                vec![Key::Key3], // 3
+                vec![Key::Q],    // 4
+                vec![Key::W],    // 5
+                vec![Key::E],    // 6
+                vec![Key::A],    // 7
+                vec![Key::S],    // 8
+                vec![Key::D],    // 9
+                vec![Key::Z],    // A
+                vec![Key::C],    // B
+                vec![Key::Key4], // C
+                vec![Key::R],    // D
+                vec![Key::F],    // E
+                vec![Key::V],    // F
+            ],
+        }
+    }
+}
+
+impl Keymap {
+    // Applies the bindings of a configuration file on top of the current ones. A CHIP-8 key that
+    // is listed in `bindings` loses its previous host keys, and a host key that is listed is
+    // unbound from any other CHIP-8 key.
+    pub fn apply(&mut self, bindings: &Bindings) -> Result<(), String> {
+        for (name, host_names) in bindings {
+            let key = parse_chip8_key(name)?;
+            let host_keys = host_names
+                .names()
+                .iter()
+                .map(|name| parse_host_key(name))
+                .collect::<Result<Vec<Key>, String>>()?;
+
+            for other in self.bindings.iter_mut() {
+                other.retain(|k| !host_keys.contains(k));
+            }
+            self.bindings[key] = host_keys;
+        }
+
+        Ok(())
+    }
+
+    pub fn read(&self, window: &Window) -> [bool; 16] {
+        let mut keypad = [false; 16];
+        for (i, keys) in self.bindings.iter().enumerate() {
+            keypad[i] = keys.iter().any(|k| window.is_key_down(*k));
+        }
+        keypad
+    }
+
+    // Returns the CHIP-8 key bound to a host key, if any.
+    pub fn chip8_key(&self, key: Key) -> Option<usize> {
+        self.bindings.iter().position(|keys| keys.contains(&key))
+    }
+
+    // Returns the CHIP-8 keys for which a host key has just been pressed.
+    pub fn pressed(&self, window: &Window) -> Vec<usize> {
+        (0..16)
+            .filter(|i| {
+                self.bindings[*i]
+                    .iter()
+                    .any(|k| window.is_key_pressed(*k, KeyRepeat::No))
+            })
+            .collect()
+    }
+}
+
+pub fn parse_chip8_key(name: &str) -> Result<usize, String> {
+    match usize::from_str_radix(name, 16) {
+        Ok(key) if key < 16 && name.len() == 1 => Ok(key),
+        _ => Err(format!("invalid CHIP-8 key {:?} (expected 0-F)", name)),
+    }
+}
+
+pub fn parse_host_key(name: &str) -> Result<Key, String> {
+    // Allow "1" as a shorthand for "Key1".
+    let name = if name.len() == 1 && name.chars().all(|c| c.is_ascii_digit()) {
+        format!("Key{}", name)
+    } else {
+        name.to_string()
+    };
+
+    HOST_KEYS
+        .iter()
+        .find(|k| format!("{:?}", k).eq_ignore_ascii_case(&name))
+        .copied()
+        .ok_or(format!("unknown key {:?}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::config::KeyNames;
+
+    fn bindings(pairs: &[(&str, &[&str])]) -> Bindings {
+        pairs
+            .iter()
+            .map(|(key, names)| {
+                let names = names.iter().map(|name| name.to_string()).collect();
+                (key.to_string(), KeyNames::Many(names))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(parse_chip8_key("0"), Ok(0));
+        assert_eq!(parse_chip8_key("a"), Ok(0xA));
+        assert_eq!(parse_chip8_key("F"), Ok(0xF));
+        assert!(parse_chip8_key("10").is_err());
+        assert!(parse_chip8_key("G").is_err());
+
+        assert_eq!(parse_host_key("1"), Ok(Key::Key1));
+        assert_eq!(parse_host_key("key1"), Ok(Key::Key1));
+        assert_eq!(parse_host_key("q"), Ok(Key::Q));
+        assert_eq!(parse_host_key("numpad5"), Ok(Key::NumPad5));
+        assert!(parse_host_key("Foo").is_err());
+    }
+
+    #[test]
+    fn default_layout() {
+        let keymap = Keymap::default();
+
+        assert_eq!(keymap.chip8_key(Key::X), Some(0x0));
+        assert_eq!(keymap.chip8_key(Key::Key4), Some(0xC));
+        assert_eq!(keymap.chip8_key(Key::V), Some(0xF));
+        assert_eq!(keymap.chip8_key(Key::Up), None);
+    }
+
+    #[test]
+    fn apply_bindings() {
+        let mut keymap = Keymap::default();
+        keymap
+            .apply(&bindings(&[("5", &["Z", "Up"]), ("7", &["Left"])]))
+            .unwrap();
+
+        // Several host keys for one CHIP-8 key.
+        assert_eq!(keymap.chip8_key(Key::Z), Some(0x5));
+        assert_eq!(keymap.chip8_key(Key::Up), Some(0x5));
+        // The previous host key of 5 is unbound, and Z is no longer bound to A.
+        assert_eq!(keymap.chip8_key(Key::W), None);
+        assert_eq!(keymap.chip8_key(Key::Left), Some(0x7));
+        assert_eq!(keymap.chip8_key(Key::A), None);
+        // The other keys are unchanged.
+        assert_eq!(keymap.chip8_key(Key::Q), Some(0x4));
+
+        assert!(keymap.apply(&bindings(&[("5", &["Foo"])])).is_err());
+    }
+}
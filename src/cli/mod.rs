@@ -0,0 +1,5 @@
+pub mod audio;
+pub mod config;
+pub mod debugger;
+pub mod keymap;
+pub mod tui;
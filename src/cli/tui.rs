@@ -0,0 +1,499 @@
+// A frontend that runs in a terminal, e.g. over SSH on machines where no window can be opened. The
+// display is drawn with Unicode half-blocks (or braille) and the side panes show the registers
+// and the disassembly around PC. The commands of the debugger are typed after ":", and their
+// output is shown below the display.
+//
+// The display window uses the terminal for its debugger too: `debug()` starts a session when a
+// breakpoint is hit, which ends when the emulation continues.
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use minifb::Key;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use super::debugger::{Action, Debugger};
+use super::keymap::{self, Keymap};
+use libchipolata::chip8;
+use libchipolata::chip8::disassembler::disassemble_at;
+
+const FRAME_DURATION: Duration = Duration::from_micros(16_667);
+
+// Most terminals only report key presses (and repeats), so a key is considered held for a few
+// frames after it has been pressed.
+const KEY_HOLD_FRAMES: u8 = 6;
+const KEY_HELD: u8 = u8::MAX;
+
+const DISASSEMBLY_LINES: usize = 12;
+// The number of lines of the debugger output below the display.
+const OUTPUT_LINES: usize = 12;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    // 1x2 pixels per character, i.e. 64x16 characters.
+    HalfBlock,
+    // 2x4 pixels per character, i.e. 32x8 characters.
+    Braille,
+}
+
+// How the terminal frontend has been left.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    // The emulation continues in the display window (see `debug()`).
+    Continue,
+    Quit,
+}
+
+struct Tui<'a> {
+    interpreter: &'a mut chip8::Interpreter,
+    speed: u8,
+    charset: Charset,
+    keymap: &'a Keymap,
+    debugger: &'a mut Debugger,
+    // Whether the terminal reports key releases.
+    releases: bool,
+    keys: [u8; 16],
+    paused: bool,
+    // A debugging session started from the display window, which ends when the emulation
+    // continues.
+    session: bool,
+    beeping: bool,
+    // The command being typed, after ":".
+    command: Option<String>,
+    output: Vec<String>,
+}
+
+// Runs the emulation in the terminal until the user exits.
+pub fn run(
+    interpreter: &mut chip8::Interpreter,
+    speed: u8,
+    charset: Charset,
+    keymap: &Keymap,
+    debugger: &mut Debugger,
+    paused: bool,
+) -> io::Result<()> {
+    let mut tui = Tui::new(interpreter, speed, charset, keymap, debugger);
+    tui.paused = paused;
+
+    tui.run().map(|_| ())
+}
+
+// Runs a debugging session in the terminal, paused, e.g. when a breakpoint is hit in the display
+// window. It ends when the emulation continues or when the user exits.
+pub fn debug(
+    interpreter: &mut chip8::Interpreter,
+    charset: Charset,
+    keymap: &Keymap,
+    debugger: &mut Debugger,
+    reason: String,
+) -> io::Result<Exit> {
+    let mut tui = Tui::new(interpreter, 0, charset, keymap, debugger);
+    tui.paused = true;
+    tui.session = true;
+    tui.output.push(reason);
+
+    tui.run()
+}
+
+impl<'a> Tui<'a> {
+    fn new(
+        interpreter: &'a mut chip8::Interpreter,
+        speed: u8,
+        charset: Charset,
+        keymap: &'a Keymap,
+        debugger: &'a mut Debugger,
+    ) -> Self {
+        Tui {
+            interpreter,
+            speed,
+            charset,
+            keymap,
+            debugger,
+            releases: false,
+            keys: [0; 16],
+            paused: false,
+            session: false,
+            beeping: false,
+            command: None,
+            output: Vec::new(),
+        }
+    }
+
+    // Sets the terminal up, runs the main loop and restores the terminal, even when the loop
+    // fails.
+    fn run(&mut self) -> io::Result<Exit> {
+        let mut stdout = io::stdout();
+
+        terminal::enable_raw_mode()?;
+        execute!(stdout, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+
+        self.releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if self.releases {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        let result = self.run_loop(&mut stdout);
+
+        if self.releases {
+            execute!(stdout, PopKeyboardEnhancementFlags)?;
+        }
+        execute!(stdout, Show, LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+
+        result
+    }
+
+    fn run_loop(&mut self, stdout: &mut io::Stdout) -> io::Result<Exit> {
+        loop {
+            let deadline = Instant::now() + FRAME_DURATION;
+
+            loop {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                if timeout.is_zero() || !event::poll(timeout)? {
+                    break;
+                }
+
+                match event::read()? {
+                    Event::Key(key) => {
+                        if let Some(exit) = self.handle_key(key) {
+                            return Ok(exit);
+                        }
+                    }
+                    Event::Resize(_, _) => queue!(stdout, Clear(ClearType::All))?,
+                    _ => {}
+                }
+            }
+
+            if !self.paused {
+                if self.session {
+                    return Ok(Exit::Continue);
+                }
+                self.run_frame();
+            }
+
+            let beep = self.interpreter.should_beep() && !self.paused;
+            if beep && !self.beeping {
+                queue!(stdout, Print('\x07'))?;
+            }
+            self.beeping = beep;
+
+            self.draw(stdout)?;
+        }
+    }
+
+    // Returns how to exit the terminal frontend, if the user wants to.
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Exit> {
+        if key.kind == KeyEventKind::Release {
+            if let Some(i) = self.chip8_key(key.code) {
+                self.keys[i] = 0;
+            }
+            return None;
+        }
+
+        if let Some(command) = &mut self.command {
+            match key.code {
+                KeyCode::Esc => self.command = None,
+                KeyCode::Backspace => {
+                    command.pop();
+                }
+                KeyCode::Enter => {
+                    let command = self.command.take().unwrap();
+                    self.output.push(format!(":{}", command));
+                    return self.execute(&command);
+                }
+                KeyCode::Char(c) => command.push(c),
+                _ => {}
+            }
+            return None;
+        }
+
+        match key.code {
+            KeyCode::Esc => return Some(Exit::Quit),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Some(Exit::Quit)
+            }
+            KeyCode::Char(':') => self.command = Some(String::new()),
+            KeyCode::F(2) => return self.execute("r"),
+            KeyCode::F(5) => {
+                self.paused = !self.paused;
+                self.output
+                    .push(if self.paused { "paused" } else { "running" }.to_string());
+            }
+            KeyCode::F(9) => {
+                let pc = self.interpreter.get_pc();
+                if self.debugger.toggle_breakpoint(pc) {
+                    self.output
+                        .push(format!("added breakpoint at 0x{:04X}", pc));
+                } else {
+                    self.output
+                        .push(format!("removed breakpoint at 0x{:04X}", pc));
+                }
+            }
+            KeyCode::F(10) if self.paused => return self.execute("s"),
+            code => {
+                if let Some(i) = self.chip8_key(code) {
+                    self.keys[i] = if self.releases {
+                        KEY_HELD
+                    } else {
+                        KEY_HOLD_FRAMES
+                    };
+                }
+            }
+        }
+
+        None
+    }
+
+    fn chip8_key(&self, code: KeyCode) -> Option<usize> {
+        host_key(code).and_then(|key| self.keymap.chip8_key(key))
+    }
+
+    fn execute(&mut self, command: &str) -> Option<Exit> {
+        let keypad = self.keypad();
+        let (action, output) = self.debugger.execute(self.interpreter, keypad, command);
+        self.output.extend(output);
+
+        match action {
+            Action::Stay => None,
+            Action::Continue => {
+                self.paused = false;
+                None
+            }
+            Action::Quit => Some(Exit::Quit),
+        }
+    }
+
+    fn keypad(&self) -> [bool; 16] {
+        let mut keypad = [false; 16];
+        for (i, pressed) in keypad.iter_mut().enumerate() {
+            *pressed = self.keys[i] > 0;
+        }
+        keypad
+    }
+
+    fn run_frame(&mut self) {
+        self.interpreter.update_keypad(self.keypad());
+
+        for _ in 0..self.speed {
+            if let Err(e) = self.interpreter.step() {
+                // The interpreter stays on the faulty instruction.
+                self.paused = true;
+                self.output.push(format!("Error: {}", e));
+                return;
+            }
+
+            if self.debugger.should_break(self.interpreter) {
+                self.paused = true;
+                self.output.push(format!(
+                    "breakpoint hit at 0x{:04X}",
+                    self.interpreter.get_pc()
+                ));
+                break;
+            }
+        }
+
+        self.interpreter.update_timers();
+
+        for key in self.keys.iter_mut() {
+            if *key != KEY_HELD && *key > 0 {
+                *key -= 1;
+            }
+        }
+    }
+
+    fn draw(&mut self, stdout: &mut io::Stdout) -> io::Result<()> {
+        let display = match self.charset {
+            Charset::HalfBlock => render_half_blocks(&self.interpreter.get_vram()),
+            Charset::Braille => render_braille(&self.interpreter.get_vram()),
+        };
+        let width = display[0].chars().count();
+
+        let mut left = vec![format!("┌{}┐", "─".repeat(width))];
+        left.extend(display.iter().map(|line| format!("│{}│", line)));
+        left.push(format!("└{}┘", "─".repeat(width)));
+
+        let mut right = self.registers_pane();
+        right.push(String::new());
+        right.extend(self.disassembly_pane());
+
+        let rows = left.len().max(right.len());
+        for row in 0..rows {
+            let l = left.get(row).map(String::as_str).unwrap_or("");
+            let r = right.get(row).map(String::as_str).unwrap_or("");
+            queue!(
+                stdout,
+                MoveTo(0, row as u16),
+                Clear(ClearType::CurrentLine),
+                Print(format!("{:<w$}  {}", l, r, w = width + 2))
+            )?;
+        }
+
+        let prompt = match &self.command {
+            Some(command) => format!(":{}_", command),
+            None => "ESC: quit  F5: run/pause  F10: step  F9: breakpoint  F2: reset  :: command"
+                .to_string(),
+        };
+        queue!(
+            stdout,
+            MoveTo(0, rows as u16 + 1),
+            Clear(ClearType::CurrentLine),
+            Print(prompt)
+        )?;
+
+        // The output is cut to the width of the terminal so that it does not wrap.
+        if self.output.len() > OUTPUT_LINES {
+            self.output.drain(..self.output.len() - OUTPUT_LINES);
+        }
+        let columns = terminal::size().map(|(columns, _)| columns).unwrap_or(80) as usize;
+        for row in 0..OUTPUT_LINES {
+            let line = self.output.get(row).map(String::as_str).unwrap_or("");
+            queue!(
+                stdout,
+                MoveTo(0, (rows + 2 + row) as u16),
+                Clear(ClearType::CurrentLine),
+                Print(line.chars().take(columns).collect::<String>())
+            )?;
+        }
+
+        stdout.flush()
+    }
+
+    fn registers_pane(&self) -> Vec<String> {
+        let registers = &self.interpreter.cpu.registers;
+
+        let mut lines = vec![
+            format!(
+                "PC={:04X} I={:04X} SP={:02X}",
+                registers.pc, registers.i, registers.sp
+            ),
+            format!("DT={:02X} ST={:02X}", registers.delay, registers.sound),
+        ];
+        for i in 0..8 {
+            lines.push(format!(
+                "V{:X}={:02X}  V{:X}={:02X}",
+                i,
+                registers.v[i],
+                i + 8,
+                registers.v[i + 8]
+            ));
+        }
+
+        lines
+    }
+
+    // Bytes that the coverage knows to be data (e.g. sprites) are not decoded as instructions.
+    fn disassembly_pane(&mut self) -> Vec<String> {
+        let pc = self.interpreter.get_pc();
+        let mut addr = pc.saturating_sub(6);
+        let mut lines = Vec::new();
+
+        while lines.len() < DISASSEMBLY_LINES && addr < 0x0FFF {
+            let (text, size) = disassemble_at(self.interpreter, addr);
+            lines.push(format!(
+                "{}{}{:04X}: {}",
+                if addr == pc { '>' } else { ' ' },
+                if self.debugger.has_breakpoint(addr) {
+                    '*'
+                } else {
+                    ' '
+                },
+                addr,
+                text
+            ));
+            addr += size;
+        }
+
+        lines
+    }
+}
+
+// Returns the host key of a terminal key, named like the keys of the display window so that the
+// key bindings apply to both frontends. Terminals report characters rather than keys, e.g. the
+// keys of the numeric keypad are reported as digits.
+fn host_key(code: KeyCode) -> Option<Key> {
+    let name = match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char('\'') => "Apostrophe".to_string(),
+        KeyCode::Char('`') => "Backquote".to_string(),
+        KeyCode::Char('\\') => "Backslash".to_string(),
+        KeyCode::Char(',') => "Comma".to_string(),
+        KeyCode::Char('=') => "Equal".to_string(),
+        KeyCode::Char('[') => "LeftBracket".to_string(),
+        KeyCode::Char('-') => "Minus".to_string(),
+        KeyCode::Char('.') => "Period".to_string(),
+        KeyCode::Char(']') => "RightBracket".to_string(),
+        KeyCode::Char(';') => "Semicolon".to_string(),
+        KeyCode::Char('/') => "Slash".to_string(),
+        KeyCode::Char(c) if c.is_ascii_alphanumeric() => c.to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Delete => "Delete".to_string(),
+        KeyCode::Insert => "Insert".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        _ => return None,
+    };
+
+    keymap::parse_host_key(&name).ok()
+}
+
+fn pixel(vram: &[u8], x: usize, y: usize) -> bool {
+    vram[x + y * chip8::WIDTH] == 1
+}
+
+fn render_half_blocks(vram: &[u8]) -> Vec<String> {
+    (0..chip8::HEIGHT / 2)
+        .map(|row| {
+            (0..chip8::WIDTH)
+                .map(
+                    |x| match (pixel(vram, x, row * 2), pixel(vram, x, row * 2 + 1)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    },
+                )
+                .collect()
+        })
+        .collect()
+}
+
+fn render_braille(vram: &[u8]) -> Vec<String> {
+    // Bit of each dot in a braille character, indexed by [y][x].
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+    (0..chip8::HEIGHT / 4)
+        .map(|row| {
+            (0..chip8::WIDTH / 2)
+                .map(|col| {
+                    let mut bits = 0;
+                    for (dy, dots) in DOTS.iter().enumerate() {
+                        for (dx, dot) in dots.iter().enumerate() {
+                            if pixel(vram, col * 2 + dx, row * 4 + dy) {
+                                bits |= dot;
+                            }
+                        }
+                    }
+                    std::char::from_u32(0x2800 + bits).unwrap()
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,288 @@
+// A C API so that chipolata can be embedded in C/C++ programs or loaded with Python's ctypes. It
+// is built into the `cdylib` when the `ffi` feature is enabled, which also generates the header
+// in `include/chipolata.h`:
+//
+//   $ cargo build --release --features ffi
+//
+// Interpreters are opaque handles created with `chipolata_new()` and released with
+// `chipolata_free()`. All the functions accept a NULL handle and do nothing in that case.
+
+use std::os::raw::c_int;
+use std::slice;
+
+use crate::chip8;
+
+// cbindgen only exports literal constants, the assertions below keep them in sync with the
+// library.
+pub const CHIPOLATA_WIDTH: usize = 64;
+pub const CHIPOLATA_HEIGHT: usize = 32;
+// The size of the buffers used by `chipolata_save_state()` and `chipolata_load_state()`.
+pub const CHIPOLATA_STATE_SIZE: usize = 6290;
+
+const _: () = assert!(CHIPOLATA_WIDTH == chip8::WIDTH);
+const _: () = assert!(CHIPOLATA_HEIGHT == chip8::HEIGHT);
+const _: () = assert!(CHIPOLATA_STATE_SIZE == chip8::Snapshot::SIZE);
+
+pub struct ChipolataInterpreter {
+    interpreter: chip8::Interpreter,
+}
+
+#[repr(C)]
+pub struct ChipolataRegisters {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u8,
+    pub delay: u8,
+    pub sound: u8,
+}
+
+unsafe fn rom_from_raw(rom: *const u8, len: usize) -> Option<Vec<u8>> {
+    if rom.is_null() || len == 0 {
+        return None;
+    }
+
+    Some(slice::from_raw_parts(rom, len).to_vec())
+}
+
+/// Creates an interpreter for a ROM, with a random seed. Returns NULL when the ROM is empty or too
+/// large.
+///
+/// # Safety
+///
+/// `rom` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_new(rom: *const u8, len: usize) -> *mut ChipolataInterpreter {
+    match rom_from_raw(rom, len).map(chip8::Interpreter::new) {
+        Some(Ok(interpreter)) => Box::into_raw(Box::new(ChipolataInterpreter { interpreter })),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Creates an interpreter for a ROM with a fixed seed, so that runs can be reproduced.
+///
+/// # Safety
+///
+/// `rom` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_new_with_seed(
+    rom: *const u8,
+    len: usize,
+    seed: u64,
+) -> *mut ChipolataInterpreter {
+    match rom_from_raw(rom, len).map(|rom| chip8::Interpreter::with_seed(rom, seed)) {
+        Some(Ok(interpreter)) => Box::into_raw(Box::new(ChipolataInterpreter { interpreter })),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `handle` must have been returned by `chipolata_new()` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_free(handle: *mut ChipolataInterpreter) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Sets the quirks (see `Quirks::to_bits()`).
+///
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_set_quirks(handle: *mut ChipolataInterpreter, quirks: u8) {
+    if let Some(handle) = handle.as_mut() {
+        handle
+            .interpreter
+            .set_quirks(chip8::Quirks::from_bits(quirks));
+    }
+}
+
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_reset(handle: *mut ChipolataInterpreter) {
+    if let Some(handle) = handle.as_mut() {
+        handle.interpreter.reset();
+    }
+}
+
+/// Sets the state of the keypad: bit N is set when key N is pressed.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_update_keypad(handle: *mut ChipolataInterpreter, keys: u16) {
+    if let Some(handle) = handle.as_mut() {
+        let mut keypad = [false; 16];
+        for (i, pressed) in keypad.iter_mut().enumerate() {
+            *pressed = keys & (1 << i) != 0;
+        }
+        handle.interpreter.update_keypad(keypad);
+    }
+}
+
+/// Executes a single instruction. Returns 0 when the ROM fails (e.g. on an unsupported opcode),
+/// in which case the interpreter stays on the faulty instruction.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_step(handle: *mut ChipolataInterpreter) -> c_int {
+    match handle.as_mut() {
+        Some(handle) => handle.interpreter.step().is_ok() as c_int,
+        None => 0,
+    }
+}
+
+/// Decrements the delay and sound timers, which should happen 60 times per second.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_update_timers(handle: *mut ChipolataInterpreter) {
+    if let Some(handle) = handle.as_mut() {
+        handle.interpreter.update_timers();
+    }
+}
+
+/// Runs a frame, i.e. `speed` instructions then the timers. Returns 1 when the display should be
+/// redrawn, 0 otherwise and -1 when the ROM fails.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_run_frame(
+    handle: *mut ChipolataInterpreter,
+    speed: u8,
+) -> c_int {
+    match handle.as_mut() {
+        Some(handle) => match handle.interpreter.run_frame(speed) {
+            Ok(redraw) => redraw as c_int,
+            Err(_) => -1,
+        },
+        None => 0,
+    }
+}
+
+/// Returns 1 when the last instruction changed the display.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_should_redraw(handle: *const ChipolataInterpreter) -> c_int {
+    match handle.as_ref() {
+        Some(handle) => handle.interpreter.should_redraw() as c_int,
+        None => 0,
+    }
+}
+
+/// Returns 1 while the sound timer is non-zero.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_should_beep(handle: *const ChipolataInterpreter) -> c_int {
+    match handle.as_ref() {
+        Some(handle) => handle.interpreter.should_beep() as c_int,
+        None => 0,
+    }
+}
+
+/// Returns the display: `CHIPOLATA_WIDTH * CHIPOLATA_HEIGHT` bytes, row by row, with 1 for the
+/// pixels that are on. The pointer is valid until the handle is freed.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_get_vram(handle: *const ChipolataInterpreter) -> *const u8 {
+    match handle.as_ref() {
+        Some(handle) => handle.interpreter.get_vram_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// Copies the registers to `registers`. Returns 0 when `handle` or `registers` is NULL.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL and `registers` must be a valid pointer or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_get_registers(
+    handle: *const ChipolataInterpreter,
+    registers: *mut ChipolataRegisters,
+) -> c_int {
+    let (handle, registers) = match (handle.as_ref(), registers.as_mut()) {
+        (Some(handle), Some(registers)) => (handle, registers),
+        _ => return 0,
+    };
+
+    let interpreter = &handle.interpreter;
+    registers
+        .v
+        .copy_from_slice(slice::from_raw_parts(interpreter.get_v_ptr(), 16));
+    registers.i = interpreter.get_i();
+    registers.pc = interpreter.get_pc();
+    registers.sp = interpreter.get_sp();
+    registers.delay = interpreter.get_delay();
+    registers.sound = interpreter.get_sound();
+
+    1
+}
+
+/// Writes the state of the interpreter to `buffer`, which must hold at least
+/// `CHIPOLATA_STATE_SIZE` bytes. Returns 0 when the buffer is too small.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL and `buffer` must point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_save_state(
+    handle: *const ChipolataInterpreter,
+    buffer: *mut u8,
+    len: usize,
+) -> c_int {
+    match handle.as_ref() {
+        Some(handle) if !buffer.is_null() && len >= CHIPOLATA_STATE_SIZE => {
+            let bytes = handle.interpreter.save_state().to_bytes();
+            slice::from_raw_parts_mut(buffer, bytes.len()).copy_from_slice(&bytes);
+            1
+        }
+        _ => 0,
+    }
+}
+
+/// Restores a state written by `chipolata_save_state()`. Returns 0 when the state is invalid.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle or NULL and `buffer` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chipolata_load_state(
+    handle: *mut ChipolataInterpreter,
+    buffer: *const u8,
+    len: usize,
+) -> c_int {
+    let handle = match handle.as_mut() {
+        Some(handle) if !buffer.is_null() => handle,
+        _ => return 0,
+    };
+
+    match chip8::Snapshot::from_bytes(slice::from_raw_parts(buffer, len)) {
+        Ok(snapshot) => {
+            handle.interpreter.load_state(&snapshot);
+            1
+        }
+        Err(_) => 0,
+    }
+}
@@ -1,4 +1,10 @@
 pub mod chip8;
 
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "libretro")]
+pub mod libretro;
+
 #[cfg(target_arch = "wasm32")]
 pub mod wasm_bindings;
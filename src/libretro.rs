@@ -0,0 +1,491 @@
+// A libretro core (https://docs.libretro.com/development/cores/developing-cores/) so that
+// chipolata can run in RetroArch and other libretro frontends. It is built into the `cdylib` when
+// the `libretro` feature is enabled:
+//
+//   $ cargo build --release --features libretro
+//
+// The frontend drives the interpreter one frame at a time with `retro_run()`, receives the
+// display as a 64x32 XRGB8888 image and the sound as 16-bit stereo samples, and maps its joypad
+// to the CHIP-8 keypad (see `JOYPAD_KEYS`).
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_uint, c_void};
+use std::slice;
+use std::sync::Mutex;
+
+use crate::chip8;
+use crate::chip8::audio::Beeper;
+
+const RETRO_API_VERSION: c_uint = 1;
+
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS: c_uint = 11;
+const RETRO_PIXEL_FORMAT_XRGB8888: c_uint = 1;
+
+const RETRO_DEVICE_JOYPAD: c_uint = 1;
+const RETRO_REGION_NTSC: c_uint = 0;
+const RETRO_MEMORY_SYSTEM_RAM: c_uint = 2;
+
+const RETRO_DEVICE_ID_JOYPAD_B: c_uint = 0;
+const RETRO_DEVICE_ID_JOYPAD_Y: c_uint = 1;
+const RETRO_DEVICE_ID_JOYPAD_SELECT: c_uint = 2;
+const RETRO_DEVICE_ID_JOYPAD_START: c_uint = 3;
+const RETRO_DEVICE_ID_JOYPAD_UP: c_uint = 4;
+const RETRO_DEVICE_ID_JOYPAD_DOWN: c_uint = 5;
+const RETRO_DEVICE_ID_JOYPAD_LEFT: c_uint = 6;
+const RETRO_DEVICE_ID_JOYPAD_RIGHT: c_uint = 7;
+const RETRO_DEVICE_ID_JOYPAD_A: c_uint = 8;
+const RETRO_DEVICE_ID_JOYPAD_X: c_uint = 9;
+const RETRO_DEVICE_ID_JOYPAD_L: c_uint = 10;
+const RETRO_DEVICE_ID_JOYPAD_R: c_uint = 11;
+const RETRO_DEVICE_ID_JOYPAD_L2: c_uint = 12;
+const RETRO_DEVICE_ID_JOYPAD_R2: c_uint = 13;
+const RETRO_DEVICE_ID_JOYPAD_L3: c_uint = 14;
+const RETRO_DEVICE_ID_JOYPAD_R3: c_uint = 15;
+
+// Most games use 2, 4, 6 and 8 as directions and 5 as the action key, so the D-pad and A are
+// mapped to them. The other keys follow the remaining buttons.
+const JOYPAD_KEYS: [(c_uint, usize, &[u8]); 16] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, 0x2, b"CHIP-8 2 (Up)\0"),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8, b"CHIP-8 8 (Down)\0"),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x4, b"CHIP-8 4 (Left)\0"),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x6, b"CHIP-8 6 (Right)\0"),
+    (RETRO_DEVICE_ID_JOYPAD_A, 0x5, b"CHIP-8 5\0"),
+    (RETRO_DEVICE_ID_JOYPAD_B, 0x0, b"CHIP-8 0\0"),
+    (RETRO_DEVICE_ID_JOYPAD_X, 0x1, b"CHIP-8 1\0"),
+    (RETRO_DEVICE_ID_JOYPAD_Y, 0x3, b"CHIP-8 3\0"),
+    (RETRO_DEVICE_ID_JOYPAD_L, 0x7, b"CHIP-8 7\0"),
+    (RETRO_DEVICE_ID_JOYPAD_R, 0x9, b"CHIP-8 9\0"),
+    (RETRO_DEVICE_ID_JOYPAD_L2, 0xA, b"CHIP-8 A\0"),
+    (RETRO_DEVICE_ID_JOYPAD_R2, 0xB, b"CHIP-8 B\0"),
+    (RETRO_DEVICE_ID_JOYPAD_L3, 0xC, b"CHIP-8 C\0"),
+    (RETRO_DEVICE_ID_JOYPAD_R3, 0xD, b"CHIP-8 D\0"),
+    (RETRO_DEVICE_ID_JOYPAD_SELECT, 0xE, b"CHIP-8 E\0"),
+    (RETRO_DEVICE_ID_JOYPAD_START, 0xF, b"CHIP-8 F\0"),
+];
+
+// The number of instructions per frame when the ROM is not in the database.
+const DEFAULT_SPEED: u8 = 9;
+const FRAMES_PER_SECOND: f64 = 60.0;
+
+#[repr(C)]
+pub struct RetroSystemInfo {
+    pub library_name: *const c_char,
+    pub library_version: *const c_char,
+    pub valid_extensions: *const c_char,
+    pub need_fullpath: bool,
+    pub block_extract: bool,
+}
+
+#[repr(C)]
+pub struct RetroGameGeometry {
+    pub base_width: c_uint,
+    pub base_height: c_uint,
+    pub max_width: c_uint,
+    pub max_height: c_uint,
+    pub aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct RetroSystemTiming {
+    pub fps: f64,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct RetroSystemAvInfo {
+    pub geometry: RetroGameGeometry,
+    pub timing: RetroSystemTiming,
+}
+
+#[repr(C)]
+pub struct RetroGameInfo {
+    pub path: *const c_char,
+    pub data: *const c_void,
+    pub size: usize,
+    pub meta: *const c_char,
+}
+
+#[repr(C)]
+struct RetroInputDescriptor {
+    port: c_uint,
+    device: c_uint,
+    index: c_uint,
+    id: c_uint,
+    description: *const c_char,
+}
+
+pub type RetroEnvironment = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+pub type RetroVideoRefresh =
+    unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+pub type RetroAudioSample = unsafe extern "C" fn(left: i16, right: i16);
+pub type RetroAudioSampleBatch = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+pub type RetroInputPoll = unsafe extern "C" fn();
+pub type RetroInputState =
+    unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+#[derive(Default)]
+struct Callbacks {
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_sample_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+}
+
+struct Core {
+    interpreter: chip8::Interpreter,
+    speed: u8,
+    palette: chip8::Palette,
+    framebuffer: Vec<u32>,
+    beeper: Beeper,
+    samples: Vec<f32>,
+    // Interleaved stereo samples, as expected by the frontend.
+    audio: Vec<i16>,
+    // Whether the frontend has a pointer to the RAM (see `retro_get_memory_data()`), which it may
+    // write between two frames (e.g. for cheats).
+    ram_exposed: bool,
+}
+
+// libretro frontends call the core from a single thread but the state still has to live in
+// statics, hence the mutexes.
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+impl Core {
+    fn new(rom: Vec<u8>) -> Result<Self, chip8::InterpreterError> {
+        let info = chip8::database::lookup(&rom);
+        let mut interpreter = chip8::Interpreter::new(rom)?;
+        let mut speed = DEFAULT_SPEED;
+        let mut palette = chip8::Palette::default();
+        if let Some(info) = info {
+            interpreter.set_quirks(info.quirks);
+            speed = info.tickrate;
+            if let Some((foreground, background)) = info.colors {
+                palette = chip8::Palette::new(background, foreground);
+            }
+        }
+
+        Ok(Core {
+            interpreter,
+            speed,
+            palette,
+            framebuffer: vec![0; chip8::WIDTH * chip8::HEIGHT],
+            beeper: Beeper::default(),
+            samples: Vec::new(),
+            audio: Vec::new(),
+            ram_exposed: false,
+        })
+    }
+
+    fn run_frame(&mut self, callbacks: &Callbacks) {
+        let mut keypad = [false; 16];
+        if let Some(input_poll) = callbacks.input_poll {
+            unsafe { input_poll() };
+        }
+        if let Some(input_state) = callbacks.input_state {
+            for (id, key, _) in JOYPAD_KEYS.iter() {
+                keypad[*key] = unsafe { input_state(0, RETRO_DEVICE_JOYPAD, 0, *id) } != 0;
+            }
+        }
+
+        self.interpreter.update_keypad(keypad);
+        if self.ram_exposed {
+            self.interpreter.invalidate_ram();
+        }
+        // When the ROM fails, the interpreter stays on the faulty instruction and the last frame
+        // keeps being displayed.
+        let _ = self.interpreter.run_frame(self.speed);
+
+        // The frontend expects a frame even when nothing changed.
+        if let Some(video_refresh) = callbacks.video_refresh {
+            self.palette
+                .render(&self.interpreter.get_vram(), &mut self.framebuffer);
+            unsafe {
+                video_refresh(
+                    self.framebuffer.as_ptr() as *const c_void,
+                    chip8::WIDTH as c_uint,
+                    chip8::HEIGHT as c_uint,
+                    chip8::WIDTH * 4,
+                )
+            };
+        }
+
+        self.samples.clear();
+        self.beeper.update(&self.interpreter);
+        self.beeper.render_frame(&mut self.samples);
+        if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+            self.audio.clear();
+            for sample in self.samples.iter() {
+                let value = (sample * i16::MAX as f32) as i16;
+                self.audio.push(value);
+                self.audio.push(value);
+            }
+            unsafe { audio_sample_batch(self.audio.as_ptr(), self.samples.len()) };
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: RetroEnvironment) {
+    CALLBACKS.lock().unwrap().environment = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: RetroVideoRefresh) {
+    CALLBACKS.lock().unwrap().video_refresh = Some(callback);
+}
+
+// Samples are sent in batches, see `retro_set_audio_sample_batch()`.
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: RetroAudioSample) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: RetroAudioSampleBatch) {
+    CALLBACKS.lock().unwrap().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: RetroInputPoll) {
+    CALLBACKS.lock().unwrap().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: RetroInputState) {
+    CALLBACKS.lock().unwrap().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *CORE.lock().unwrap() = None;
+}
+
+/// # Safety
+///
+/// `info` must point to a valid `retro_system_info` struct.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    *info = RetroSystemInfo {
+        library_name: b"chipolata\0".as_ptr() as *const c_char,
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: b"ch8|c8\0".as_ptr() as *const c_char,
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+///
+/// `info` must point to a valid `retro_system_av_info` struct.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    let sample_rate = CORE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|core| core.beeper.get_sample_rate())
+        .unwrap_or(chip8::audio::DEFAULT_SAMPLE_RATE);
+
+    *info = RetroSystemAvInfo {
+        geometry: RetroGameGeometry {
+            base_width: chip8::WIDTH as c_uint,
+            base_height: chip8::HEIGHT as c_uint,
+            max_width: chip8::WIDTH as c_uint,
+            max_height: chip8::HEIGHT as c_uint,
+            aspect_ratio: 2.0,
+        },
+        timing: RetroSystemTiming {
+            fps: FRAMES_PER_SECOND,
+            sample_rate: sample_rate as f64,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = CORE.lock().unwrap().as_mut() {
+        core.interpreter.reset();
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let callbacks = CALLBACKS.lock().unwrap();
+    if let Some(core) = CORE.lock().unwrap().as_mut() {
+        core.run_frame(&callbacks);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    chip8::Snapshot::SIZE
+}
+
+/// # Safety
+///
+/// `data` must point to `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let core = CORE.lock().unwrap();
+    match core.as_ref() {
+        Some(core) if size >= chip8::Snapshot::SIZE => {
+            let bytes = core.interpreter.save_state().to_bytes();
+            slice::from_raw_parts_mut(data as *mut u8, bytes.len()).copy_from_slice(&bytes);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// # Safety
+///
+/// `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let mut core = CORE.lock().unwrap();
+    let core = match core.as_mut() {
+        Some(core) => core,
+        None => return false,
+    };
+
+    let bytes = slice::from_raw_parts(data as *const u8, size);
+    match chip8::Snapshot::from_bytes(&bytes[..size.min(chip8::Snapshot::SIZE)]) {
+        Ok(snapshot) => {
+            core.interpreter.load_state(&snapshot);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+///
+/// `game` must point to a valid `retro_game_info` struct, whose `data` points to `size` bytes or
+/// whose `path` is a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    if game.is_null() {
+        return false;
+    }
+    let game = &*game;
+
+    let rom = if !game.data.is_null() {
+        slice::from_raw_parts(game.data as *const u8, game.size).to_vec()
+    } else if !game.path.is_null() {
+        match CStr::from_ptr(game.path)
+            .to_str()
+            .ok()
+            .and_then(|path| std::fs::read(path).ok())
+        {
+            Some(rom) => rom,
+            None => return false,
+        }
+    } else {
+        return false;
+    };
+
+    if rom.is_empty() {
+        return false;
+    }
+    let core = match Core::new(rom) {
+        Ok(core) => core,
+        Err(_) => return false,
+    };
+
+    if let Some(environment) = CALLBACKS.lock().unwrap().environment {
+        let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+        if !environment(
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            &mut format as *mut c_uint as *mut c_void,
+        ) {
+            return false;
+        }
+
+        let mut descriptors: Vec<RetroInputDescriptor> = JOYPAD_KEYS
+            .iter()
+            .map(|(id, _, description)| RetroInputDescriptor {
+                port: 0,
+                device: RETRO_DEVICE_JOYPAD,
+                index: 0,
+                id: *id,
+                description: description.as_ptr() as *const c_char,
+            })
+            .collect();
+        descriptors.push(RetroInputDescriptor {
+            port: 0,
+            device: 0,
+            index: 0,
+            id: 0,
+            description: std::ptr::null(),
+        });
+        environment(
+            RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+            descriptors.as_mut_ptr() as *mut c_void,
+        );
+    }
+
+    *CORE.lock().unwrap() = Some(core);
+
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *CORE.lock().unwrap() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    match CORE.lock().unwrap().as_mut() {
+        Some(core) if id == RETRO_MEMORY_SYSTEM_RAM => {
+            core.ram_exposed = true;
+            core.interpreter.get_ram_mut_ptr() as *mut c_void
+        }
+        _ => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    match CORE.lock().unwrap().as_ref() {
+        Some(_) if id == RETRO_MEMORY_SYSTEM_RAM => 0x1000,
+        _ => 0,
+    }
+}
@@ -2,21 +2,182 @@ use std::convert::TryInto;
 use wasm_bindgen::prelude::*;
 
 use crate::chip8;
+use crate::chip8::audio::Beeper;
+use crate::chip8::cheats::Cheats;
+use crate::chip8::database::{self, RomInfo};
+
+// Returns the names of the palette presets, separated by commas.
+#[wasm_bindgen]
+pub fn palette_names() -> String {
+    chip8::PALETTES
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<&str>>()
+        .join(",")
+}
 
 #[wasm_bindgen]
 pub struct JsInterpreter {
     interpreter: chip8::Interpreter,
+    rom_info: Option<&'static RomInfo>,
+    rom_hash: String,
+    palette: chip8::Palette,
+    phosphor: Option<chip8::Phosphor>,
+    gif_recorder: Option<chip8::capture::GifRecorder<Vec<u8>>>,
+    beeper: Beeper,
+    // RGBA pixels of the display, rendered with the palette.
+    framebuffer: Vec<u8>,
 }
 
 #[wasm_bindgen]
 impl JsInterpreter {
     #[wasm_bindgen(constructor)]
-    pub fn new(rom: Vec<u8>) -> Self {
-        JsInterpreter {
-            interpreter: chip8::Interpreter::new(rom),
+    pub fn new(rom: Vec<u8>) -> Result<JsInterpreter, JsValue> {
+        // Known ROMs get their recommended quirks automatically.
+        let rom_info = database::lookup(&rom);
+        let rom_hash = chip8::rom_hash_hex(&rom);
+        let mut interpreter =
+            chip8::Interpreter::new(rom).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        // The disassembly uses the coverage to tell code from data.
+        interpreter.set_coverage(true);
+        let mut palette = chip8::Palette::default();
+        if let Some(info) = rom_info {
+            interpreter.set_quirks(info.quirks);
+            if let Some((foreground, background)) = info.colors {
+                palette = chip8::Palette::new(background, foreground);
+            }
+        }
+
+        Ok(JsInterpreter {
+            interpreter,
+            rom_info,
+            rom_hash,
+            palette,
+            phosphor: None,
+            gif_recorder: None,
+            beeper: Beeper::default(),
+            framebuffer: vec![0; chip8::WIDTH * chip8::HEIGHT * 4],
+        })
+    }
+
+    // Returns `false` when there is no palette with this name.
+    pub fn set_palette(&mut self, name: &str) -> bool {
+        match chip8::Palette::preset(name) {
+            Some(palette) => {
+                self.palette = palette;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_phosphor(&mut self, enabled: bool) {
+        self.phosphor = if enabled {
+            let mut phosphor = chip8::Phosphor::default();
+            phosphor.update(&self.interpreter.get_vram());
+            Some(phosphor)
+        } else {
+            None
+        };
+    }
+
+    // Must be called once per frame. Returns `true` when the display should be redrawn because
+    // some pixels are fading out.
+    pub fn update_phosphor(&mut self) -> bool {
+        match &mut self.phosphor {
+            Some(phosphor) => {
+                let fading = phosphor.is_fading();
+                phosphor.update(&self.interpreter.get_vram());
+                fading
+            }
+            None => false,
+        }
+    }
+
+    pub fn update_framebuffer(&mut self) {
+        match &self.phosphor {
+            Some(phosphor) => phosphor.render_rgba(&self.palette, &mut self.framebuffer),
+            None => self
+                .palette
+                .render_rgba(&self.interpreter.get_vram(), &mut self.framebuffer),
+        }
+    }
+
+    pub fn get_framebuffer_ptr(&self) -> *const u8 {
+        self.framebuffer.as_ptr()
+    }
+
+    // Returns the display as a PNG image.
+    pub fn screenshot_png(&self, scale: u8) -> Result<Vec<u8>, JsValue> {
+        chip8::capture::png(&self.interpreter.get_vram(), &self.palette, scale)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    pub fn start_gif_recording(&mut self, scale: u8) -> Result<(), JsValue> {
+        let recorder = chip8::capture::GifRecorder::new(Vec::new(), &self.palette, scale)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.gif_recorder = Some(recorder);
+
+        Ok(())
+    }
+
+    // Must be called once per frame while recording.
+    pub fn add_gif_frame(&mut self) -> Result<(), JsValue> {
+        match &mut self.gif_recorder {
+            Some(recorder) => recorder
+                .add_frame(&self.interpreter.get_vram())
+                .map_err(|e| JsValue::from_str(&e.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    // Returns the recorded GIF.
+    pub fn stop_gif_recording(&mut self) -> Result<Vec<u8>, JsValue> {
+        match self.gif_recorder.take() {
+            Some(recorder) => recorder
+                .finish()
+                .map_err(|e| JsValue::from_str(&e.to_string())),
+            None => Err(JsValue::from_str("not recording")),
         }
     }
 
+    pub fn set_audio_sample_rate(&mut self, sample_rate: u32) {
+        self.beeper = Beeper::new(sample_rate);
+    }
+
+    // Returns the next `len` samples of the sound, which is muted when `active` is `false`.
+    pub fn fill_audio(&mut self, len: usize, active: bool) -> Vec<f32> {
+        let mut samples = vec![0.0; len];
+        self.beeper.update(&self.interpreter);
+        self.beeper
+            .set_active(active && self.interpreter.should_beep());
+        self.beeper.fill(&mut samples);
+
+        samples
+    }
+
+    pub fn get_rom_title(&self) -> Option<String> {
+        self.rom_info.map(|info| info.title.to_string())
+    }
+
+    pub fn get_rom_tickrate(&self) -> Option<u8> {
+        self.rom_info.map(|info| info.tickrate)
+    }
+
+    // Returns the SHA-1 of the ROM, e.g. to save its cheats.
+    pub fn get_rom_hash(&self) -> String {
+        self.rom_hash.clone()
+    }
+
+    // Replaces the cheats with a cheat list (see `chip8::cheats`). Throws when the list is
+    // invalid.
+    pub fn set_cheats(&mut self, text: &str) -> Result<(), JsValue> {
+        let cheats = Cheats::parse(text).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.interpreter.set_cheats(cheats);
+
+        Ok(())
+    }
+
     pub fn update_keypad(&mut self, keypad: Vec<u8>) {
         let keypad = keypad
             .iter()
@@ -27,8 +188,11 @@ impl JsInterpreter {
         self.interpreter.update_keypad(keypad);
     }
 
-    pub fn step(&mut self) {
-        self.interpreter.step();
+    // Throws when the ROM fails, e.g. on an unsupported opcode.
+    pub fn step(&mut self) -> Result<(), JsValue> {
+        self.interpreter
+            .step()
+            .map_err(|e| JsValue::from_str(&e.to_string()))
     }
 
     pub fn should_redraw(&self) -> bool {
@@ -55,6 +219,26 @@ impl JsInterpreter {
         self.interpreter.get_pc()
     }
 
+    // Returns a pointer to the coverage tags of each byte of RAM (see `chip8::coverage`).
+    pub fn get_coverage_ptr(&self) -> *const u8 {
+        self.interpreter
+            .get_coverage()
+            .map_or(std::ptr::null(), |coverage| coverage.as_slice().as_ptr())
+    }
+
+    // Returns a number that changes when some bytes get new coverage tags.
+    pub fn get_coverage_changes(&self) -> u32 {
+        self.interpreter
+            .get_coverage()
+            .map_or(0, |coverage| coverage.get_changes() as u32)
+    }
+
+    pub fn coverage_json(&self) -> Option<String> {
+        self.interpreter
+            .get_coverage()
+            .map(|coverage| coverage.to_json())
+    }
+
     pub fn get_v_ptr(&self) -> *const u8 {
         self.interpreter.get_v_ptr()
     }
@@ -0,0 +1,97 @@
+// The listing of any ROM must re-assemble into the exact same ROM.
+
+use libchipolata::chip8::analysis::{Analysis, Edge};
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::symbols::Symbols;
+use proptest::prelude::*;
+
+const SPACE_INVADERS: &[u8] = include_bytes!("../docs/space-invaders.ch8");
+
+fn assert_round_trip(rom: &[u8], symbols: &Symbols) {
+    let listing = Analysis::new(rom).listing(symbols);
+    let assembled = assemble(&listing).unwrap_or_else(|e| panic!("{}\n{}", e, listing));
+
+    assert_eq!(assembled, rom, "{}", listing);
+}
+
+#[test]
+fn space_invaders() {
+    let analysis = Analysis::new(SPACE_INVADERS);
+
+    // The title at the start of the ROM is skipped by the first jump.
+    assert!(analysis.is_code(0x200));
+    assert!(analysis.is_data(0x202));
+    assert!(analysis.is_code(0x225));
+
+    assert_round_trip(SPACE_INVADERS, &Symbols::new());
+}
+
+#[test]
+fn symbols() {
+    let symbols = Symbols::parse("0x225 init\n0x391 draw_score\n").unwrap();
+    let listing = Analysis::new(SPACE_INVADERS).listing(&symbols);
+
+    assert!(listing.contains("\ninit:\n"));
+    assert!(listing.contains("CALL draw_score"));
+    assert_round_trip(SPACE_INVADERS, &symbols);
+}
+
+#[test]
+fn control_flow_graph() {
+    let rom = assemble(
+        "
+        start:
+            SE V0, 01
+            CALL sub
+            JP start
+        sub:
+            LD I, sprite
+            DRW V0, V1, 1
+            RET
+        sprite:
+            DB 80
+        ",
+    )
+    .unwrap();
+    let analysis = Analysis::new(&rom);
+
+    let blocks: Vec<_> = analysis.blocks().map(|b| (b.start, b.end)).collect();
+    assert_eq!(
+        blocks,
+        vec![
+            (0x200, 0x202),
+            (0x202, 0x204),
+            (0x204, 0x206),
+            (0x206, 0x20C)
+        ]
+    );
+    assert_eq!(
+        analysis.get_block(0x200).unwrap().successors,
+        vec![(Edge::Next, 0x202), (Edge::Skip, 0x204)]
+    );
+    assert_eq!(
+        analysis.get_block(0x202).unwrap().successors,
+        vec![(Edge::Call, 0x206), (Edge::Next, 0x204)]
+    );
+    assert_eq!(
+        analysis.get_block(0x204).unwrap().successors,
+        vec![(Edge::Jump, 0x200)]
+    );
+    assert!(analysis.get_block(0x206).unwrap().successors.is_empty());
+    assert!(analysis.is_data(0x20C));
+
+    let listing = analysis.listing(&Symbols::new());
+    assert!(listing.contains("CALL sub_206"));
+    assert!(listing.contains("LD I, data_20C"));
+
+    let dot = analysis.to_dot(&Symbols::new());
+    assert!(dot.contains("b200 -> b204 [label=\"skip\"];"));
+    assert!(dot.contains("b202 -> b206 [label=\"call\", style=dashed];"));
+}
+
+proptest! {
+    #[test]
+    fn random_roms(rom in prop::collection::vec(any::<u8>(), 0..512)) {
+        assert_round_trip(&rom, &Symbols::new());
+    }
+}
@@ -0,0 +1,165 @@
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::audio::{write_wav, Beeper};
+use libchipolata::chip8::{Interpreter, DEFAULT_PITCH};
+
+// At this rate, a 500 Hz square wave lasts exactly 16 samples and the 2 ms ramps too, so that
+// there are no rounding errors.
+const SAMPLE_RATE: u32 = 8000;
+
+fn beeper() -> Beeper {
+    let mut beeper = Beeper::new(SAMPLE_RATE);
+    beeper.set_frequency(500.0);
+    beeper.set_volume(1.0);
+    beeper
+}
+
+fn fill(beeper: &mut Beeper, len: usize) -> Vec<f32> {
+    let mut samples = vec![0.0; len];
+    beeper.fill(&mut samples);
+    samples
+}
+
+#[test]
+fn square_wave() {
+    let mut beeper = beeper();
+    assert_eq!(fill(&mut beeper, 16), vec![0.0; 16]);
+
+    // The volume ramps up over 16 samples, then the wave is high for 8 samples and low for 8.
+    beeper.set_active(true);
+    let samples = fill(&mut beeper, 64);
+    for (n, sample) in samples.iter().enumerate() {
+        let amplitude = ((n + 1) as f32 / 16.0).min(1.0);
+        let expected = if n % 16 < 8 { amplitude } else { -amplitude };
+        assert_eq!(*sample, expected, "sample {}", n);
+    }
+
+    // It ramps down over 16 samples when the beeper is turned off.
+    beeper.set_active(false);
+    let samples = fill(&mut beeper, 32);
+    for (n, sample) in samples.iter().enumerate() {
+        let amplitude = (1.0 - (n + 1) as f32 / 16.0).max(0.0);
+        let expected = if n % 16 < 8 { amplitude } else { -amplitude };
+        assert_eq!(*sample, expected, "sample {}", n);
+    }
+
+    // The next beep starts at the beginning of a period.
+    beeper.set_active(true);
+    let samples = fill(&mut beeper, 9);
+    assert!(samples[..8].iter().all(|sample| *sample > 0.0));
+    assert!(samples[8] < 0.0);
+}
+
+#[test]
+fn pattern_rate() {
+    let mut beeper = beeper();
+    for (pitch, rate) in [
+        (DEFAULT_PITCH, 4000.0),
+        (112, 8000.0),
+        (16, 2000.0),
+        (0, 1587.4),
+    ] {
+        beeper.set_pitch(pitch);
+        assert!(
+            (beeper.get_pattern_rate() - rate).abs() < 0.1,
+            "pitch {}",
+            pitch
+        );
+    }
+}
+
+#[test]
+fn pattern() {
+    // The bits are played from the most significant bit of the first byte: 1, 0, 1, 0, 0, ...,
+    // 1 (the last bit).
+    let mut pattern = [0; 16];
+    pattern[0] = 0b1010_0000;
+    pattern[15] = 0b0000_0001;
+
+    let mut beeper = beeper();
+    beeper.set_pattern(Some(pattern));
+    beeper.set_active(true);
+
+    // At 4000 bits per second, each bit lasts 2 samples and the pattern loops every 256 samples.
+    let samples = fill(&mut beeper, 512);
+    let bits: Vec<bool> = samples.iter().map(|sample| *sample > 0.0).collect();
+    let mut expected = vec![false; 256];
+    expected[..6].copy_from_slice(&[true, true, false, false, true, true]);
+    expected[254..].copy_from_slice(&[true, true]);
+    assert_eq!(bits[..256], expected[..]);
+    assert_eq!(bits[256..], expected[..]);
+
+    // Twice as fast, each bit lasts 1 sample.
+    beeper.set_pitch(112);
+    let samples = fill(&mut beeper, 4);
+    let bits: Vec<bool> = samples.iter().map(|sample| *sample > 0.0).collect();
+    assert_eq!(bits, vec![true, false, true, false]);
+}
+
+#[test]
+fn interpreter_pattern() {
+    // Loads a pattern and sets the pitch to 0x70 (112), i.e. 8000 bits per second.
+    let rom = assemble(
+        "
+        LD I, pattern
+        LD AUDIO, [I]
+        LD V0, 70
+        LD PITCH, V0
+        LD ST, V0
+    loop:
+        JP loop
+    pattern:
+        DB FF, 00, FF, 00, FF, 00, FF, 00, FF, 00, FF, 00, FF, 00, FF, 0F
+",
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::with_seed(rom, 0).unwrap();
+    interpreter.run_frame(9).unwrap();
+
+    let mut beeper = beeper();
+    beeper.update(&interpreter);
+    assert!(beeper.is_active());
+    assert!((beeper.get_pattern_rate() - 8000.0).abs() < 0.1);
+    let samples = fill(&mut beeper, 4);
+    assert!(samples.iter().all(|sample| *sample > 0.0));
+}
+
+#[test]
+fn frames() {
+    let mut beeper = beeper();
+    let mut samples = Vec::new();
+    let mut lengths = Vec::new();
+    for _ in 0..60 {
+        let start = samples.len();
+        beeper.render_frame(&mut samples);
+        lengths.push(samples.len() - start);
+    }
+
+    // 8000 / 60 = 133.33 samples per frame: every third frame has an extra sample.
+    assert_eq!(lengths[..3], [133, 133, 134]);
+    assert_eq!(samples.len(), SAMPLE_RATE as usize);
+}
+
+#[test]
+fn wav() {
+    let mut bytes = Vec::new();
+    write_wav(&mut bytes, SAMPLE_RATE, &[0.0, 1.0, -1.0, 2.0]).unwrap();
+
+    assert_eq!(bytes.len(), 44 + 8);
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(bytes[4..8], (36u32 + 8).to_le_bytes());
+    assert_eq!(&bytes[8..16], b"WAVEfmt ");
+    assert_eq!(bytes[16..20], 16u32.to_le_bytes());
+    // PCM, mono, sample rate, bytes per second, bytes per sample, bits per sample.
+    assert_eq!(bytes[20..24], [1, 0, 1, 0]);
+    assert_eq!(bytes[24..28], SAMPLE_RATE.to_le_bytes());
+    assert_eq!(bytes[28..32], (SAMPLE_RATE * 2).to_le_bytes());
+    assert_eq!(bytes[32..36], [2, 0, 16, 0]);
+    assert_eq!(&bytes[36..40], b"data");
+    assert_eq!(bytes[40..44], 8u32.to_le_bytes());
+
+    let samples: Vec<i16> = bytes[44..]
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    assert_eq!(samples, vec![0, i16::MAX, -i16::MAX, i16::MAX]);
+}
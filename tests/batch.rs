@@ -0,0 +1,97 @@
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::batch::Batch;
+use libchipolata::chip8::Interpreter;
+
+const SPACE_INVADERS: &[u8] = include_bytes!("../docs/space-invaders.ch8");
+const SPEED: u8 = 9;
+
+// Draws random sprites where the pressed keys tell it to.
+const PROGRAM: &str = "
+    loop:
+        RND V0, 3F
+        RND V1, 1F
+        LD V2, 05
+        SKNP V2
+        LD F, V0
+        DRW V0, V1, 5
+        JP loop
+";
+
+fn input(index: usize, frame: usize) -> u16 {
+    let hash = (index * 31 + frame / 10).wrapping_mul(2654435761);
+    1 << (hash % 16)
+}
+
+fn interpreters() -> Vec<Interpreter> {
+    let roms = [SPACE_INVADERS.to_vec(), assemble(PROGRAM).unwrap()];
+    (0..8)
+        .map(|i| Interpreter::with_seed(roms[i % 2].clone(), i as u64).unwrap())
+        .collect()
+}
+
+fn states(interpreters: &[Interpreter]) -> Vec<Vec<u8>> {
+    interpreters
+        .iter()
+        .map(|interpreter| interpreter.save_state().to_bytes())
+        .collect()
+}
+
+#[test]
+fn same_as_sequential_runs() {
+    let mut expected = interpreters();
+    for (index, interpreter) in expected.iter_mut().enumerate() {
+        for frame in 0..300 {
+            let keys = input(index, frame);
+            let mut keypad = [false; 16];
+            for (key, pressed) in keypad.iter_mut().enumerate() {
+                *pressed = keys & (1 << key) != 0;
+            }
+            interpreter.update_keypad(keypad);
+            interpreter.run_frame(SPEED).unwrap();
+        }
+    }
+
+    for threads in [1, 3, 8] {
+        let mut batch = Batch::new(interpreters());
+        batch.set_threads(threads);
+        batch.set_block_cache(threads == 3);
+        let stats = batch.run(300, SPEED, input);
+
+        assert_eq!(stats.frames, 8 * 300);
+        assert_eq!(stats.instructions, 8 * 300 * SPEED as u64);
+        assert_eq!(stats.halted, 0);
+        assert_eq!(states(batch.get_interpreters()), states(&expected));
+    }
+}
+
+#[test]
+fn halted_interpreters() {
+    // Fails at the 6th instruction, i.e. in the middle of the second frame.
+    let failing = assemble(
+        "
+        ADD V0, 01
+        ADD V0, 01
+        ADD V0, 01
+        ADD V0, 01
+        ADD V0, 01
+        DB FF, FF
+",
+    )
+    .unwrap();
+    let mut interpreters = interpreters();
+    interpreters.push(Interpreter::with_seed(failing, 0).unwrap());
+    let mut batch = Batch::new(interpreters);
+    batch.set_threads(4);
+
+    let stats = batch.run(10, 3, input);
+    assert_eq!(stats.halted, 1);
+    assert_eq!(stats.frames, 8 * 10 + 1);
+    assert_eq!(stats.instructions, 8 * 10 * 3 + 5);
+    assert!(batch.get_errors()[8].is_some());
+    assert_eq!(batch.get_interpreters()[8].get_pc(), 0x20A);
+
+    // A halted interpreter is not run anymore.
+    let stats = batch.run(10, 3, input);
+    assert_eq!(stats.halted, 1);
+    assert_eq!(stats.instructions, 8 * 10 * 3);
+}
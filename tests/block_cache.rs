@@ -0,0 +1,165 @@
+// The block cache must not change the behavior of the interpreter in any way: these tests run the
+// same ROMs with and without it and compare the whole state after each frame.
+
+use proptest::prelude::*;
+use std::fs;
+
+use libchipolata::chip8::{Interpreter, Quirks};
+
+fn interpreters(rom: &[u8], quirks: Quirks) -> (Interpreter, Interpreter) {
+    let mut interpreter = Interpreter::with_seed(rom.to_vec(), 42).unwrap();
+    interpreter.set_quirks(quirks);
+    let mut cached = interpreter.clone();
+    cached.set_block_cache(true);
+    assert!(cached.is_block_cache_enabled());
+
+    (interpreter, cached)
+}
+
+fn keypad(keys: u16) -> [bool; 16] {
+    let mut keypad = [false; 16];
+    for (key, pressed) in keypad.iter_mut().enumerate() {
+        *pressed = keys & (1 << key) != 0;
+    }
+    keypad
+}
+
+// Runs a frame on both interpreters and returns whether they are still running.
+fn run_frame(
+    interpreter: &mut Interpreter,
+    cached: &mut Interpreter,
+    keys: u16,
+    speed: u8,
+) -> Result<bool, TestCaseError> {
+    interpreter.update_keypad(keypad(keys));
+    cached.update_keypad(keypad(keys));
+
+    let expected = interpreter.run_frame(speed);
+    prop_assert_eq!(cached.run_frame(speed), expected);
+    prop_assert_eq!(cached.should_redraw(), interpreter.should_redraw());
+    prop_assert!(cached.save_state().to_bytes() == interpreter.save_state().to_bytes());
+
+    Ok(expected.is_ok())
+}
+
+fn v(interpreter: &Interpreter) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(interpreter.get_v_ptr(), 16) }
+}
+
+#[test]
+fn space_invaders() {
+    let rom = fs::read("docs/space-invaders.ch8").unwrap();
+    let (mut interpreter, mut cached) = interpreters(&rom, Quirks::default());
+
+    let mut snapshot = None;
+    for frame in 0..3000u32 {
+        // Move and shoot from time to time.
+        let keys = match frame % 120 {
+            0..=29 => 1 << 4,
+            30..=59 => 1 << 6,
+            60..=64 => 1 << 5,
+            _ => 0,
+        };
+        assert!(run_frame(&mut interpreter, &mut cached, keys, 9).unwrap());
+
+        // Go back in time once, which replaces the whole RAM.
+        if frame == 1000 {
+            snapshot = Some(interpreter.save_state());
+        } else if frame == 2000 {
+            let snapshot = snapshot.take().unwrap();
+            interpreter.load_state(&snapshot);
+            cached.load_state(&snapshot);
+        }
+    }
+}
+
+#[test]
+fn self_modifying_code() {
+    let rom = [
+        0xA2, 0x12, // 0x200: I = 0x212
+        0x22, 0x10, // 0x202: call 0x210
+        0x60, 0x62, // 0x204: V0 = 0x62
+        0x61, 0x99, // 0x206: V1 = 0x99
+        0xF1, 0x55, // 0x208: [I] = V0, V1, i.e. 0x212 becomes "V2 = 0x99"
+        0x22, 0x10, // 0x20A: call 0x210
+        0x12, 0x0C, // 0x20C: loop
+        0x00, 0x00, // 0x20E
+        0x63, 0x01, // 0x210: V3 = 0x01
+        0x62, 0x00, // 0x212: V2 = 0x00
+        0x00, 0xEE, // 0x214: return
+    ];
+    let (mut interpreter, mut cached) = interpreters(&rom, Quirks::default());
+
+    assert!(run_frame(&mut interpreter, &mut cached, 0, 20).unwrap());
+    assert_eq!(v(&cached)[2], 0x99);
+    assert_eq!(v(&cached)[3], 0x01);
+}
+
+#[test]
+fn ram_written_through_pointer() {
+    let rom = [
+        0x60, 0x01, // 0x200: V0 = 0x01
+        0x12, 0x00, // 0x202: loop
+    ];
+    let (_, mut cached) = interpreters(&rom, Quirks::default());
+    cached.run_frame(10).unwrap();
+    assert_eq!(v(&cached)[0], 0x01);
+
+    // The RAM is written through the pointer, e.g. by a libretro frontend, and the cache has to
+    // be invalidated after each write.
+    let ram = cached.get_ram_mut_ptr();
+    unsafe { *ram.add(0x201) = 0x02 };
+    cached.run_frame(10).unwrap();
+    assert_eq!(v(&cached)[0], 0x02);
+
+    unsafe { *ram.add(0x201) = 0x03 };
+    cached.invalidate_ram();
+    cached.run_frame(10).unwrap();
+    assert_eq!(v(&cached)[0], 0x03);
+}
+
+// An address in the first 256 bytes of the ROM.
+fn addr() -> impl Strategy<Value = u16> {
+    (0u16..0x80).prop_map(|n| 0x200 + n * 2)
+}
+
+// Valid instructions, which jump and point I within the program so that it loops, calls
+// subroutines and writes to its own code.
+fn instruction() -> impl Strategy<Value = u16> {
+    let x = || (0u16..16).prop_map(|x| x << 8);
+    prop_oneof![
+        addr().prop_map(|addr| 0x1000 | addr),
+        addr().prop_map(|addr| 0x2000 | addr),
+        addr().prop_map(|addr| 0xA000 | addr),
+        Just(0x00E0),
+        Just(0x00EE),
+        (0x3u16..=0x9, any::<u16>()).prop_map(|(op, operands)| (op << 12) | (operands & 0x0FF7)),
+        (0xCu16..=0xD, any::<u16>()).prop_map(|(op, operands)| (op << 12) | (operands & 0x0FFF)),
+        (x(), prop::sample::select(vec![0x9E, 0xA1])).prop_map(|(x, nn)| 0xE000 | x | nn),
+        (
+            x(),
+            prop::sample::select(vec![0x07, 0x0A, 0x15, 0x18, 0x1E, 0x29, 0x33, 0x55, 0x65])
+        )
+            .prop_map(|(x, nn)| 0xF000 | x | nn),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(256))]
+
+    #[test]
+    fn random_programs(
+        program in prop::collection::vec(instruction(), 1..128),
+        quirks in 0u8..32,
+        keys in prop::collection::vec(any::<u16>(), 60),
+        speed in 1u8..50,
+    ) {
+        let rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+        let (mut interpreter, mut cached) = interpreters(&rom, Quirks::from_bits(quirks));
+        for keys in keys {
+            if !run_frame(&mut interpreter, &mut cached, keys, speed)? {
+                break;
+            }
+        }
+    }
+}
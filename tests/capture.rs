@@ -0,0 +1,76 @@
+use libchipolata::chip8::capture::{png, GifRecorder};
+use libchipolata::chip8::{Palette, HEIGHT, WIDTH};
+
+fn vram(pixels: &[(usize, u8)]) -> Vec<u8> {
+    let mut vram = vec![0; WIDTH * HEIGHT];
+    for (i, pixel) in pixels {
+        vram[*i] = *pixel;
+    }
+    vram
+}
+
+#[test]
+fn screenshot() {
+    let palette = Palette::preset("octo").unwrap();
+    let vram = vram(&[(0, 1), (WIDTH + 1, 2), (WIDTH * HEIGHT - 1, 3)]);
+    let bytes = png(&vram, &palette, 2).unwrap();
+
+    let decoder = png::Decoder::new(&bytes[..]);
+    let mut reader = decoder.read_info().unwrap();
+    let info = reader.info();
+    assert_eq!(
+        (info.width, info.height),
+        (WIDTH as u32 * 2, HEIGHT as u32 * 2)
+    );
+    assert_eq!(info.color_type, png::ColorType::Indexed);
+    assert_eq!(
+        info.palette.as_deref(),
+        Some(&[0x99, 0x66, 0x00, 0xFF, 0xCC, 0x00, 0xFF, 0x66, 0x00, 0x66, 0x22, 0x00][..])
+    );
+
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut pixels).unwrap();
+    // Each pixel of `vram` is a 2x2 square.
+    let at = |x: usize, y: usize| pixels[y * WIDTH * 2 + x];
+    assert_eq!([at(0, 0), at(1, 0), at(0, 1), at(1, 1)], [1, 1, 1, 1]);
+    assert_eq!([at(2, 2), at(3, 3), at(2, 1), at(4, 2)], [2, 2, 0, 0]);
+    assert_eq!(at(WIDTH * 2 - 1, HEIGHT * 2 - 1), 3);
+    assert_eq!(pixels.iter().filter(|p| **p != 0).count(), 12);
+}
+
+#[test]
+fn animation() {
+    let palette = Palette::default();
+    let frames = [vram(&[(0, 1)]), vram(&[(1, 1)]), vram(&[])];
+    let mut recorder = GifRecorder::new(Vec::new(), &palette, 1).unwrap();
+    assert!(recorder.is_empty());
+    // Consecutive identical frames are merged: 2 frames, then 1 frame, then 3 frames.
+    for n in [0, 0, 1, 2, 2, 2] {
+        recorder.add_frame(&frames[n]).unwrap();
+    }
+    assert_eq!(recorder.len(), 6);
+    let bytes = recorder.finish().unwrap();
+
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(&bytes[..]).unwrap();
+    assert_eq!(
+        (decoder.width() as usize, decoder.height() as usize),
+        (WIDTH, HEIGHT)
+    );
+
+    let mut decoded = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        decoded.push((frame.delay, frame.buffer.to_vec()));
+    }
+    // 6 frames at 60 Hz last 10 centiseconds: 2 frames end at 3cs, 3 frames at 5cs and 6 frames
+    // at 10cs.
+    assert_eq!(
+        decoded,
+        vec![
+            (3, frames[0].clone()),
+            (2, frames[1].clone()),
+            (5, frames[2].clone())
+        ]
+    );
+}
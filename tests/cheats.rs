@@ -0,0 +1,109 @@
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::cheats::{Cheats, CheatsError, Comparison, Search};
+use libchipolata::chip8::Interpreter;
+
+// The number of lives, at 0x300, starts at 3 and decreases at each frame.
+const LIVES: &str = "
+        LD V0, 03
+        LD I, 300
+        LD [I], V0
+    loop:
+        LD I, 300
+        LD V0, [I]
+        ADD V0, FF
+        LD [I], V0
+        JP loop
+";
+
+fn interpreter() -> Interpreter {
+    Interpreter::with_seed(assemble(LIVES).unwrap(), 0).unwrap()
+}
+
+#[test]
+fn parse() {
+    let cheats = Cheats::parse("# Lives\n300:09\n\n 2f0 : ff\n").unwrap();
+
+    assert_eq!(cheats.len(), 2);
+    assert_eq!(cheats.get(0x300), Some(0x09));
+    assert_eq!(cheats.get(0x2F0), Some(0xFF));
+    assert_eq!(cheats.to_string(), "2F0:FF\n300:09\n");
+    assert_eq!(Cheats::parse(&cheats.to_string()).unwrap(), cheats);
+
+    assert_eq!(Cheats::parse("300"), Err(CheatsError::InvalidLine(1)));
+    assert_eq!(Cheats::parse("\n300:100"), Err(CheatsError::InvalidLine(2)));
+    assert_eq!(Cheats::parse("1000:00"), Err(CheatsError::InvalidLine(1)));
+}
+
+#[test]
+fn search() {
+    let mut interpreter = interpreter();
+    interpreter.run_frame(5).unwrap();
+
+    let mut search = Search::new(&interpreter);
+    assert_eq!(search.candidates().len(), 0x1000);
+
+    interpreter.run_frame(5).unwrap();
+    search.filter(&interpreter, Comparison::Decreased);
+    assert!(search.candidates().contains(&0x300));
+
+    search.filter(&interpreter, Comparison::Unchanged);
+    interpreter.run_frame(5).unwrap();
+    search.filter(&interpreter, Comparison::Changed);
+    let lives = interpreter.read_byte(0x300);
+    search.filter(&interpreter, Comparison::Equal(lives));
+    assert_eq!(search.candidates(), &[0x300]);
+    assert_eq!(search.get_value(0x300), lives);
+
+    interpreter.run_frame(5).unwrap();
+    search.filter(&interpreter, Comparison::Increased);
+    assert!(search.candidates().is_empty());
+}
+
+#[test]
+fn freeze() {
+    let mut interpreter = interpreter();
+    let mut cheats = Cheats::new();
+    cheats.insert(0x300, 0x09);
+    interpreter.set_cheats(cheats);
+    assert_eq!(interpreter.read_byte(0x300), 0x09);
+
+    // The value changes during a frame but it is written back at the end of each frame.
+    for _ in 0..10 {
+        interpreter.run_frame(5).unwrap();
+        assert_eq!(interpreter.read_byte(0x300), 0x09);
+    }
+
+    // The cheats are kept on reset and when a state is loaded.
+    let snapshot = interpreter.save_state();
+    interpreter.reset();
+    interpreter.run_frame(5).unwrap();
+    assert_eq!(interpreter.read_byte(0x300), 0x09);
+    interpreter.load_state(&snapshot);
+    interpreter.run_frame(5).unwrap();
+    assert_eq!(interpreter.read_byte(0x300), 0x09);
+
+    interpreter.set_cheats(Cheats::new());
+    interpreter.run_frame(5).unwrap();
+    assert_ne!(interpreter.read_byte(0x300), 0x09);
+}
+
+#[test]
+fn block_cache() {
+    let mut cached = interpreter();
+    cached.set_block_cache(true);
+    let mut uncached = interpreter();
+
+    let mut cheats = Cheats::new();
+    cheats.insert(0x300, 0x09);
+    cached.set_cheats(cheats.clone());
+    uncached.set_cheats(cheats);
+
+    for _ in 0..10 {
+        cached.run_frame(7).unwrap();
+        uncached.run_frame(7).unwrap();
+        assert_eq!(
+            cached.save_state().to_bytes(),
+            uncached.save_state().to_bytes()
+        );
+    }
+}
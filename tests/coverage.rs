@@ -0,0 +1,78 @@
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::coverage::{DRAWN, EXECUTED, READ, WRITTEN};
+use libchipolata::chip8::sprites::Sprite;
+use libchipolata::chip8::Interpreter;
+
+// Draws a sprite, reads 2 bytes of a table and writes 3 BCD digits after the code.
+const PROGRAM: &str = "
+        LD I, sprite
+        DRW V0, V0, 3
+        LD I, table
+        LD V1, [I]
+        LD I, scratch
+        LD B, V1
+    loop:
+        JP loop
+    sprite:
+        DB 18, 3C, FF
+    table:
+        DB 01, 02, 03
+    scratch:
+        DB 00, 00, 00
+";
+
+fn coverage() -> Interpreter {
+    let rom = assemble(PROGRAM).unwrap();
+    let mut interpreter = Interpreter::with_seed(rom, 0).unwrap();
+    interpreter.set_coverage(true);
+    for _ in 0..7 {
+        interpreter.step().unwrap();
+    }
+    interpreter
+}
+
+#[test]
+fn tags() {
+    let interpreter = coverage();
+    let coverage = interpreter.get_coverage().unwrap();
+
+    assert_eq!(coverage.ranges(EXECUTED), vec![(0x200, 0x20E)]);
+    assert_eq!(coverage.ranges(DRAWN), vec![(0x20E, 0x211)]);
+    assert_eq!(coverage.ranges(READ), vec![(0x211, 0x213)]);
+    assert_eq!(coverage.ranges(WRITTEN), vec![(0x214, 0x217)]);
+    assert_eq!(coverage.get(0x20C), EXECUTED);
+    assert_eq!(coverage.get(0x213), 0);
+    // 14 bytes executed, 3 drawn, 2 read and 3 written.
+    assert_eq!(coverage.get_changes(), 22);
+
+    // Running the loop again does not change anything.
+    let mut interpreter = interpreter.clone();
+    interpreter.step().unwrap();
+    assert_eq!(interpreter.get_coverage().unwrap().get_changes(), 22);
+}
+
+#[test]
+fn code_and_data() {
+    let interpreter = coverage();
+    let coverage = interpreter.get_coverage().unwrap();
+
+    assert!(!coverage.is_data(0x200));
+    assert!(coverage.is_data(0x20E));
+    assert!(coverage.is_data(0x211));
+    // Never accessed.
+    assert!(!coverage.is_data(0x213));
+    // Written only.
+    assert!(!coverage.is_data(0x214));
+
+    assert_eq!(coverage.sprites(), vec![Sprite::new(0x20E, 3)]);
+}
+
+#[test]
+fn json() {
+    let interpreter = coverage();
+
+    assert_eq!(
+        interpreter.get_coverage().unwrap().to_json(),
+        "{\n  \"executed\": [[512, 526]],\n  \"read\": [[529, 531]],\n  \"written\": [[532, 535]],\n  \"drawn\": [[526, 529]]\n}\n"
+    );
+}
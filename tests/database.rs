@@ -0,0 +1,28 @@
+use libchipolata::chip8::database::{self, Platform};
+use libchipolata::chip8::{rom_hash_hex, Quirks};
+
+const SPACE_INVADERS: &[u8] = include_bytes!("../docs/space-invaders.ch8");
+
+#[test]
+fn known_rom() {
+    assert_eq!(
+        rom_hash_hex(SPACE_INVADERS),
+        "5c28a5f85289c9d859f95fd5eadbdcb1c30bb08b"
+    );
+
+    let info = database::lookup(SPACE_INVADERS).unwrap();
+    assert_eq!(info.title, "Space Invaders (David Winter)");
+    assert_eq!(info.platform, Platform::Chip8);
+    assert_eq!(info.quirks, Quirks::default());
+    assert_eq!(info.tickrate, 9);
+    assert!(info.keys.contains(&(0x5, "fire / start")));
+}
+
+#[test]
+fn unknown_rom() {
+    let mut rom = SPACE_INVADERS.to_vec();
+    rom[0x100] ^= 1;
+
+    assert!(database::lookup(&rom).is_none());
+    assert!(database::lookup(&[]).is_none());
+}
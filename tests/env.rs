@@ -0,0 +1,175 @@
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::env::{self, Env, Game, RamValue};
+use libchipolata::chip8::{Interpreter, Quirks};
+
+const SPACE_INVADERS: &[u8] = include_bytes!("../docs/space-invaders.ch8");
+
+// Scores a point per frame (at 5 instructions per frame) and loses its 3 lives at once at the
+// 11th frame.
+const PROGRAM: &str = "
+        LD V2, 03
+    loop:
+        ADD V1, 01
+        LD I, score
+        LD B, V1
+        SNE V1, 0A
+        LD V2, 00
+        JP loop
+    score:
+        DB 00, 00, 00
+";
+const SCORE: u16 = 0x20E;
+const SPEED: u8 = 5;
+
+fn game() -> Game {
+    Game {
+        score: Some(RamValue::Digits {
+            addr: SCORE,
+            len: 3,
+        }),
+        lives: Some(RamValue::Register(2)),
+        ..Game::default()
+    }
+}
+
+#[test]
+fn ram_values() {
+    let interpreter = Interpreter::with_seed(vec![0x12, 0x34, 0x56, 0x07, 0xAB], 0).unwrap();
+    let read = |value: RamValue| value.read(&interpreter);
+
+    assert_eq!(read(RamValue::Byte(0x200)), 0x12);
+    assert_eq!(read(RamValue::Register(0)), 0);
+    assert_eq!(
+        read(RamValue::BigEndian {
+            addr: 0x200,
+            len: 2
+        }),
+        0x1234
+    );
+    assert_eq!(
+        read(RamValue::BigEndian {
+            addr: 0x200,
+            len: 8
+        }),
+        0x12345607
+    );
+    assert_eq!(
+        read(RamValue::Digits {
+            addr: 0x202,
+            len: 2
+        }),
+        67
+    );
+    assert_eq!(
+        read(RamValue::PackedBcd {
+            addr: 0x200,
+            len: 3
+        }),
+        123456
+    );
+}
+
+#[test]
+fn episode() {
+    let rom = assemble(PROGRAM).unwrap();
+    let mut env = Env::new(rom, game()).unwrap();
+    env.set_speed(SPEED);
+    env.set_frameskip(4);
+    assert_eq!(env.action_count(), 17);
+
+    let observation = env.reset(0);
+    assert!(observation.iter().all(|pixel| *pixel == 0));
+    assert_eq!(
+        (env.get_frame(), env.get_score(), env.is_done()),
+        (0, 0, false)
+    );
+
+    // Each step holds the action for 4 frames, and the rewards of the frames add up.
+    assert_eq!(env.step(0), (env.get_interpreter().get_vram(), 4.0, false));
+    assert_eq!(env.step(0).1, 4.0);
+    assert_eq!((env.get_frame(), env.get_score()), (8, 8));
+
+    // The episode is over when the lives drop to 0, in the middle of the step.
+    let (_, reward, done) = env.step(0);
+    assert_eq!((reward, done), (3.0, true));
+    assert_eq!(env.get_frame(), 11);
+    assert!(env.get_error().is_none());
+
+    let (_, reward, done) = env.step(0);
+    assert_eq!((reward, done), (0.0, true));
+    assert_eq!(env.get_frame(), 11);
+
+    env.reset(1);
+    assert_eq!(
+        (env.get_frame(), env.get_score(), env.is_done()),
+        (0, 0, false)
+    );
+    assert_eq!(env.step(16).1, 4.0);
+}
+
+#[test]
+fn truncation_and_errors() {
+    let rom = assemble(PROGRAM).unwrap();
+    let mut env = Env::new(
+        rom,
+        Game {
+            max_frames: Some(6),
+            ..game()
+        },
+    )
+    .unwrap();
+    env.set_speed(SPEED);
+    env.set_frameskip(4);
+    assert!(!env.step(0).2);
+    assert!(env.step(0).2);
+    assert_eq!(env.get_frame(), 6);
+
+    // A crash ends the episode.
+    let mut env = Env::new(vec![0xFF, 0xFF], Game::default()).unwrap();
+    assert!(env.step(0).2);
+    assert_eq!(env.get_frame(), 0);
+    assert!(env.get_error().is_some());
+}
+
+#[test]
+fn space_invaders() {
+    assert!(env::lookup(&[0x12, 0x00]).is_none());
+    let game = env::lookup(SPACE_INVADERS).unwrap();
+    assert_eq!(game.actions.len(), 3);
+
+    // Moves left and right while firing: a point per alien, until the aliens land.
+    let play = |env: &mut Env| {
+        let mut rewards = Vec::new();
+        for n in 0..2000 {
+            let (_, reward, done) = env.step((n / 7) % 3);
+            rewards.push(reward);
+            if done {
+                break;
+            }
+        }
+        rewards
+    };
+
+    let mut env = Env::new(SPACE_INVADERS.to_vec(), game).unwrap();
+    let rewards = play(&mut env);
+    assert!(env.is_done());
+    assert!(env.get_error().is_none());
+    assert!(rewards
+        .iter()
+        .all(|reward| *reward == 0.0 || *reward == 1.0));
+    assert!(rewards.iter().sum::<f32>() > 0.0);
+
+    // The episode can be replayed exactly.
+    env.reset(0);
+    assert_eq!(play(&mut env), rewards);
+}
+
+// The ROMs that are not in the database run with the default quirks.
+#[test]
+fn unknown_rom() {
+    let mut rom = SPACE_INVADERS.to_vec();
+    rom[0x100] ^= 1;
+
+    let env = Env::new(rom, Game::default()).unwrap();
+    assert_eq!(env.get_interpreter().get_quirks(), Quirks::default());
+}
@@ -0,0 +1,157 @@
+// Drives the C API from Rust, the way a C program would use `include/chipolata.h`.
+#![cfg(feature = "ffi")]
+
+use std::ptr;
+
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::MAX_ROM_SIZE;
+use libchipolata::ffi::*;
+
+// Draws the sprite of the digit 0 at (V0, 0) and moves it to the right while key 1 is pressed.
+const PROGRAM: &str = "
+    loop:
+        LD F, V2
+        DRW V0, V2, 5
+        LD V1, 01
+        SKNP V1
+        ADD V0, 01
+        DRW V0, V2, 5
+        JP loop
+";
+
+fn registers(handle: *const ChipolataInterpreter) -> ChipolataRegisters {
+    let mut registers = ChipolataRegisters {
+        v: [0; 16],
+        i: 0,
+        pc: 0,
+        sp: 0,
+        delay: 0,
+        sound: 0,
+    };
+    assert_eq!(
+        unsafe { chipolata_get_registers(handle, &mut registers) },
+        1
+    );
+    registers
+}
+
+#[test]
+fn run() {
+    let rom = assemble(PROGRAM).unwrap();
+    unsafe {
+        let handle = chipolata_new_with_seed(rom.as_ptr(), rom.len(), 0);
+        assert!(!handle.is_null());
+
+        assert_eq!(chipolata_step(handle), 1);
+        assert_eq!(registers(handle).pc, 0x202);
+
+        assert_eq!(chipolata_run_frame(handle, 1), 1);
+        assert_eq!(chipolata_should_redraw(handle), 1);
+        let vram = std::slice::from_raw_parts(
+            chipolata_get_vram(handle),
+            CHIPOLATA_WIDTH * CHIPOLATA_HEIGHT,
+        );
+        assert_eq!(vram[..5], [1, 1, 1, 1, 0]);
+
+        // The loop has 7 instructions, so the sprite moves once per frame.
+        chipolata_update_keypad(handle, 1 << 1);
+        for _ in 0..10 {
+            assert!(chipolata_run_frame(handle, 7) >= 0);
+        }
+        assert_eq!(registers(handle).v[0], 10);
+
+        chipolata_reset(handle);
+        assert_eq!(registers(handle).pc, 0x200);
+        assert_eq!(registers(handle).v[0], 0);
+
+        chipolata_free(handle);
+    }
+}
+
+#[test]
+fn state() {
+    let rom = assemble(PROGRAM).unwrap();
+    unsafe {
+        let handle = chipolata_new_with_seed(rom.as_ptr(), rom.len(), 0);
+        chipolata_update_keypad(handle, 1 << 1);
+        chipolata_run_frame(handle, 9);
+
+        let mut state = vec![0; CHIPOLATA_STATE_SIZE];
+        assert_eq!(
+            chipolata_save_state(handle, state.as_mut_ptr(), state.len()),
+            1
+        );
+        let saved = registers(handle);
+
+        chipolata_run_frame(handle, 9);
+        assert_ne!(registers(handle).v[0], saved.v[0]);
+
+        assert_eq!(chipolata_load_state(handle, state.as_ptr(), state.len()), 1);
+        let restored = registers(handle);
+        assert_eq!(restored.v, saved.v);
+        assert_eq!((restored.i, restored.pc), (saved.i, saved.pc));
+
+        // The state can be restored in another interpreter of the same ROM.
+        let other = chipolata_new(rom.as_ptr(), rom.len());
+        assert_eq!(chipolata_load_state(other, state.as_ptr(), state.len()), 1);
+        assert_eq!(registers(other).v, saved.v);
+
+        chipolata_free(other);
+        chipolata_free(handle);
+    }
+}
+
+#[test]
+fn errors() {
+    let rom = assemble(PROGRAM).unwrap();
+    let too_large = vec![0; MAX_ROM_SIZE + 1];
+    unsafe {
+        assert!(chipolata_new(ptr::null(), 10).is_null());
+        assert!(chipolata_new(rom.as_ptr(), 0).is_null());
+        assert!(chipolata_new(too_large.as_ptr(), too_large.len()).is_null());
+
+        // A NULL handle is ignored.
+        let null = ptr::null_mut();
+        chipolata_reset(null);
+        chipolata_update_keypad(null, 0xFFFF);
+        assert_eq!(chipolata_step(null), 0);
+        assert_eq!(chipolata_run_frame(null, 9), 0);
+        assert!(chipolata_get_vram(null).is_null());
+        let mut state = vec![0; CHIPOLATA_STATE_SIZE];
+        assert_eq!(
+            chipolata_save_state(null, state.as_mut_ptr(), state.len()),
+            0
+        );
+        chipolata_free(null);
+
+        let handle = chipolata_new(rom.as_ptr(), rom.len());
+        assert_eq!(chipolata_get_registers(handle, ptr::null_mut()), 0);
+
+        // The buffers of the states must be large enough.
+        assert_eq!(chipolata_save_state(handle, ptr::null_mut(), 0), 0);
+        assert_eq!(
+            chipolata_save_state(handle, state.as_mut_ptr(), CHIPOLATA_STATE_SIZE - 1),
+            0
+        );
+        assert_eq!(
+            chipolata_save_state(handle, state.as_mut_ptr(), state.len()),
+            1
+        );
+        assert_eq!(chipolata_load_state(handle, ptr::null(), state.len()), 0);
+        assert_eq!(
+            chipolata_load_state(handle, state.as_ptr(), CHIPOLATA_STATE_SIZE - 1),
+            0
+        );
+        assert_eq!(chipolata_load_state(handle, state.as_ptr(), state.len()), 1);
+
+        // The faulty instruction is not skipped.
+        let invalid = [0xFF, 0xFF];
+        let failing = chipolata_new(invalid.as_ptr(), invalid.len());
+        assert_eq!(chipolata_step(failing), 0);
+        assert_eq!(chipolata_run_frame(failing, 9), -1);
+        assert_eq!(registers(failing).pc, 0x200);
+
+        chipolata_free(failing);
+        chipolata_free(handle);
+    }
+}
@@ -0,0 +1,113 @@
+// Runs the fuzzing harness on random inputs for a couple of seconds (or the number of seconds in
+// `CHIPOLATA_FUZZ_SECONDS`), plus the inputs that used to panic the interpreter. Longer fuzzing
+// sessions use cargo-fuzz, see `fuzz/`.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::env;
+use std::time::{Duration, Instant};
+
+use libchipolata::chip8::{Interpreter, InterpreterError, MAX_ROM_SIZE};
+
+#[path = "../fuzz/harness.rs"]
+mod harness;
+
+const DEFAULT_DURATION: u64 = 1;
+
+fn duration() -> Duration {
+    let seconds = env::var("CHIPOLATA_FUZZ_SECONDS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_DURATION);
+    Duration::from_secs(seconds)
+}
+
+fn random_input(rng: &mut ChaCha8Rng) -> Vec<u8> {
+    // Most inputs fit in RAM, a few are too large.
+    let len = rng.random_range(0..MAX_ROM_SIZE + 64);
+    let mut input = vec![0; len];
+    rng.fill(&mut input[..]);
+    input
+}
+
+#[test]
+fn fuzz_cpu() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let start = Instant::now();
+    while start.elapsed() < duration() {
+        harness::run_cpu(&random_input(&mut rng));
+    }
+}
+
+#[test]
+fn fuzz_rom() {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let start = Instant::now();
+    while start.elapsed() < duration() {
+        harness::run_rom(&random_input(&mut rng));
+    }
+}
+
+fn run(rom: &[u8], steps: usize) -> Result<Interpreter, InterpreterError> {
+    let mut interpreter = Interpreter::with_seed(rom.to_vec(), 0)?;
+    for _ in 0..steps {
+        interpreter.step()?;
+    }
+    Ok(interpreter)
+}
+
+#[test]
+fn rom_too_large() {
+    let rom = vec![0; MAX_ROM_SIZE + 1];
+    assert_eq!(
+        Interpreter::new(rom).err(),
+        Some(InterpreterError::RomTooLarge(MAX_ROM_SIZE + 1))
+    );
+    assert!(Interpreter::new(vec![0x12, 0x00]).is_ok());
+}
+
+#[test]
+fn memory_wraps_around() {
+    // I = 0xFFF, V0..VF = *I, *I = V0..VF, then draw 15 rows at I.
+    let rom = [0xAF, 0xFF, 0xFF, 0x65, 0xFF, 0x55, 0xD0, 0x1F, 0x12, 0x00];
+    assert!(run(&rom, 100).is_ok());
+}
+
+#[test]
+fn stack_overflow() {
+    // 0x200: call 0x200
+    let result = run(&[0x22, 0x00], 17);
+    assert_eq!(
+        result.err(),
+        Some(InterpreterError::StackOverflow { addr: 0x200 })
+    );
+}
+
+#[test]
+fn stack_underflow() {
+    let result = run(&[0x00, 0xEE], 1);
+    assert_eq!(
+        result.err(),
+        Some(InterpreterError::StackUnderflow { addr: 0x200 })
+    );
+}
+
+#[test]
+fn unsupported_opcode() {
+    let mut interpreter = Interpreter::with_seed(vec![0x60, 0x01, 0xE0, 0xFF], 0).unwrap();
+    interpreter.step().unwrap();
+    let error = InterpreterError::UnsupportedOpcode {
+        opcode: 0xE0FF,
+        addr: 0x202,
+    };
+    assert_eq!(interpreter.step(), Err(error));
+    // The interpreter stays on the faulty instruction.
+    assert_eq!(interpreter.get_pc(), 0x202);
+    assert_eq!(interpreter.step(), Err(error));
+}
+
+#[test]
+fn keys_above_0xf() {
+    // V0 = 0xFF, skip if key V0 is pressed.
+    assert!(run(&[0x60, 0xFF, 0xE0, 0x9E, 0xE0, 0xA1, 0x12, 0x00], 10).is_ok());
+}
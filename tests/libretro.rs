@@ -0,0 +1,181 @@
+// Drives the libretro core like a frontend would (see also `examples/libretro_host.rs`, which
+// loads the built library instead).
+#![cfg(feature = "libretro")]
+
+use std::os::raw::{c_char, c_uint, c_void};
+use std::ptr;
+use std::sync::Mutex;
+
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::{Snapshot, HEIGHT, WIDTH};
+use libchipolata::libretro::*;
+
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_PIXEL_FORMAT_XRGB8888: c_uint = 1;
+const RETRO_MEMORY_SYSTEM_RAM: c_uint = 2;
+const RETRO_DEVICE_ID_JOYPAD_A: c_uint = 8;
+
+// Draws a pixel at (0, 0) then copies `value` to `copy` in a loop, and counts the instructions
+// run while key 5 (A) is pressed in V2.
+const PROGRAM: &str = "
+        LD I, dot
+        DRW V3, V3, 1
+    loop:
+        LD I, value
+        LD V0, [I]
+        LD I, copy
+        LD [I], V0
+        LD V1, 05
+        SKNP V1
+        ADD V2, 01
+        JP loop
+    dot:
+        DB 80
+    value:
+        DB 00
+    copy:
+        DB 00
+";
+const VALUE: usize = 0x215;
+const COPY: usize = 0x216;
+
+struct Host {
+    pixel_format: Option<c_uint>,
+    video: Vec<u32>,
+    audio_frames: usize,
+    pressed: bool,
+}
+
+static HOST: Mutex<Host> = Mutex::new(Host {
+    pixel_format: None,
+    video: Vec::new(),
+    audio_frames: 0,
+    pressed: false,
+});
+
+unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match cmd {
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT => {
+            HOST.lock().unwrap().pixel_format = Some(*(data as *const c_uint));
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn video_refresh(
+    data: *const c_void,
+    width: c_uint,
+    height: c_uint,
+    pitch: usize,
+) {
+    let mut host = HOST.lock().unwrap();
+    host.video.clear();
+    for y in 0..height as usize {
+        let row = (data as *const u8).add(y * pitch) as *const u32;
+        host.video
+            .extend_from_slice(std::slice::from_raw_parts(row, width as usize));
+    }
+}
+
+unsafe extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+    HOST.lock().unwrap().audio_frames += frames;
+    frames
+}
+
+unsafe extern "C" fn input_poll() {}
+
+unsafe extern "C" fn input_state(port: c_uint, _device: c_uint, _index: c_uint, id: c_uint) -> i16 {
+    (port == 0 && id == RETRO_DEVICE_ID_JOYPAD_A && HOST.lock().unwrap().pressed) as i16
+}
+
+fn game(rom: &[u8]) -> RetroGameInfo {
+    RetroGameInfo {
+        path: ptr::null(),
+        data: rom.as_ptr() as *const c_void,
+        size: rom.len(),
+        meta: ptr::null::<c_char>(),
+    }
+}
+
+fn serialize() -> Vec<u8> {
+    let mut state = vec![0; retro_serialize_size()];
+    assert!(unsafe { retro_serialize(state.as_mut_ptr() as *mut c_void, state.len()) });
+    state
+}
+
+// Runs frames while pressing A during the first `pressed` ones.
+fn run(frames: usize, pressed: usize) {
+    for n in 0..frames {
+        HOST.lock().unwrap().pressed = n < pressed;
+        retro_run();
+    }
+}
+
+// The core is global, so everything is tested in a single test.
+#[test]
+fn core() {
+    assert_eq!(retro_api_version(), 1);
+    assert!(retro_get_memory_data(RETRO_MEMORY_SYSTEM_RAM).is_null());
+    assert_eq!(retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM), 0);
+
+    retro_set_environment(environment);
+    retro_set_video_refresh(video_refresh);
+    retro_set_audio_sample_batch(audio_sample_batch);
+    retro_set_input_poll(input_poll);
+    retro_set_input_state(input_state);
+    retro_init();
+
+    let rom = assemble(PROGRAM).unwrap();
+    unsafe {
+        assert!(!retro_load_game(ptr::null()));
+        assert!(!retro_load_game(&game(&[])));
+        assert!(retro_load_game(&game(&rom)));
+    }
+    assert_eq!(
+        HOST.lock().unwrap().pixel_format,
+        Some(RETRO_PIXEL_FORMAT_XRGB8888)
+    );
+
+    // A frame of video and 1/60 s of audio.
+    retro_run();
+    {
+        let host = HOST.lock().unwrap();
+        assert_eq!(host.video.len(), WIDTH * HEIGHT);
+        assert_eq!(host.video[..2], [0xFFFFFF, 0x000000]);
+        assert_eq!(host.audio_frames, 735);
+    }
+
+    // The frontend can read and write the RAM between frames.
+    assert_eq!(retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM), 0x1000);
+    let ram = retro_get_memory_data(RETRO_MEMORY_SYSTEM_RAM) as *mut u8;
+    assert!(!ram.is_null());
+    unsafe { *ram.add(VALUE) = 0x42 };
+    retro_run();
+    assert_eq!(unsafe { *ram.add(COPY) }, 0x42);
+
+    // Restoring a state replays the same frames.
+    assert_eq!(retro_serialize_size(), Snapshot::SIZE);
+    let mut short = vec![0; Snapshot::SIZE - 1];
+    assert!(!unsafe { retro_serialize(short.as_mut_ptr() as *mut c_void, short.len()) });
+    let state = serialize();
+    run(10, 5);
+    let expected = serialize();
+    assert_ne!(expected, state);
+    unsafe {
+        assert!(!retro_unserialize(state.as_ptr() as *const c_void, 10));
+        assert!(retro_unserialize(
+            state.as_ptr() as *const c_void,
+            state.len()
+        ));
+    }
+    run(10, 5);
+    assert_eq!(serialize(), expected);
+
+    retro_reset();
+    assert_eq!(unsafe { *ram.add(COPY) }, 0x00);
+
+    retro_unload_game();
+    assert!(retro_get_memory_data(RETRO_MEMORY_SYSTEM_RAM).is_null());
+    retro_deinit();
+}
@@ -0,0 +1,110 @@
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::movie::{Movie, MovieError};
+use libchipolata::chip8::{Interpreter, Quirks};
+
+// Counts the frames where a random key is pressed, so that the final state depends on both the
+// inputs and the RNG.
+const PROGRAM: &str = "
+    loop:
+        RND V1, 0F
+        SKNP V1
+        ADD V2, 01
+        ADD V3, V1
+        JP loop
+";
+
+const SPEED: u8 = 9;
+
+fn keypad(frame: usize) -> [bool; 16] {
+    let mut keypad = [false; 16];
+    keypad[frame % 16] = true;
+    keypad[(frame / 3) % 16] = !frame.is_multiple_of(5);
+    keypad
+}
+
+#[test]
+fn round_trip() {
+    let rom = assemble(PROGRAM).unwrap();
+    let mut movie = Movie::new(&rom, Quirks::schip(), 42, SPEED);
+    for frame in 0..1000 {
+        // Long runs of the same state are split in several runs in the file.
+        movie.record_frame(keypad(frame / 100));
+    }
+    for _ in 0..70_000 {
+        movie.record_frame([false; 16]);
+    }
+
+    let decoded = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(decoded, movie);
+    assert_eq!(decoded.len(), 71_000);
+    assert_eq!(decoded.frame(250), Some(keypad(2)));
+    assert!(decoded.matches_rom(&rom));
+}
+
+#[test]
+fn replay() {
+    let rom = assemble(PROGRAM).unwrap();
+    let mut interpreter = Interpreter::with_seed(rom.clone(), 7).unwrap();
+    interpreter.set_quirks(Quirks::chip8());
+    let mut movie = Movie::for_interpreter(&rom, &interpreter, SPEED);
+
+    for frame in 0..600 {
+        movie.record_frame(keypad(frame));
+        interpreter.update_keypad(keypad(frame));
+        interpreter.run_frame(SPEED).unwrap();
+    }
+
+    // The movie is played back by a new interpreter, from the file.
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    let mut replayed = movie.interpreter(rom).unwrap();
+    for n in 0..movie.len() {
+        replayed.update_keypad(movie.frame(n).unwrap());
+        replayed.run_frame(movie.speed).unwrap();
+    }
+
+    assert_eq!(
+        replayed.save_state().to_bytes(),
+        interpreter.save_state().to_bytes()
+    );
+}
+
+#[test]
+fn rom_mismatch() {
+    let rom = assemble(PROGRAM).unwrap();
+    let movie = Movie::new(&rom, Quirks::default(), 0, SPEED);
+
+    assert!(matches!(
+        movie.interpreter(vec![0x12, 0x00]),
+        Err(MovieError::RomMismatch)
+    ));
+}
+
+#[test]
+fn malformed() {
+    let rom = assemble(PROGRAM).unwrap();
+    let mut movie = Movie::new(&rom, Quirks::default(), 0, SPEED);
+    movie.record_frame([false; 16]);
+    let bytes = movie.to_bytes();
+
+    assert_eq!(Movie::from_bytes(b"NOPE"), Err(MovieError::InvalidMagic));
+    assert_eq!(
+        Movie::from_bytes(&bytes[..0x20]),
+        Err(MovieError::Truncated)
+    );
+    assert_eq!(
+        Movie::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(MovieError::Truncated)
+    );
+
+    // A file that declares one frame but holds a run of 65535 frames: the run is checked against
+    // the declared length before it is expanded.
+    let mut huge = bytes[..bytes.len() - 4].to_vec();
+    huge.extend_from_slice(&[0, 0, 0xFF, 0xFF]);
+    assert_eq!(Movie::from_bytes(&huge), Err(MovieError::Truncated));
+
+    // A file that declares 2^32 - 1 frames but holds a single one: nothing is allocated for the
+    // declared length.
+    let mut huge = bytes.clone();
+    huge[0x22..0x26].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(Movie::from_bytes(&huge), Err(MovieError::Truncated));
+}
@@ -0,0 +1,53 @@
+use libchipolata::chip8::{Palette, PALETTES};
+
+#[test]
+fn presets() {
+    assert_eq!(Palette::preset("default"), Some(Palette::default()));
+    assert_eq!(
+        Palette::preset("AMBER"),
+        Some(Palette::new(0x1A1000, 0xFFB000))
+    );
+    assert_eq!(Palette::preset("nope"), None);
+    for (name, palette) in PALETTES {
+        assert_eq!(Palette::preset(name), Some(*palette));
+    }
+}
+
+#[test]
+fn planes() {
+    let palette = Palette::preset("octo").unwrap();
+
+    // Index 1 is the first plane, 2 the second plane and 3 both planes. The other bits of a
+    // pixel are ignored.
+    assert_eq!(palette.color(0), 0x996600);
+    assert_eq!(palette.color(1), 0xFFCC00);
+    assert_eq!(palette.color(2), 0xFF6600);
+    assert_eq!(palette.color(3), 0x662200);
+    assert_eq!(palette.color(0x81), 0xFFCC00);
+
+    // A two-color palette draws both planes with the foreground color.
+    let mut palette = Palette::new(0x000000, 0xFFFFFF);
+    palette.set_foreground(0x41FF00);
+    palette.set_background(0x0C1A0C);
+    assert_eq!(palette.colors, [0x0C1A0C, 0x41FF00, 0x41FF00, 0x41FF00]);
+}
+
+#[test]
+fn render() {
+    let palette = Palette::preset("lcd").unwrap();
+    let vram = [0, 1, 2, 3];
+
+    let mut framebuffer = [0; 4];
+    palette.render(&vram, &mut framebuffer);
+    assert_eq!(framebuffer, [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F]);
+
+    let mut framebuffer = [0; 16];
+    palette.render_rgba(&vram, &mut framebuffer);
+    assert_eq!(
+        framebuffer,
+        [
+            0x9B, 0xBC, 0x0F, 0xFF, 0x0F, 0x38, 0x0F, 0xFF, 0x30, 0x62, 0x30, 0xFF, 0x8B, 0xAC,
+            0x0F, 0xFF
+        ]
+    );
+}
@@ -0,0 +1,132 @@
+use libchipolata::chip8::patch::{self, PatchError};
+use proptest::prelude::*;
+
+const SPACE_INVADERS: &[u8] = include_bytes!("../docs/space-invaders.ch8");
+
+// Appends the checksums of a BPS patch whose body (header and actions) is `body`.
+fn with_checksums(body: &[u8], source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = body.to_vec();
+    patch.extend_from_slice(&patch::crc32(source).to_le_bytes());
+    patch.extend_from_slice(&patch::crc32(target).to_le_bytes());
+    let crc = patch::crc32(&patch);
+    patch.extend_from_slice(&crc.to_le_bytes());
+
+    patch
+}
+
+#[test]
+fn crc32() {
+    assert_eq!(patch::crc32(b"123456789"), 0xCBF43926);
+}
+
+#[test]
+fn ips() {
+    let mut fixed = SPACE_INVADERS.to_vec();
+    fixed[0x10] = 0xFF;
+    fixed[0x100..0x104].copy_from_slice(&[1, 2, 3, 4]);
+
+    let ips = patch::create_ips(SPACE_INVADERS, &fixed);
+    assert!(ips.starts_with(b"PATCH"));
+    assert!(ips.ends_with(b"EOF"));
+    assert_eq!(patch::apply(SPACE_INVADERS, &ips).unwrap(), fixed);
+}
+
+#[test]
+fn ips_rle_and_truncation() {
+    let mut ips = b"PATCH".to_vec();
+    // 4 times 0xAA at offset 2, then truncate to 5 bytes.
+    ips.extend_from_slice(&[0, 0, 2, 0, 0, 0, 4, 0xAA]);
+    ips.extend_from_slice(b"EOF");
+    ips.extend_from_slice(&[0, 0, 5]);
+
+    assert_eq!(
+        patch::apply(&[0; 8], &ips).unwrap(),
+        vec![0, 0, 0xAA, 0xAA, 0xAA]
+    );
+    assert_eq!(
+        patch::apply(&[0; 8], &ips[..10]),
+        Err(PatchError::InvalidPatch)
+    );
+}
+
+#[test]
+fn bps() {
+    let mut fixed = SPACE_INVADERS.to_vec();
+    fixed[0x10] = 0xFF;
+    fixed.extend_from_slice(&[0x12, 0x00]);
+
+    let bps = patch::create_bps(SPACE_INVADERS, &fixed);
+    assert!(bps.starts_with(b"BPS1"));
+    assert_eq!(patch::apply(SPACE_INVADERS, &bps).unwrap(), fixed);
+}
+
+#[test]
+fn bps_checksums() {
+    let mut fixed = SPACE_INVADERS.to_vec();
+    fixed[0x10] = 0xFF;
+    let bps = patch::create_bps(SPACE_INVADERS, &fixed);
+
+    // Another ROM of the same size.
+    let mut other = SPACE_INVADERS.to_vec();
+    other[0x20] ^= 1;
+    assert_eq!(patch::apply(&other, &bps), Err(PatchError::SourceChecksum));
+
+    let mut corrupted = bps.clone();
+    corrupted[8] ^= 1;
+    assert_eq!(
+        patch::apply(SPACE_INVADERS, &corrupted),
+        Err(PatchError::PatchChecksum)
+    );
+
+    // A patch with a valid checksum but the wrong target checksum.
+    let body = &bps[..bps.len() - 12];
+    let wrong = with_checksums(body, SPACE_INVADERS, SPACE_INVADERS);
+    assert_eq!(
+        patch::apply(SPACE_INVADERS, &wrong),
+        Err(PatchError::TargetChecksum)
+    );
+
+    assert_eq!(
+        patch::apply(SPACE_INVADERS, b"NOPE"),
+        Err(PatchError::UnknownFormat)
+    );
+}
+
+#[test]
+fn bps_copies() {
+    let source = [1, 2, 3, 4];
+    let target = [3, 4, 1, 2, 9, 9, 9, 9];
+    let mut body = b"BPS1".to_vec();
+    body.extend_from_slice(&[
+        0x84, // source size: 4
+        0x88, // target size: 8
+        0x80, // no metadata
+        0x86, // source copy of 2 bytes...
+        0x84, // ...from offset +2: 3, 4
+        0x86, // source copy of 2 bytes...
+        0x89, // ...from offset -4: 1, 2
+        0x81, // target read of 1 byte...
+        0x09, // ...9
+        0x8B, // target copy of 3 bytes...
+        0x88, // ...from offset +4, overlapping the bytes it writes: 9, 9, 9
+    ]);
+
+    assert_eq!(
+        patch::apply(&source, &with_checksums(&body, &source, &target)).unwrap(),
+        target
+    );
+}
+
+proptest! {
+    #[test]
+    fn round_trips(
+        original in prop::collection::vec(any::<u8>(), 0..256),
+        modified in prop::collection::vec(any::<u8>(), 0..256),
+    ) {
+        let ips = patch::create_ips(&original, &modified);
+        prop_assert_eq!(patch::apply(&original, &ips).unwrap(), modified.clone());
+
+        let bps = patch::create_bps(&original, &modified);
+        prop_assert_eq!(patch::apply(&original, &bps).unwrap(), modified);
+    }
+}
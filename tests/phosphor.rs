@@ -0,0 +1,64 @@
+use libchipolata::chip8::{Palette, Phosphor, HEIGHT, WIDTH};
+
+fn frame(on: bool) -> Vec<u8> {
+    let mut vram = vec![0; WIDTH * HEIGHT];
+    vram[0] = on as u8;
+    vram
+}
+
+#[test]
+fn decay() {
+    let mut phosphor = Phosphor::new(0.5);
+    phosphor.update(&frame(true));
+    assert_eq!(phosphor.get_intensities()[0], 1.0);
+    assert!(!phosphor.is_fading());
+
+    // The intensity halves at each frame, until it is below 1/256 after 9 frames.
+    for n in 1..=8 {
+        phosphor.update(&frame(false));
+        assert_eq!(phosphor.get_intensities()[0], 0.5f32.powi(n));
+        assert!(phosphor.is_fading());
+    }
+    phosphor.update(&frame(false));
+    assert_eq!(phosphor.get_intensities()[0], 0.0);
+    assert!(!phosphor.is_fading());
+
+    // A pixel turned on again is at full intensity at once.
+    phosphor.update(&frame(true));
+    assert_eq!(phosphor.get_intensities()[0], 1.0);
+}
+
+#[test]
+fn no_decay() {
+    let mut phosphor = Phosphor::new(0.0);
+    phosphor.update(&frame(true));
+    phosphor.update(&frame(false));
+
+    assert_eq!(phosphor.get_intensities()[0], 0.0);
+    assert!(!phosphor.is_fading());
+}
+
+#[test]
+fn render() {
+    let palette = Palette::new(0x000000, 0xFFFFFF);
+    let mut phosphor = Phosphor::new(0.5);
+    let mut framebuffer = vec![0; WIDTH * HEIGHT];
+
+    phosphor.update(&frame(true));
+    phosphor.render(&palette, &mut framebuffer);
+    assert_eq!(framebuffer[0], 0xFFFFFF);
+    assert_eq!(framebuffer[1], 0x000000);
+
+    // The color of a fading pixel is blended with the background.
+    phosphor.update(&frame(false));
+    phosphor.render(&palette, &mut framebuffer);
+    assert_eq!(framebuffer[0], 0x808080);
+
+    let mut rgba = vec![0; WIDTH * HEIGHT * 4];
+    phosphor.render_rgba(&palette, &mut rgba);
+    assert_eq!(rgba[..8], [0x80, 0x80, 0x80, 0xFF, 0x00, 0x00, 0x00, 0xFF]);
+
+    phosphor.reset();
+    phosphor.render(&palette, &mut framebuffer);
+    assert_eq!(framebuffer[0], 0x000000);
+}
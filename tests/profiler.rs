@@ -0,0 +1,108 @@
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::profiler::Subroutine;
+use libchipolata::chip8::symbols::Symbols;
+use libchipolata::chip8::Interpreter;
+
+// Calls `sub` 3 times, which calls `inner`, then loops forever at `end`.
+const PROGRAM: &str = "
+        LD V0, 03
+    loop:
+        CALL sub
+        ADD V0, FF
+        SE V0, 00
+        JP loop
+    end:
+        JP end
+    sub:
+        CALL inner
+        RET
+    inner:
+        ADD V1, 01
+        RET
+";
+
+// 1 + 3 * 7 + 2 instructions until `end`, then 6 times at `end`.
+const STEPS: usize = 30;
+
+fn profile() -> Interpreter {
+    let rom = assemble(PROGRAM).unwrap();
+    let mut interpreter = Interpreter::with_seed(rom, 0).unwrap();
+    interpreter.set_profiling(true);
+    for _ in 0..STEPS {
+        interpreter.step().unwrap();
+    }
+    interpreter
+}
+
+#[test]
+fn hot_spots() {
+    let interpreter = profile();
+    let profile = interpreter.get_profile().unwrap();
+
+    assert_eq!(profile.get_total(), STEPS as u64);
+    assert_eq!(profile.get_count(0x208), 2);
+    assert_eq!(profile.get_count(0x214), 0);
+    assert_eq!(
+        profile.hot_spots(),
+        vec![
+            (0x20A, 6),
+            (0x202, 3),
+            (0x204, 3),
+            (0x206, 3),
+            (0x20C, 3),
+            (0x20E, 3),
+            (0x210, 3),
+            (0x212, 3),
+            (0x208, 2),
+            (0x200, 1),
+        ]
+    );
+
+    let report = profile.report(&interpreter, &Symbols::default(), 1);
+    assert!(report.starts_with("30 instructions\n"));
+    assert!(report.contains("0x20A    120A    JP 020A"), "{}", report);
+}
+
+#[test]
+fn subroutines() {
+    let interpreter = profile();
+    let profile = interpreter.get_profile().unwrap();
+
+    // The calls are counted in the caller, the returns in the subroutine.
+    assert_eq!(
+        profile.subroutines(),
+        vec![
+            Subroutine {
+                addr: 0x20C,
+                calls: 3,
+                cycles: 12,
+                self_cycles: 6,
+            },
+            Subroutine {
+                addr: 0x210,
+                calls: 3,
+                cycles: 6,
+                self_cycles: 6,
+            },
+        ]
+    );
+    assert_eq!(
+        profile.folded(&Symbols::default()),
+        "main 18\nmain;0x20C 6\nmain;0x20C;0x210 6\n"
+    );
+}
+
+#[test]
+fn frames() {
+    // Profiling works the same with the block cache, which is bypassed.
+    let rom = assemble(PROGRAM).unwrap();
+    let mut interpreter = Interpreter::with_seed(rom, 0).unwrap();
+    interpreter.set_block_cache(true);
+    interpreter.set_profiling(true);
+    interpreter.run_frame(STEPS as u8).unwrap();
+
+    assert_eq!(
+        interpreter.get_profile().unwrap().hot_spots(),
+        profile().get_profile().unwrap().hot_spots()
+    );
+}
@@ -0,0 +1,658 @@
+// Differential testing of the interpreter against a reference model. The model below implements
+// the CHIP-8 instructions (with the quirks) independently of `CPU::execute()` and in the most
+// straightforward way, so that refactoring the interpreter cannot change its behavior unnoticed.
+//
+// Both implementations start from the same random state, which is shared as a serialized snapshot
+// (see `Snapshot::to_bytes()`), execute the same instructions and must end up with exactly the
+// same snapshot.
+
+use proptest::prelude::*;
+use proptest::sample::select;
+use proptest::strategy::ValueTree;
+use proptest::test_runner::TestRunner;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use libchipolata::chip8::{Interpreter, InterpreterError, Snapshot, HEIGHT, WIDTH};
+
+const RAM_SIZE: usize = 0x1000;
+const FONT_ADDR: u16 = 0x050;
+
+// The layout of a serialized snapshot, used to name the fields that differ.
+const FIELDS: &[(&str, usize)] = &[
+    ("header", 4),
+    ("RAM", RAM_SIZE),
+    ("VRAM", WIDTH * HEIGHT),
+    ("V", 16),
+    ("I", 2),
+    ("PC", 2),
+    ("SP", 1),
+    ("delay timer", 1),
+    ("sound timer", 1),
+    ("stack", 32),
+    ("keypad", 2),
+    ("waiting for a key", 1),
+    ("key register", 1),
+    ("audio pattern", 17),
+    ("pitch", 1),
+    ("quirks", 1),
+    ("seed", 8),
+    ("RNG", 56),
+];
+
+#[derive(Clone)]
+struct Machine {
+    ram: Vec<u8>,
+    pixels: Vec<bool>,
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    sp: u8,
+    delay: u8,
+    sound: u8,
+    stack: [u16; 16],
+    keys: u16,
+    waiting: bool,
+    key_register: u8,
+    pattern: Option<[u8; 16]>,
+    pitch: u8,
+    quirks: u8,
+    seed: u64,
+    rng: ChaCha8Rng,
+    redraw: bool,
+}
+
+// The state is large, only the registers are printed when a test fails.
+impl std::fmt::Debug for Machine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pc = self.pc as usize % RAM_SIZE;
+        write!(
+            f,
+            "opcode={:02X}{:02X} v={:02X?} i={:04X} pc={:04X} sp={} stack={:04X?} keys={:04X} \
+            waiting={} key_register={} quirks={:05b}",
+            self.ram[pc],
+            self.ram[(pc + 1) % RAM_SIZE],
+            self.v,
+            self.i,
+            self.pc,
+            self.sp,
+            self.stack,
+            self.keys,
+            self.waiting,
+            self.key_register,
+            self.quirks
+        )
+    }
+}
+
+impl Machine {
+    fn quirk(&self, bit: u8) -> bool {
+        self.quirks & (1 << bit) != 0
+    }
+
+    fn shift_vy(&self) -> bool {
+        self.quirk(0)
+    }
+
+    fn load_store_increment_i(&self) -> bool {
+        self.quirk(1)
+    }
+
+    fn jump_vx(&self) -> bool {
+        self.quirk(2)
+    }
+
+    fn vf_reset(&self) -> bool {
+        self.quirk(3)
+    }
+
+    fn clip_sprites(&self) -> bool {
+        self.quirk(4)
+    }
+
+    fn read(&self, addr: usize) -> u8 {
+        self.ram[addr % RAM_SIZE]
+    }
+
+    fn write(&mut self, addr: usize, value: u8) {
+        self.ram[addr % RAM_SIZE] = value;
+    }
+
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn step(&mut self, keys: u16) -> Result<(), InterpreterError> {
+        self.redraw = false;
+        self.keys = keys;
+
+        // FX0A waits for a key, the program counter already points to the next instruction.
+        if self.waiting {
+            if let Some(key) = (0..16).find(|key| keys & (1 << key) != 0) {
+                self.waiting = false;
+                self.v[self.key_register as usize] = key as u8;
+            }
+            return Ok(());
+        }
+
+        // The program counter is 16-bit but wraps around at the end of the RAM when fetching.
+        let addr = self.pc % RAM_SIZE as u16;
+        let opcode = u16::from_be_bytes([self.read(addr as usize), self.read(addr as usize + 1)]);
+        self.pc = addr + 2;
+
+        let result = self.execute(opcode, addr);
+        // A failing instruction is executed again at the next step.
+        if result.is_err() {
+            self.pc = addr;
+        }
+        result
+    }
+
+    fn execute(&mut self, opcode: u16, addr: u16) -> Result<(), InterpreterError> {
+        let nibbles = (
+            (opcode >> 12) as u8,
+            ((opcode >> 8) & 0xF) as usize,
+            ((opcode >> 4) & 0xF) as usize,
+            (opcode & 0xF) as u8,
+        );
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        let unsupported = Err(InterpreterError::UnsupportedOpcode { opcode, addr });
+
+        match nibbles {
+            // Only the last nibble of 0NNN instructions is decoded.
+            (0x0, _, _, 0x0) => {
+                self.pixels.iter_mut().for_each(|pixel| *pixel = false);
+                self.redraw = true;
+            }
+            (0x0, _, _, 0xE) => {
+                if self.sp == 0 {
+                    return Err(InterpreterError::StackUnderflow { addr });
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp as usize];
+            }
+            (0x0, _, _, _) => return unsupported,
+            (0x1, _, _, _) => self.pc = nnn,
+            (0x2, _, _, _) => {
+                if self.sp == 16 {
+                    return Err(InterpreterError::StackOverflow { addr });
+                }
+                self.stack[self.sp as usize] = self.pc;
+                self.sp += 1;
+                self.pc = nnn;
+            }
+            (0x3, x, _, _) => self.skip_if(self.v[x] == nn),
+            (0x4, x, _, _) => self.skip_if(self.v[x] != nn),
+            // The last nibble of 5XY0 and 9XY0 is ignored.
+            (0x5, x, y, _) => self.skip_if(self.v[x] == self.v[y]),
+            (0x6, x, _, _) => self.v[x] = nn,
+            (0x7, x, _, _) => self.v[x] = self.v[x].wrapping_add(nn),
+            (0x8, x, y, 0x0) => self.v[x] = self.v[y],
+            (0x8, x, y, n @ 0x1..=0x3) => {
+                self.v[x] = match n {
+                    0x1 => self.v[x] | self.v[y],
+                    0x2 => self.v[x] & self.v[y],
+                    _ => self.v[x] ^ self.v[y],
+                };
+                if self.vf_reset() {
+                    self.v[0xF] = 0;
+                }
+            }
+            (0x8, x, y, 0x4) => {
+                let (result, carry) = self.v[x].overflowing_add(self.v[y]);
+                self.v[x] = result;
+                self.v[0xF] = carry as u8;
+            }
+            // 8XY5 and 8XY7 set VF *before* the subtraction, which then uses the new value of
+            // VF when X or Y is F.
+            (0x8, x, y, 0x5) => {
+                self.v[0xF] = (self.v[x] > self.v[y]) as u8;
+                self.v[x] = self.v[x].wrapping_sub(self.v[y]);
+            }
+            (0x8, x, y, 0x7) => {
+                self.v[0xF] = (self.v[y] >= self.v[x]) as u8;
+                self.v[x] = self.v[y].wrapping_sub(self.v[x]);
+            }
+            (0x8, x, y, n @ (0x6 | 0xE)) => {
+                let value = if self.shift_vy() {
+                    self.v[y]
+                } else {
+                    self.v[x]
+                };
+                if n == 0x6 {
+                    self.v[x] = value >> 1;
+                    self.v[0xF] = value & 0x01;
+                } else {
+                    self.v[x] = value << 1;
+                    self.v[0xF] = value >> 7;
+                }
+            }
+            (0x8, _, _, _) => return unsupported,
+            (0x9, x, y, _) => self.skip_if(self.v[x] != self.v[y]),
+            (0xA, _, _, _) => self.i = nnn,
+            (0xB, x, _, _) => {
+                let base = if self.jump_vx() { self.v[x] } else { self.v[0] };
+                self.pc = base as u16 + nnn;
+            }
+            (0xC, x, _, _) => self.v[x] = self.rng.random::<u8>() & nn,
+            (0xD, x, y, n) => self.draw(self.v[x] as usize, self.v[y] as usize, n as usize),
+            (0xE, x, _, _) => {
+                let pressed = self.keys & (1 << (self.v[x] & 0xF)) != 0;
+                match nn {
+                    0x9E => self.skip_if(pressed),
+                    0xA1 => self.skip_if(!pressed),
+                    _ => return unsupported,
+                }
+            }
+            (0xF, x, _, _) => match nn {
+                0x02 if x == 0 => {
+                    let mut pattern = [0; 16];
+                    for (offset, byte) in pattern.iter_mut().enumerate() {
+                        *byte = self.read(self.i as usize + offset);
+                    }
+                    self.pattern = Some(pattern);
+                }
+                0x07 => self.v[x] = self.delay,
+                0x0A => {
+                    self.waiting = true;
+                    self.key_register = x as u8;
+                }
+                0x15 => self.delay = self.v[x],
+                0x18 => self.sound = self.v[x],
+                // VF is set when I goes past 0xF00.
+                0x1E => {
+                    let sum = self.i as u32 + self.v[x] as u32;
+                    self.i = sum as u16;
+                    self.v[0xF] = (sum > 0xF00) as u8;
+                }
+                0x29 => self.i = FONT_ADDR + self.v[x] as u16 * 5,
+                0x33 => {
+                    let value = self.v[x];
+                    self.write(self.i as usize, value / 100);
+                    self.write(self.i as usize + 1, value / 10 % 10);
+                    self.write(self.i as usize + 2, value % 10);
+                }
+                0x3A => self.pitch = self.v[x],
+                0x55 => {
+                    for r in 0..=x {
+                        self.write(self.i as usize + r, self.v[r]);
+                    }
+                    if self.load_store_increment_i() {
+                        self.i = self.i.wrapping_add(x as u16 + 1);
+                    }
+                }
+                0x65 => {
+                    for r in 0..=x {
+                        self.v[r] = self.read(self.i as usize + r);
+                    }
+                    if self.load_store_increment_i() {
+                        self.i = self.i.wrapping_add(x as u16 + 1);
+                    }
+                }
+                _ => return unsupported,
+            },
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    // Sprites wrap around the edges of the screen, or are clipped with the quirk. Either way, the
+    // starting position always wraps around.
+    fn draw(&mut self, x: usize, y: usize, rows: usize) {
+        let clip = self.clip_sprites();
+        let (x, y) = if clip {
+            (x % WIDTH, y % HEIGHT)
+        } else {
+            (x, y)
+        };
+
+        self.v[0xF] = 0;
+        for row in 0..rows {
+            let byte = self.read(self.i as usize + row);
+            for col in 0..8 {
+                if byte & (0x80 >> col) == 0 || (clip && (x + col >= WIDTH || y + row >= HEIGHT)) {
+                    continue;
+                }
+
+                let pixel = &mut self.pixels[(y + row) % HEIGHT * WIDTH + (x + col) % WIDTH];
+                if *pixel {
+                    self.v[0xF] = 1;
+                }
+                *pixel = !*pixel;
+            }
+        }
+
+        self.redraw = true;
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = b"C8S\x01".to_vec();
+        bytes.extend_from_slice(&self.ram);
+        bytes.extend(self.pixels.iter().map(|pixel| *pixel as u8));
+        bytes.extend_from_slice(&self.v);
+        bytes.extend_from_slice(&self.i.to_le_bytes());
+        bytes.extend_from_slice(&self.pc.to_le_bytes());
+        bytes.extend_from_slice(&[self.sp, self.delay, self.sound]);
+        for addr in self.stack.iter() {
+            bytes.extend_from_slice(&addr.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.keys.to_le_bytes());
+        bytes.extend_from_slice(&[self.waiting as u8, self.key_register]);
+        bytes.push(self.pattern.is_some() as u8);
+        bytes.extend_from_slice(&self.pattern.unwrap_or([0; 16]));
+        bytes.extend_from_slice(&[self.pitch, self.quirks]);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.rng.get_seed());
+        bytes.extend_from_slice(&self.rng.get_stream().to_le_bytes());
+        bytes.extend_from_slice(&self.rng.get_word_pos().to_le_bytes());
+        bytes
+    }
+}
+
+// The interpreter under test, in the same state as `machine`.
+fn interpreter(machine: &Machine) -> Interpreter {
+    let snapshot = Snapshot::from_bytes(&machine.to_bytes()).unwrap();
+    let mut interpreter = Interpreter::with_seed(Vec::new(), 0).unwrap();
+    interpreter.load_state(&snapshot);
+    interpreter
+}
+
+fn keypad(keys: u16) -> [bool; 16] {
+    let mut keypad = [false; 16];
+    for (key, pressed) in keypad.iter_mut().enumerate() {
+        *pressed = keys & (1 << key) != 0;
+    }
+    keypad
+}
+
+// Returns the name of the first field that differs between two snapshots.
+fn first_difference(expected: &[u8], actual: &[u8]) -> Option<&'static str> {
+    let offset = expected.iter().zip(actual).position(|(a, b)| a != b)?;
+    let mut start = 0;
+    FIELDS.iter().find_map(|(name, len)| {
+        start += len;
+        if offset < start {
+            Some(*name)
+        } else {
+            None
+        }
+    })
+}
+
+// Executes a step on both implementations and compares the results.
+fn check_step(
+    machine: &mut Machine,
+    interpreter: &mut Interpreter,
+    keys: u16,
+) -> Result<Result<(), InterpreterError>, TestCaseError> {
+    let expected = machine.step(keys);
+    interpreter.update_keypad(keypad(keys));
+    let actual = interpreter.step();
+
+    prop_assert_eq!(actual, expected);
+    prop_assert_eq!(interpreter.should_redraw(), machine.redraw);
+    // The snapshots only have 16 bits for I and PC, so the registers are compared as well: a value
+    // out of range would otherwise go unnoticed until the state is restored.
+    let registers = &interpreter.cpu.registers;
+    prop_assert_eq!(
+        (registers.v, registers.i, registers.pc, registers.sp),
+        (
+            machine.v,
+            machine.i as usize,
+            machine.pc as usize,
+            machine.sp as usize
+        )
+    );
+    prop_assert_eq!(
+        (registers.delay, registers.sound),
+        (machine.delay, machine.sound)
+    );
+    let state = interpreter.save_state().to_bytes();
+    prop_assert!(
+        state == machine.to_bytes(),
+        "{} differs",
+        first_difference(&machine.to_bytes(), &state).unwrap_or("the size")
+    );
+
+    Ok(expected)
+}
+
+// Opcodes for all the instructions, with random operands where there are `X`s. Unsupported
+// opcodes are generated with completely random values.
+const OPCODES: &[(u16, u16)] = &[
+    (0x00E0, 0x0000),
+    (0x00EE, 0x0000),
+    (0x1000, 0x0FFF),
+    (0x2000, 0x0FFF),
+    (0x3000, 0x0FFF),
+    (0x4000, 0x0FFF),
+    (0x5000, 0x0FF0),
+    (0x6000, 0x0FFF),
+    (0x7000, 0x0FFF),
+    (0x8000, 0x0FF0),
+    (0x8001, 0x0FF0),
+    (0x8002, 0x0FF0),
+    (0x8003, 0x0FF0),
+    (0x8004, 0x0FF0),
+    (0x8005, 0x0FF0),
+    (0x8006, 0x0FF0),
+    (0x8007, 0x0FF0),
+    (0x800E, 0x0FF0),
+    (0x9000, 0x0FF0),
+    (0xA000, 0x0FFF),
+    (0xB000, 0x0FFF),
+    (0xC000, 0x0FFF),
+    (0xD000, 0x0FFF),
+    (0xE09E, 0x0F00),
+    (0xE0A1, 0x0F00),
+    (0xF002, 0x0000),
+    (0xF007, 0x0F00),
+    (0xF00A, 0x0F00),
+    (0xF015, 0x0F00),
+    (0xF018, 0x0F00),
+    (0xF01E, 0x0F00),
+    (0xF029, 0x0F00),
+    (0xF033, 0x0F00),
+    (0xF03A, 0x0F00),
+    (0xF055, 0x0F00),
+    (0xF065, 0x0F00),
+];
+
+fn opcode() -> impl Strategy<Value = u16> {
+    prop_oneof![
+        9 => (select(OPCODES), any::<u16>()).prop_map(|((opcode, mask), bits)| opcode | (bits & mask)),
+        1 => any::<u16>(),
+    ]
+}
+
+// Registers are often equal, small or at their limits, to exercise the edge cases.
+fn register() -> impl Strategy<Value = u8> {
+    prop_oneof![Just(0u8), Just(0xFF), 0u8..16, any::<u8>()]
+}
+
+fn address() -> impl Strategy<Value = u16> {
+    prop_oneof![0x200u16..0x1000, 0xFF0u16..0x1000, any::<u16>()]
+}
+
+prop_compose! {
+    // A random state: the values that matter the most are generated by proptest (so that failures
+    // can be shrunk), the bulk of the state from a seed.
+    fn machine()(
+        v in prop::array::uniform16(register()),
+        i in address(),
+        pc in address(),
+        sp in 0u8..=16,
+        timers in any::<(u8, u8)>(),
+        keys in any::<u16>(),
+        waiting in prop::bool::weighted(0.1),
+        key_register in 0u8..16,
+        quirks in 0u8..32,
+        seed in any::<u64>(),
+    ) -> Machine {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut ram = vec![0; RAM_SIZE];
+        rng.fill(&mut ram[..]);
+        let pixels = (0..WIDTH * HEIGHT).map(|_| rng.random_bool(0.3)).collect();
+        let mut stack = [0; 16];
+        rng.fill(&mut stack[..]);
+        let pattern = if rng.random() { Some(rng.random()) } else { None };
+
+        let mut machine_rng = ChaCha8Rng::from_seed(rng.random());
+        machine_rng.set_stream(rng.random());
+        machine_rng.set_word_pos(rng.random::<u32>() as u128);
+
+        Machine {
+            ram,
+            pixels,
+            v,
+            i,
+            pc,
+            sp,
+            delay: timers.0,
+            sound: timers.1,
+            stack,
+            keys,
+            waiting,
+            key_register,
+            pattern,
+            pitch: rng.random(),
+            quirks,
+            seed: rng.random(),
+            rng: machine_rng,
+            redraw: false,
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    // Executes a single instruction from a random state, with each quirks configuration.
+    #[test]
+    fn single_instruction(machine in machine(), opcode in opcode(), keys in any::<u16>()) {
+        let mut machine = machine;
+        machine.waiting = false;
+        let pc = machine.pc as usize;
+        machine.write(pc, (opcode >> 8) as u8);
+        machine.write(pc + 1, opcode as u8);
+
+        for quirks in 0..32 {
+            let mut machine = machine.clone();
+            machine.quirks = quirks;
+            let mut interpreter = interpreter(&machine);
+            let _ = check_step(&mut machine, &mut interpreter, keys)?;
+        }
+    }
+
+    // Runs a random program for a while, with random key presses.
+    #[test]
+    fn program(
+        machine in machine(),
+        program in prop::collection::vec(opcode(), 64),
+        keys in prop::collection::vec(prop_oneof![Just(0u16), any::<u16>()], 200),
+    ) {
+        let mut machine = machine;
+        machine.pc = 0x200;
+        for (n, opcode) in program.iter().enumerate() {
+            machine.write(0x200 + n * 2, (opcode >> 8) as u8);
+            machine.write(0x200 + n * 2 + 1, *opcode as u8);
+        }
+
+        let mut interpreter = interpreter(&machine);
+        for keys in keys {
+            if check_step(&mut machine, &mut interpreter, keys)?.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+// A random state for the edge cases below.
+fn any_machine() -> Machine {
+    let mut machine = machine()
+        .new_tree(&mut TestRunner::deterministic())
+        .unwrap()
+        .current();
+    machine.waiting = false;
+    machine.pc = 0x200;
+    machine
+}
+
+// I is a 16-bit register: it wraps around when it goes past 0xFFFF.
+#[test]
+fn index_register_overflow() {
+    const LOAD_STORE_INCREMENT_I: u8 = 1 << 1;
+
+    for (opcode, i, quirks, expected) in [
+        (0xF01E, 0xFFF0, 0, 0x0010),
+        (0xF01E, 0xFFFF, 0, 0x001F),
+        (0xF155, 0xFFFE, LOAD_STORE_INCREMENT_I, 0x0000),
+        (0xF265, 0xFFFF, LOAD_STORE_INCREMENT_I, 0x0002),
+    ] {
+        let mut machine = any_machine();
+        machine.write(0x200, (opcode >> 8) as u8);
+        machine.write(0x201, opcode as u8);
+        machine.i = i;
+        machine.v[0] = 0x20;
+        machine.quirks = quirks;
+        let mut interpreter = interpreter(&machine);
+
+        check_step(&mut machine, &mut interpreter, 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(machine.i, expected, "{:04X}", opcode);
+        if opcode == 0xF01E {
+            assert_eq!(machine.v[0xF], 1);
+        }
+    }
+}
+
+// The program counter is stored on 16 bits too.
+#[test]
+fn program_counter_overflow() {
+    // The instruction is fetched at 0xFFE, and PC goes past the end of the RAM.
+    let mut machine = any_machine();
+    machine.pc = 0xFFFE;
+    machine.write(0xFFE, 0x60);
+    machine.write(0xFFF, 0x42);
+    machine.write(0x000, 0x61);
+    machine.write(0x001, 0x24);
+    let mut interpreter = interpreter(&machine);
+
+    check_step(&mut machine, &mut interpreter, 0)
+        .unwrap()
+        .unwrap();
+    assert_eq!(machine.v[0], 0x42);
+    assert_eq!(machine.pc, 0x1000);
+
+    // Then the next instruction is fetched at 0x000.
+    check_step(&mut machine, &mut interpreter, 0)
+        .unwrap()
+        .unwrap();
+    assert_eq!(machine.v[1], 0x24);
+    assert_eq!(machine.pc, 0x0002);
+}
+
+// A key press ends FX0A, and the next instruction is executed at the following step.
+#[test]
+fn wait_for_key() {
+    let mut machine = any_machine();
+    machine.write(0x200, 0xF3);
+    machine.write(0x201, 0x0A);
+    machine.write(0x202, 0x73);
+    machine.write(0x203, 0x01);
+    let mut interpreter = interpreter(&machine);
+
+    for keys in [0, 0, 1 << 5, 0] {
+        check_step(&mut machine, &mut interpreter, keys)
+            .unwrap()
+            .unwrap();
+    }
+    assert!(!machine.waiting);
+    assert_eq!(machine.v[3], 0x06);
+    assert_eq!(machine.pc, 0x204);
+}
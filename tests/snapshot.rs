@@ -0,0 +1,38 @@
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::{Interpreter, Snapshot};
+
+// Adds 0xFF to I until it wraps around: 0xFFF + 241 * 0xFF = 0x1000E, i.e. 0x000E.
+const PROGRAM: &str = "
+        LD I, FFF
+        LD V0, FF
+    loop:
+        ADD I, V0
+        JP loop
+";
+
+#[test]
+fn index_register_overflow() {
+    let rom = assemble(PROGRAM).unwrap();
+    let mut interpreter = Interpreter::with_seed(rom.clone(), 0).unwrap();
+    for _ in 0..2 + 2 * 241 {
+        interpreter.step().unwrap();
+    }
+    assert_eq!(interpreter.cpu.registers.i, 0x000E);
+
+    let bytes = interpreter.save_state().to_bytes();
+    let mut restored = Interpreter::with_seed(rom, 0).unwrap();
+    restored.load_state(&Snapshot::from_bytes(&bytes).unwrap());
+
+    // I is now below 0xF00, so the next ADD clears VF on both interpreters.
+    for _ in 0..2 {
+        interpreter.step().unwrap();
+        restored.step().unwrap();
+    }
+    assert_eq!(restored.cpu.registers.i, interpreter.cpu.registers.i);
+    assert_eq!(
+        restored.save_state().to_bytes(),
+        interpreter.save_state().to_bytes()
+    );
+    assert_eq!(interpreter.get_i(), 0x010D);
+    assert_eq!(unsafe { *interpreter.get_v_ptr().add(0xF) }, 0);
+}
@@ -0,0 +1,90 @@
+use libchipolata::chip8::analysis::Analysis;
+use libchipolata::chip8::assembler::assemble;
+use libchipolata::chip8::sprites::{self, Sprite};
+use libchipolata::chip8::symbols::Symbols;
+use libchipolata::chip8::{Interpreter, Palette};
+
+const PROGRAM: &str = "
+    start:
+        LD I, ship
+        DRW V0, V1, 3
+        LD I, digits
+        ADD I, V2
+        DRW V0, V1, 5
+    loop:
+        JP loop
+    ship:
+        DB 18, 3C, FF
+    digits:
+        DB F0, 90, 90, 90, F0
+";
+
+#[test]
+fn static_analysis() {
+    let rom = assemble(PROGRAM).unwrap();
+
+    // The address of the second sprite is computed, it can only be found at runtime.
+    assert_eq!(Analysis::new(&rom).sprites(), vec![Sprite::new(0x20C, 3)]);
+}
+
+#[test]
+fn coverage() {
+    let rom = assemble(PROGRAM).unwrap();
+    let mut interpreter = Interpreter::with_seed(rom, 0).unwrap();
+    interpreter.set_coverage(true);
+    for _ in 0..6 {
+        interpreter.step().unwrap();
+    }
+
+    let drawn = interpreter.get_coverage().unwrap().sprites();
+    assert_eq!(drawn, vec![Sprite::new(0x20C, 3), Sprite::new(0x20F, 5)]);
+    assert_eq!(
+        Sprite::new(0x20C, 3).to_text(&interpreter),
+        "...##...\n..####..\n########\n"
+    );
+}
+
+#[test]
+fn merge() {
+    let merged = sprites::merge(vec![
+        Sprite::new(0x300, 2),
+        Sprite::new(0x200, 5),
+        Sprite::new(0x300, 4),
+    ]);
+
+    assert_eq!(merged, vec![Sprite::new(0x200, 5), Sprite::new(0x300, 4)]);
+}
+
+#[test]
+fn literals() {
+    let rom = assemble(PROGRAM).unwrap();
+    let interpreter = Interpreter::with_seed(rom, 0).unwrap();
+    let found = vec![Sprite::new(0x20C, 3), Sprite::new(0x20F, 5)];
+    let symbols = Symbols::parse("0x20C ship\n").unwrap();
+
+    let octo = sprites::to_octo(&found, &interpreter, &symbols);
+    assert!(octo.starts_with(": ship # 0x20C, 8x3\n  0x18 # ...##...\n"));
+    assert!(octo.contains(": sprite_20F # 0x20F, 8x5\n"));
+
+    // The assembler literals assemble into the bytes of the sprites.
+    let source = sprites::to_assembler(&found, &interpreter, &symbols);
+    assert_eq!(
+        assemble(&source).unwrap(),
+        vec![0x18, 0x3C, 0xFF, 0xF0, 0x90, 0x90, 0x90, 0xF0]
+    );
+}
+
+#[test]
+fn sheet() {
+    let rom = assemble(PROGRAM).unwrap();
+    let interpreter = Interpreter::with_seed(rom, 0).unwrap();
+    let found: Vec<Sprite> = (0..9).map(|n| Sprite::new(0x20C + n, 3)).collect();
+
+    let png = sprites::sheet_png(&found, &interpreter, &Palette::default(), 2).unwrap();
+    let decoder = png::Decoder::new(png.as_slice());
+    let reader = decoder.read_info().unwrap();
+
+    // 8 sprites per row, in 20x24 cells.
+    assert_eq!(reader.info().width, 8 * 20 * 2);
+    assert_eq!(reader.info().height, 2 * 24 * 2);
+}
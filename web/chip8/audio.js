@@ -1,14 +1,8 @@
-const makeOscillator = (audioContext) => {
-  const oscillator = audioContext.createOscillator();
+// The samples are generated by the interpreter (see `JsInterpreter::fill_audio()`), this only
+// plays them.
+const BUFFER_SIZE = 1024;
 
-  oscillator.type = "sine";
-  oscillator.frequency.value = 400;
-  oscillator.connect(audioContext.destination);
-
-  return oscillator;
-};
-
-export const createAudio = () => {
+export const createAudio = (fill) => {
   if (!window.AudioContext && !window.webkitAudioContext) {
     return null;
   }
@@ -16,19 +10,26 @@ export const createAudio = () => {
   const audioContext = new (window.AudioContext || window.webkitAudioContext)();
 
   return {
-    oscillator: null,
+    sampleRate: audioContext.sampleRate,
+    node: null,
 
     start() {
-      if (!this.oscillator) {
-        this.oscillator = makeOscillator(audioContext);
-        this.oscillator.start();
+      if (!this.node) {
+        audioContext.resume();
+
+        this.node = audioContext.createScriptProcessor(BUFFER_SIZE, 0, 1);
+        this.node.onaudioprocess = (event) => {
+          const output = event.outputBuffer.getChannelData(0);
+          output.set(fill(output.length));
+        };
+        this.node.connect(audioContext.destination);
       }
     },
 
     stop() {
-      if (this.oscillator) {
-        this.oscillator.stop();
-        this.oscillator = null;
+      if (this.node) {
+        this.node.disconnect();
+        this.node = null;
       }
     },
   };
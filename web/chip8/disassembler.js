@@ -99,6 +99,11 @@ const disassembleAddr = (program, addr) => {
       break;
     case 0xf000:
       switch (opcode & 0x00ff) {
+        case 0x02:
+          if (x(opcode) == 0) {
+            instr = `LD AUDIO, [I]`;
+          }
+          break;
         case 0x07:
           instr = `LD V${x(opcode)}, DT`;
           break;
@@ -120,6 +125,9 @@ const disassembleAddr = (program, addr) => {
         case 0x33:
           instr = `LD B, V${x(opcode)}`;
           break;
+        case 0x3a:
+          instr = `LD PITCH, V${x(opcode)}`;
+          break;
         case 0x55:
           instr = `LD [I], V${x(opcode)}`;
           break;
@@ -132,14 +140,43 @@ const disassembleAddr = (program, addr) => {
   return [instr];
 };
 
-export const disassemble = (ram) => {
+// Coverage tags (see `src/chip8/coverage.rs`).
+const EXECUTED = 1 << 0;
+const READ = 1 << 1;
+const DRAWN = 1 << 3;
+
+// Whether the byte has been read or drawn, but never executed.
+const isData = (coverage, addr) =>
+  coverage &&
+  (coverage[addr] & EXECUTED) === 0 &&
+  (coverage[addr] & (READ | DRAWN)) !== 0;
+
+// Bytes that the coverage knows to be data (e.g. sprites) are shown with their pixels instead of
+// being decoded as instructions.
+export const disassemble = (ram, coverage) => {
   const lines = [];
 
-  for (let addr = 0x201; addr < 0x1000; addr += 2) {
-    const [instr] = disassembleAddr(ram, addr);
-    lines.push(
-      `<div class="addr-${addr}">${hexformat(addr, 4)}: ${instr}</div>`
-    );
+  for (let addr = 0x200; addr < 0x1000; ) {
+    if (isData(coverage, addr)) {
+      const pixels = ram[addr]
+        .toString(2)
+        .padStart(8, "0")
+        .replace(/0/g, ".")
+        .replace(/1/g, "#");
+      lines.push(
+        `<div class="addr-${addr} data">${hexformat(addr, 4)}: DB ${hexformat(
+          ram[addr],
+          2
+        )} ${pixels}</div>`
+      );
+      addr += 1;
+    } else {
+      const [instr] = disassembleAddr(ram, addr);
+      lines.push(
+        `<div class="addr-${addr}">${hexformat(addr, 4)}: ${instr}</div>`
+      );
+      addr += 2;
+    }
   }
 
   return lines.join("");
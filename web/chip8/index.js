@@ -5,17 +5,21 @@ import { createAudio } from "./audio";
 import { createDisplay } from "./display";
 import { disassemble } from "./disassembler";
 import { makeKeypad } from "./keypad";
-import { hexformat } from "./utils";
+import { download, hexformat } from "./utils";
 
 const Chip8 = {
   // TODO: retrieve these values via the interpreter instance.
   WIDTH: 64,
   HEIGHT: 32,
+  // The scale of the screenshots and GIFs.
+  CAPTURE_SCALE: 8,
 
-  // TODO: make it configurable.
+  // TODO: make it configurable. Known ROMs use their recommended tickrate.
   speed: 9,
   paused: false,
   muted: true,
+  phosphor: false,
+  recordingGif: false,
   keysPressed: {},
 
   display: null,
@@ -24,12 +28,23 @@ const Chip8 = {
   $pauseBtn: null,
   $muteBtn: null,
   $resetBtn: null,
+  $paletteSelect: null,
+  $phosphorBtn: null,
+  $screenshotBtn: null,
+  $gifBtn: null,
+  $coverageBtn: null,
+  $cheats: null,
+  $cheatsBtn: null,
+  $opcode: null,
   $registers1: null,
   $registers2: null,
   $registers3: null,
 
   interpreter: null,
   v_registers: null,
+  // The coverage changes of the current disassembly, and when it has been rendered.
+  coverageChanges: -1,
+  disassembledAt: 0,
 
   init(_document, _screen) {
     this.display = createDisplay(
@@ -40,11 +55,25 @@ const Chip8 = {
       _screen.height
     );
 
-    this.audio = createAudio();
+    this.audio = createAudio((length) => {
+      if (!this.interpreter) {
+        return new Float32Array(length);
+      }
+
+      return this.interpreter.fill_audio(length, !this.paused);
+    });
 
     this.$pauseBtn = _document.querySelector("#btn-pause");
     this.$muteBtn = _document.querySelector("#btn-mute");
     this.$resetBtn = _document.querySelector("#btn-reset");
+    this.$paletteSelect = _document.querySelector("#select-palette");
+    this.$phosphorBtn = _document.querySelector("#btn-phosphor");
+    this.$screenshotBtn = _document.querySelector("#btn-screenshot");
+    this.$gifBtn = _document.querySelector("#btn-gif");
+    this.$coverageBtn = _document.querySelector("#btn-coverage");
+    this.$cheats = _document.querySelector("#cheats");
+    this.$cheatsBtn = _document.querySelector("#btn-cheats");
+    this.$opcode = _document.querySelector(".opcode .values");
     this.$registers1 = _document.querySelector(".registers .values-1");
     this.$registers2 = _document.querySelector(".registers .values-2");
     this.$registers3 = _document.querySelector(".registers .values-3");
@@ -54,12 +83,31 @@ const Chip8 = {
     this.onPauseClick = this.onPauseClick.bind(this);
     this.onMuteClick = this.onMuteClick.bind(this);
     this.onResetClick = this.onResetClick.bind(this);
+    this.onPaletteChange = this.onPaletteChange.bind(this);
+    this.onPhosphorClick = this.onPhosphorClick.bind(this);
+    this.onScreenshotClick = this.onScreenshotClick.bind(this);
+    this.onGifClick = this.onGifClick.bind(this);
+    this.onCoverageClick = this.onCoverageClick.bind(this);
+    this.onCheatsClick = this.onCheatsClick.bind(this);
+
+    for (const name of libchipolata.palette_names().split(",")) {
+      const $option = _document.createElement("option");
+      $option.value = name;
+      $option.textContent = `palette: ${name}`;
+      this.$paletteSelect.appendChild($option);
+    }
 
     _document.addEventListener("keydown", this.onKeyDown);
     _document.addEventListener("keyup", this.onKeyUp);
     this.$pauseBtn.addEventListener("click", this.onPauseClick);
     this.$muteBtn.addEventListener("click", this.onMuteClick);
     this.$resetBtn.addEventListener("click", this.onResetClick);
+    this.$paletteSelect.addEventListener("change", this.onPaletteChange);
+    this.$phosphorBtn.addEventListener("click", this.onPhosphorClick);
+    this.$screenshotBtn.addEventListener("click", this.onScreenshotClick);
+    this.$gifBtn.addEventListener("click", this.onGifClick);
+    this.$coverageBtn.addEventListener("click", this.onCoverageClick);
+    this.$cheatsBtn.addEventListener("click", this.onCheatsClick);
   },
 
   onKeyDown(event) {
@@ -80,12 +128,122 @@ const Chip8 = {
     this.$muteBtn.classList.toggle("btn-ghost");
     this.$muteBtn.textContent = this.muted ? "mute" : "unmute";
     this.muted = !this.muted;
+
+    if (this.audio) {
+      if (this.muted) {
+        this.audio.stop();
+      } else {
+        this.audio.start();
+      }
+    }
   },
 
   onResetClick() {
     this.interpreter.reset();
   },
 
+  onPaletteChange() {
+    this.interpreter.set_palette(this.$paletteSelect.value);
+    this.draw();
+  },
+
+  onPhosphorClick() {
+    this.$phosphorBtn.classList.toggle("btn-ghost");
+    this.phosphor = !this.phosphor;
+    this.interpreter.set_phosphor(this.phosphor);
+    this.draw();
+  },
+
+  onScreenshotClick() {
+    download(
+      this.interpreter.screenshot_png(this.CAPTURE_SCALE),
+      "image/png",
+      "chipolata.png"
+    );
+  },
+
+  onGifClick() {
+    this.$gifBtn.classList.toggle("btn-ghost");
+
+    if (this.recordingGif) {
+      this.$gifBtn.textContent = "record gif";
+      download(
+        this.interpreter.stop_gif_recording(),
+        "image/gif",
+        "chipolata.gif"
+      );
+    } else {
+      this.$gifBtn.textContent = "stop gif";
+      this.interpreter.start_gif_recording(this.CAPTURE_SCALE);
+    }
+
+    this.recordingGif = !this.recordingGif;
+  },
+
+  onCoverageClick() {
+    download(
+      this.interpreter.coverage_json(),
+      "application/json",
+      "chipolata-coverage.json"
+    );
+  },
+
+  // The cheats are saved in the local storage, per ROM.
+  cheatsKey() {
+    return `chipolata-cheats-${this.interpreter.get_rom_hash()}`;
+  },
+
+  onCheatsClick() {
+    try {
+      this.interpreter.set_cheats(this.$cheats.value);
+    } catch (error) {
+      alert(`Invalid cheats: ${error}`);
+      return;
+    }
+
+    localStorage.setItem(this.cheatsKey(), this.$cheats.value);
+  },
+
+  draw() {
+    this.interpreter.update_framebuffer();
+    this.display.draw(
+      new Uint8ClampedArray(
+        memory.buffer,
+        this.interpreter.get_framebuffer_ptr(),
+        this.WIDTH * this.HEIGHT * 4
+      )
+    );
+  },
+
+  // Renders the disassembly again when the coverage has changed, at most twice per second.
+  updateDisassembly() {
+    const changes = this.interpreter.get_coverage_changes();
+    const now = performance.now();
+    if (
+      changes === this.coverageChanges ||
+      now - this.disassembledAt < 500
+    ) {
+      return;
+    }
+
+    const ram = new Uint8Array(
+      memory.buffer,
+      this.interpreter.get_ram_ptr(),
+      0x1000
+    );
+    const coverage = new Uint8Array(
+      memory.buffer,
+      this.interpreter.get_coverage_ptr(),
+      0x1000
+    );
+
+    const scrollTop = this.$opcode.scrollTop;
+    this.$opcode.innerHTML = disassemble(ram, coverage);
+    this.$opcode.scrollTop = scrollTop;
+    this.coverageChanges = changes;
+    this.disassembledAt = now;
+  },
+
   updateInfo() {
     const pc = this.interpreter.get_pc();
     const i = this.interpreter.get_i();
@@ -130,53 +288,63 @@ const Chip8 = {
 
   run(rom) {
     this.interpreter = new libchipolata.JsInterpreter(rom);
+    if (this.audio) {
+      this.interpreter.set_audio_sample_rate(this.audio.sampleRate);
+    }
+    this.speed = this.interpreter.get_rom_tickrate() || this.speed;
+
+    const title = this.interpreter.get_rom_title();
+    if (title) {
+      document.title = `chipolata - ${title}`;
+    }
     this.v_registers = new Uint8Array(
       memory.buffer,
       this.interpreter.get_v_ptr(),
       16
     );
 
-    const vram = new Uint8Array(
-      memory.buffer,
-      this.interpreter.get_vram_ptr(),
-      this.WIDTH * this.HEIGHT
-    );
+    this.$cheats.value = localStorage.getItem(this.cheatsKey()) || "";
+    this.interpreter.set_cheats(this.$cheats.value);
 
-    const ram = new Uint8Array(
-      memory.buffer,
-      this.interpreter.get_ram_ptr(),
-      0x1000
-    );
-
-    const $opcode = document.querySelector(".opcode .values");
-    $opcode.innerHTML = disassemble(ram);
+    this.coverageChanges = -1;
+    this.disassembledAt = 0;
+    this.updateDisassembly();
 
     const renderLoop = () => {
       if (!this.paused) {
         let redraw = false;
         for (let i = 0; i < this.speed; i++) {
           this.interpreter.update_keypad(makeKeypad(this.keysPressed));
-          this.interpreter.step();
+          try {
+            this.interpreter.step();
+          } catch (error) {
+            // The interpreter stays on the faulty instruction, which can be inspected.
+            console.error(error);
+            this.onPauseClick();
+            break;
+          }
 
           if (this.interpreter.should_redraw()) {
             redraw = true;
           }
         }
 
-        if (redraw) {
-          this.display.draw(vram);
+        // The phosphor filter needs a redraw at each frame while pixels fade out.
+        if (this.interpreter.update_phosphor()) {
+          redraw = true;
         }
 
-        if (!this.muted) {
-          if (this.interpreter.should_beep()) {
-            this.audio.start();
-          } else {
-            this.audio.stop();
-          }
+        if (redraw) {
+          this.draw();
         }
 
         this.interpreter.update_timers();
 
+        if (this.recordingGif) {
+          this.interpreter.add_gif_frame();
+        }
+
+        this.updateDisassembly();
         this.updateInfo();
       }
 
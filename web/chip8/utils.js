@@ -1,3 +1,15 @@
 export const hexformat = (val, size) => {
   return `0x${val.toString(16).padStart(size, "0")}`;
 };
+
+// Saves `bytes` to a file on the user's computer.
+export const download = (bytes, type, filename) => {
+  const url = URL.createObjectURL(new Blob([bytes], { type }));
+
+  const $link = document.createElement("a");
+  $link.href = url;
+  $link.download = filename;
+  $link.click();
+
+  URL.revokeObjectURL(url);
+};